@@ -0,0 +1,215 @@
+// generated by ars, do not edit
+
+#[allow(non_camel_case_types, clippy::upper_case_acronyms, clippy::wrong_self_convention)]
+mod kinds {
+    ars::build_kinds!(Kind, Lit0, Lit1, Lit2, Lit3, Lit4, Lit5, Lit6, NUM, ID, WS, Program, Statement, Expr, Call, Args, List);
+}
+pub use kinds::Kind;
+
+pub fn lexer() -> ars::lexer::Lexer {
+    ars::build_lexer!(
+        ars::token::Token::new_lit("\"+\"", 0, "+"),
+        ars::token::Token::new_lit("\"-\"", 1, "-"),
+        ars::token::Token::new_lit("\"*\"", 2, "*"),
+        ars::token::Token::new_lit("\";\"", 3, ";"),
+        ars::token::Token::new_lit("\"(\"", 4, "("),
+        ars::token::Token::new_lit("\")\"", 5, ")"),
+        ars::token::Token::new_lit("\",\"", 6, ","),
+        ars::token::Token::new_regex_from_str("NUM", 7, "\\d+"),
+        ars::token::Token::new_regex_from_str("ID", 8, "[a-z]+"),
+        ars::token::Token::new_regex_from_str("WS", 9, "\\s+")
+    )
+}
+
+const OPERATORS: &[ars::parser::Operator] = &[
+    ars::parser::Operator::new(0, ars::parser::Fixity::Left, 1),
+    ars::parser::Operator::new(1, ars::parser::Fixity::Left, 1),
+    ars::parser::Operator::new(2, ars::parser::Fixity::Left, 2),
+    ars::parser::Operator::new(1, ars::parser::Fixity::Prefix, 3),
+];
+
+/// parses the whole input with `Program`
+pub fn parse(input: &str) -> ars::parser::ParseResult {
+    let mut lexer = lexer();
+    lexer.begin(input);
+    let tokens = lexer.try_all().map_err(|location| ars::parser::ParseError::InvalidCharacter { location })?;
+    let mut state = ars::parser::ParserState::new(tokens, None).with_memo();
+    let node = parse_program(&mut state)?;
+    match state.lookahead(0) {
+        Some(token) => Err(ars::parser::ParseError::Unexpected { expected: vec![], found: token.clone() }),
+        None => Ok(node),
+    }
+}
+
+/// `Program` as a `Parser`, whose `parse` panics on errors, `try_parse` and `parse_program` return them
+pub struct Program;
+
+impl ars::parser::Parser for Program {
+    fn parse(&self, state: &mut ars::parser::ParserState) -> ars::ast::Node {
+        parse_program(state).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_parse(&self, state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+        parse_program(state)
+    }
+}
+
+pub fn parse_program(state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+    state.rule(10, |state| {
+        let children: ars::parser::ParseResult<Vec<ars::ast::Node>> = (|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {
+            let mut nodes = vec![];
+            nodes.extend(parse_statement(state).map(|node| vec![node])?);
+            nodes.extend(Ok(state.attempt(|state: &mut ars::parser::ParserState| state.expect_any(&[9]).map(|token| vec![ars::ast::Node::from_token(&token)])).unwrap_or_default())?);
+            Ok(nodes)
+        })(state).map(|mut nodes| { nodes.extend(state.many(|state: &mut ars::parser::ParserState| (|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {
+                let mut nodes = vec![];
+                nodes.extend(parse_statement(state).map(|node| vec![node])?);
+                nodes.extend(Ok(state.attempt(|state: &mut ars::parser::ParserState| state.expect_any(&[9]).map(|token| vec![ars::ast::Node::from_token(&token)])).unwrap_or_default())?);
+                Ok(nodes)
+            })(state))); nodes });
+        Ok(ars::grammar::interpreter::shape_node(10, "Program", None, &[9], children?))
+    })
+}
+
+/// `Statement` as a `Parser`, whose `parse` panics on errors, `try_parse` and `parse_statement` return them
+pub struct Statement;
+
+impl ars::parser::Parser for Statement {
+    fn parse(&self, state: &mut ars::parser::ParserState) -> ars::ast::Node {
+        parse_statement(state).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_parse(&self, state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+        parse_statement(state)
+    }
+}
+
+pub fn parse_statement(state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+    state.rule(11, |state| {
+        let children: ars::parser::ParseResult<Vec<ars::ast::Node>> = (|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {
+            let mut nodes = vec![];
+            nodes.extend(parse_expr(state).map(|node| vec![node])?);
+            nodes.extend(state.expect_any(&[3]).map(|token| vec![ars::ast::Node::from_token(&token)]).map(|_| vec![])?);
+            Ok(nodes)
+        })(state);
+        Ok(ars::grammar::interpreter::shape_node(11, "Statement", None, &[], children?))
+    })
+}
+
+/// `Expr` as a `Parser`, whose `parse` panics on errors, `try_parse` and `parse_expr` return them
+pub struct Expr;
+
+impl ars::parser::Parser for Expr {
+    fn parse(&self, state: &mut ars::parser::ParserState) -> ars::ast::Node {
+        parse_expr(state).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_parse(&self, state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+        parse_expr(state)
+    }
+}
+
+pub fn parse_expr(state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+    state.rule(12, |state| {
+        state.pratt(OPERATORS, 12, &|state: &mut ars::parser::ParserState| {
+            let children: ars::parser::ParseResult<Vec<ars::ast::Node>> = state.choice(&[
+                &|state: &mut ars::parser::ParserState| parse_call(state).map(|node| vec![node]),
+                &|state: &mut ars::parser::ParserState| state.expect_any(&[7]).map(|token| vec![ars::ast::Node::from_token(&token)]),
+                &|state: &mut ars::parser::ParserState| (|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {
+                    let mut nodes = vec![];
+                    nodes.extend(state.expect_any(&[4]).map(|token| vec![ars::ast::Node::from_token(&token)]).map(|_| vec![])?);
+                    nodes.extend(parse_expr(state).map(|node| vec![node])?);
+                    nodes.extend(state.expect_any(&[5]).map(|token| vec![ars::ast::Node::from_token(&token)]).map(|_| vec![])?);
+                    Ok(nodes)
+                })(state),
+            ]);
+            Ok(ars::grammar::interpreter::operand_node(12, "Expr", None, &[], children?))
+        })
+    })
+}
+
+/// `Call` as a `Parser`, whose `parse` panics on errors, `try_parse` and `parse_call` return them
+pub struct Call;
+
+impl ars::parser::Parser for Call {
+    fn parse(&self, state: &mut ars::parser::ParserState) -> ars::ast::Node {
+        parse_call(state).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_parse(&self, state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+        parse_call(state)
+    }
+}
+
+pub fn parse_call(state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+    state.rule(13, |state| {
+        let children: ars::parser::ParseResult<Vec<ars::ast::Node>> = (|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {
+            let mut nodes = vec![];
+            nodes.extend(state.expect_any(&[8]).map(|token| vec![ars::ast::Node::from_token(&token)]).map(|mut nodes| { for node in nodes.iter_mut() { node.label = "name".to_string(); } nodes })?);
+            nodes.extend(state.expect_any(&[4]).map(|token| vec![ars::ast::Node::from_token(&token)]).map(|_| vec![])?);
+            nodes.extend(Ok(state.attempt(|state: &mut ars::parser::ParserState| parse_args(state).map(|node| node.into_children())).unwrap_or_default())?);
+            nodes.extend(state.expect_any(&[5]).map(|token| vec![ars::ast::Node::from_token(&token)]).map(|_| vec![])?);
+            Ok(nodes)
+        })(state);
+        Ok(ars::grammar::interpreter::shape_node(13, "Call", Some("name"), &[], children?))
+    })
+}
+
+/// `Args` as a `Parser`, whose `parse` panics on errors, `try_parse` and `parse_args` return them
+pub struct Args;
+
+impl ars::parser::Parser for Args {
+    fn parse(&self, state: &mut ars::parser::ParserState) -> ars::ast::Node {
+        parse_args(state).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_parse(&self, state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+        parse_args(state)
+    }
+}
+
+pub fn parse_args(state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+    state.rule(14, |state| {
+        let children: ars::parser::ParseResult<Vec<ars::ast::Node>> = (|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {
+            let mut nodes = vec![];
+            nodes.extend(parse_expr(state).map(|node| vec![node])?);
+            nodes.extend(Ok(state.many(|state: &mut ars::parser::ParserState| (|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {
+                    let mut nodes = vec![];
+                    nodes.extend(state.expect_any(&[6]).map(|token| vec![ars::ast::Node::from_token(&token)]).map(|_| vec![])?);
+                    nodes.extend(parse_expr(state).map(|node| vec![node])?);
+                    Ok(nodes)
+                })(state)))?);
+            Ok(nodes)
+        })(state);
+        Ok(ars::grammar::interpreter::shape_node(14, "Args", None, &[], children?))
+    })
+}
+
+/// `List` as a `Parser`, whose `parse` panics on errors, `try_parse` and `parse_list` return them
+pub struct List;
+
+impl ars::parser::Parser for List {
+    fn parse(&self, state: &mut ars::parser::ParserState) -> ars::ast::Node {
+        parse_list(state).unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    fn try_parse(&self, state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+        parse_list(state)
+    }
+}
+
+pub fn parse_list(state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {
+    state.rule(15, |state| {
+        let children: ars::parser::ParseResult<Vec<ars::ast::Node>> = state.choice(&[
+            &|state: &mut ars::parser::ParserState| (|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {
+                let mut nodes = vec![];
+                nodes.extend(parse_list(state).map(|node| vec![node])?);
+                nodes.extend(state.expect_any(&[6]).map(|token| vec![ars::ast::Node::from_token(&token)])?);
+                nodes.extend(state.expect_any(&[7]).map(|token| vec![ars::ast::Node::from_token(&token)])?);
+                Ok(nodes)
+            })(state),
+            &|state: &mut ars::parser::ParserState| state.expect_any(&[7]).map(|token| vec![ars::ast::Node::from_token(&token)]),
+        ]);
+        Ok(ars::grammar::interpreter::shape_node(15, "List", None, &[], children?))
+    })
+}
@@ -1,26 +1,246 @@
-use std::{borrow::{Borrow, BorrowMut}, cell::Cell, fmt::{Display, Write}, ops::Deref, sync::{Arc, RwLock, RwLockReadGuard}};
+use std::{collections::BTreeMap, fmt::Display, sync::Arc};
 
-#[derive(Debug, Clone)]
+use crate::{
+    printer::{Printable, Printer},
+    span::Span,
+    token::TokenData,
+};
+
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Value {
     String(String),
+    Integer(i64),
+    Float(f64),
+    Bool(bool),
+    Char(char),
+    List(Vec<Value>),
+    /// ordered by key so that trees print the same every time
+    Map(BTreeMap<String, Value>),
     None,
 }
 
 impl Value {
-    pub fn as_string(&self) -> String {
+    /// the value as text, nothing for `None`, see `as_str` for strings only
+    pub fn as_string(&self) -> Option<String> {
+        match self {
+            Value::String(s) => Some(s.to_string()),
+            Value::None => None,
+            value => Some(value.to_string()),
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Value::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_integer(&self) -> Option<i64> {
+        match self {
+            Value::Integer(i) => Some(*i),
+            _ => None,
+        }
+    }
+
+    /// floats, and integers widened to floats
+    pub fn as_float(&self) -> Option<f64> {
+        match self {
+            Value::Float(f) => Some(*f),
+            Value::Integer(i) => Some(*i as f64),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Value::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_char(&self) -> Option<char> {
+        match self {
+            Value::Char(c) => Some(*c),
+            _ => None,
+        }
+    }
+
+    pub fn as_list(&self) -> Option<&[Value]> {
         match self {
-            Value::String(s) => s.to_string(),
-            Value::None => panic!("Value is None"),
+            Value::List(items) => Some(items),
+            _ => None,
         }
     }
+
+    pub fn as_map(&self) -> Option<&BTreeMap<String, Value>> {
+        match self {
+            Value::Map(entries) => Some(entries),
+            _ => None,
+        }
+    }
+
+    pub fn is_none(&self) -> bool {
+        matches!(self, Value::None)
+    }
+
+    /// name of the variant, used in error messages
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::String(_) => "string",
+            Value::Integer(_) => "integer",
+            Value::Float(_) => "float",
+            Value::Bool(_) => "bool",
+            Value::Char(_) => "char",
+            Value::List(_) => "list",
+            Value::Map(_) => "map",
+            Value::None => "none",
+        }
+    }
+}
+
+impl Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::String(s) => write!(f, "{}", s),
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Float(x) => write!(f, "{:?}", x),
+            Value::Bool(b) => write!(f, "{}", b),
+            Value::Char(c) => write!(f, "{}", c),
+            Value::List(items) => {
+                write!(f, "[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}", item)?;
+                }
+                write!(f, "]")
+            }
+            Value::Map(entries) => {
+                write!(f, "{{")?;
+                for (i, (key, value)) in entries.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, ", ")?;
+                    }
+                    write!(f, "{}: {}", key, value)?;
+                }
+                write!(f, "}}")
+            }
+            Value::None => write!(f, "none"),
+        }
+    }
+}
+
+/// a value of another variant than the one asked for
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValueError {
+    pub expected: &'static str,
+    pub found: &'static str,
+}
+
+impl Display for ValueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Expected a {} value but found {}", self.expected, self.found)
+    }
+}
+
+impl std::error::Error for ValueError {}
+
+macro_rules! value_conversions {
+    ( $( $variant:ident($type:ty) as $name:literal ),* ) => {
+        $(
+            impl From<$type> for Value {
+                fn from(value: $type) -> Self {
+                    Value::$variant(value)
+                }
+            }
+
+            impl TryFrom<&Value> for $type {
+                type Error = ValueError;
+
+                fn try_from(value: &Value) -> Result<Self, Self::Error> {
+                    match value {
+                        Value::$variant(inner) => Ok(inner.clone()),
+                        other => Err(ValueError { expected: $name, found: other.type_name() }),
+                    }
+                }
+            }
+        )*
+    };
+}
+
+value_conversions!(
+    String(String) as "string",
+    Integer(i64) as "integer",
+    Float(f64) as "float",
+    Bool(bool) as "bool",
+    Char(char) as "char",
+    List(Vec<Value>) as "list",
+    Map(BTreeMap<String, Value>) as "map"
+);
+
+impl From<&str> for Value {
+    fn from(value: &str) -> Self {
+        Value::String(value.to_string())
+    }
+}
+
+impl From<i32> for Value {
+    fn from(value: i32) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl From<u32> for Value {
+    fn from(value: u32) -> Self {
+        Value::Integer(value.into())
+    }
+}
+
+impl From<f32> for Value {
+    fn from(value: f32) -> Self {
+        Value::Float(value.into())
+    }
+}
+
+impl<T: Into<Value>> From<Option<T>> for Value {
+    fn from(value: Option<T>) -> Self {
+        value.map_or(Value::None, Into::into)
+    }
+}
+
+/// the text of the token
+impl From<TokenData> for Value {
+    fn from(token: TokenData) -> Self {
+        Value::String(token.value)
+    }
+}
+
+impl From<&TokenData> for Value {
+    fn from(token: &TokenData) -> Self {
+        Value::String(token.value.clone())
+    }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Node {
     pub kind: u32,
     pub label: String,
     pub value: Value,
     pub children: Vec<Arc<Node>>,
+    /// first through last token of the node, unknown for nodes built by hand
+    #[cfg_attr(feature = "serde", serde(default))]
+    pub span: Span,
+}
+
+/// nodes are equal when their trees are, wherever they come from in the source
+impl PartialEq for Node {
+    fn eq(&self, other: &Self) -> bool {
+        self.kind == other.kind && self.label == other.label && self.value == other.value && self.children == other.children
+    }
 }
 
 impl Node {
@@ -28,37 +248,41 @@ impl Node {
         Node {
             kind,
             label: label.to_string(),
-            value: value,
+            value,
             children: vec![],
+            span: Span::default(),
         }
     }
 
+    /// a leaf holding the text of a token
+    pub fn from_token(token: &TokenData) -> Node {
+        Node::new(token.kind, &token.label, Value::String(token.value.clone())).with_span(token)
+    }
+
+    pub fn with_span(mut self, span: impl Into<Span>) -> Node {
+        self.span = span.into();
+        self
+    }
+
+    /// adds a child, growing the span of the node to cover it
     pub fn add_child(&mut self, child: Node) {
+        if child.span.is_known() {
+            self.span = match self.span.is_known() {
+                true => self.span.merge(child.span),
+                false => child.span,
+            };
+        }
         self.children.push(Arc::new(child));
     }
 
+    /// takes the children out of the node, cloning only the ones that are shared
+    pub fn into_children(self) -> Vec<Node> {
+        self.children.into_iter().map(Arc::unwrap_or_clone).collect()
+    }
+
+    /// writes the node as a tree with the default printer, `depth` levels deep
     pub fn display(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
-        let line_color = format!("\x1b[38;2;{};{};{}m", 120, 120, 120);
-        let number_color = format!("\x1b[38;2;{};{};{}m", 240, 140, 30);
-        let label_color = format!("\x1b[38;2;{};{};{}m", 200, 30, 200);
-        let field_color = format!("\x1b[38;2;{};{};{}m", 150, 150, 170);
-        let string_color = format!("\x1b[38;2;{};{};{}m", 60, 180, 100);
-        let none_color = format!("\x1b[38;2;{};{};{}m", 255, 100, 100);
-        let stop = "\x1b[0m";
-
-        let space = " ".repeat(depth * 2);
-        write!(f, "{label_color}{}{stop}\n", self.label)?;
-        write!(f, "{space}{line_color}└┬─{stop} {field_color}value:{field_color} ")?;
-        match &self.value {
-            Value::String(s) => write!(f, "{string_color}{}{stop}\n", s)?,
-            Value::None => write!(f, "{none_color}none{stop}\n")?,
-        }
-        write!(f, "{space} {line_color}└─{stop} {field_color}kind:{field_color} {number_color}{}{stop}\n", self.kind)?;
-        for child in &self.children {
-            write!(f, "{space}    {line_color}┌─{stop} ")?;
-            child.display(f, depth + 2)?;
-        }
-        Ok(())
+        self.print(&Printer::default(), f, depth)
     }
 }
 
@@ -73,6 +297,14 @@ macro_rules! node {
     ( $kind:literal, $label:literal, $value:expr ) => {
         Node::new($kind, $label, $value)
     };
+
+    ( $kind:literal, $label:literal, $value:expr, $span:expr ) => {
+        Node::new($kind, $label, $value).with_span($span)
+    };
+
+    ( $token:expr ) => {
+        Node::from_token(&$token)
+    };
 }
 
 #[macro_export]
@@ -87,4 +319,32 @@ macro_rules! add_node {
     };
 
     ( $root:expr ) => {}
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_value_accessors() {
+        let value = Value::from(10);
+        assert_eq!(value.as_integer(), Some(10));
+        assert_eq!(value.as_float(), Some(10.0));
+        assert_eq!(value.as_str(), None);
+        assert_eq!(value.as_string(), Some("10".to_string()));
+        assert_eq!(Value::None.as_string(), None);
+        assert_eq!(Value::from(Some('x')).as_char(), Some('x'));
+        assert!(Value::from(None::<bool>).is_none());
+
+        assert_eq!(i64::try_from(&value), Ok(10));
+        let error = String::try_from(&value).unwrap_err();
+        assert_eq!(error.to_string(), "Expected a string value but found integer");
+    }
+
+    #[test]
+    fn test_value_display() {
+        let map = BTreeMap::from([("b".to_string(), Value::from(true)), ("a".to_string(), Value::from(1.5))]);
+        let value = Value::List(vec![Value::from("x"), Value::Map(map), Value::from(2.0)]);
+        assert_eq!(value.to_string(), "[x, {a: 1.5, b: true}, 2.0]");
+        assert_eq!(value.as_list().map(|items| items.len()), Some(3));
+    }
+}
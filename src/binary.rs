@@ -0,0 +1,325 @@
+use std::{collections::BTreeMap, fmt::Display};
+
+use crate::{
+    ast::{Node, Value},
+    span::Span,
+};
+
+const MAGIC: &[u8; 4] = b"ARS\x01";
+
+/// how deep nodes and values can nest when reading, so that crafted input cannot overflow the stack
+const MAX_DEPTH: usize = 256;
+
+/// encodes `node` with its spans, labels are stored once in a table at the start
+///
+/// numbers are LEB128 varints, signed ones zigzagged, floats their 8 little endian bytes
+pub fn to_bytes(node: &Node) -> Vec<u8> {
+    let mut labels = vec![];
+    let mut indices = BTreeMap::new();
+    for node in node.descendants() {
+        if !indices.contains_key(node.label.as_str()) {
+            indices.insert(node.label.as_str(), labels.len());
+            labels.push(node.label.as_str());
+        }
+    }
+    let mut out = MAGIC.to_vec();
+    write_varint(&mut out, labels.len() as u64);
+    for label in labels {
+        write_str(&mut out, label);
+    }
+    write_node(&mut out, node, &indices);
+    out
+}
+
+fn write_node(out: &mut Vec<u8>, node: &Node, labels: &BTreeMap<&str, usize>) {
+    write_varint(out, node.kind.into());
+    write_varint(out, labels[node.label.as_str()] as u64);
+    write_value(out, &node.value);
+    let span = node.span;
+    for n in [span.start, span.end.saturating_sub(span.start), span.line, span.column, span.source] {
+        write_varint(out, n as u64);
+    }
+    write_varint(out, node.children.len() as u64);
+    for child in node.children.iter() {
+        write_node(out, child, labels);
+    }
+}
+
+fn write_value(out: &mut Vec<u8>, value: &Value) {
+    match value {
+        Value::None => out.push(0),
+        Value::String(s) => {
+            out.push(1);
+            write_str(out, s);
+        }
+        Value::Integer(i) => {
+            out.push(2);
+            write_varint(out, ((i << 1) ^ (i >> 63)) as u64);
+        }
+        Value::Float(f) => {
+            out.push(3);
+            out.extend(f.to_bits().to_le_bytes());
+        }
+        Value::Bool(b) => out.push(if *b { 5 } else { 4 }),
+        Value::Char(c) => {
+            out.push(6);
+            write_varint(out, (*c).into());
+        }
+        Value::List(items) => {
+            out.push(7);
+            write_varint(out, items.len() as u64);
+            items.iter().for_each(|item| write_value(out, item));
+        }
+        Value::Map(entries) => {
+            out.push(8);
+            write_varint(out, entries.len() as u64);
+            for (key, value) in entries {
+                write_str(out, key);
+                write_value(out, value);
+            }
+        }
+    }
+}
+
+fn write_varint(out: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        out.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    out.push(n as u8);
+}
+
+fn write_str(out: &mut Vec<u8>, s: &str) {
+    write_varint(out, s.len() as u64);
+    out.extend(s.as_bytes());
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BinaryError {
+    pub message: String,
+    /// byte offset of the error
+    pub offset: usize,
+}
+
+impl Display for BinaryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for BinaryError {}
+
+/// reads a node written by `to_bytes`, spans included
+pub fn from_bytes(bytes: &[u8]) -> Result<Node, BinaryError> {
+    let mut reader = Reader { bytes, position: 0, labels: vec![], depth: 0 };
+    if !bytes.starts_with(MAGIC) {
+        return Err(reader.error("Not an encoded tree".to_string()));
+    }
+    reader.position = MAGIC.len();
+    let count = reader.length()?;
+    for _ in 0..count {
+        let label = reader.string()?;
+        reader.labels.push(label);
+    }
+    let node = reader.node()?;
+    match reader.position == bytes.len() {
+        true => Ok(node),
+        false => Err(reader.error("Unexpected bytes after the tree".to_string())),
+    }
+}
+
+struct Reader<'b> {
+    bytes: &'b [u8],
+    position: usize,
+    labels: Vec<String>,
+    depth: usize,
+}
+
+impl Reader<'_> {
+    fn error(&self, message: String) -> BinaryError {
+        BinaryError { message, offset: self.position }
+    }
+
+    fn byte(&mut self) -> Result<u8, BinaryError> {
+        let byte = *self.bytes.get(self.position).ok_or_else(|| self.error("Unexpected end of the input".to_string()))?;
+        self.position += 1;
+        Ok(byte)
+    }
+
+    fn take(&mut self, len: usize) -> Result<&[u8], BinaryError> {
+        let end = self.position.checked_add(len).filter(|&end| end <= self.bytes.len());
+        let end = end.ok_or_else(|| self.error("Unexpected end of the input".to_string()))?;
+        let bytes = &self.bytes[self.position..end];
+        self.position = end;
+        Ok(bytes)
+    }
+
+    fn varint(&mut self) -> Result<u64, BinaryError> {
+        let start = self.position;
+        let mut n = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.byte()?;
+            n |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(n);
+            }
+        }
+        Err(BinaryError { message: "Varint is too long".to_string(), offset: start })
+    }
+
+    /// a varint used as a size or an index
+    fn length(&mut self) -> Result<usize, BinaryError> {
+        let start = self.position;
+        let n = self.varint()?;
+        usize::try_from(n).map_err(|_| BinaryError { message: format!("`{}` is too large", n), offset: start })
+    }
+
+    fn string(&mut self) -> Result<String, BinaryError> {
+        let len = self.length()?;
+        let start = self.position;
+        let bytes = self.take(len)?.to_vec();
+        String::from_utf8(bytes).map_err(|_| BinaryError { message: "Invalid UTF-8".to_string(), offset: start })
+    }
+
+    /// goes one level deeper, the caller goes back up once it is read
+    fn enter(&mut self) -> Result<(), BinaryError> {
+        self.depth += 1;
+        match self.depth > MAX_DEPTH {
+            true => Err(self.error(format!("Nesting is deeper than {} levels", MAX_DEPTH))),
+            false => Ok(()),
+        }
+    }
+
+    fn node(&mut self) -> Result<Node, BinaryError> {
+        self.enter()?;
+        let start = self.position;
+        let kind = u32::try_from(self.varint()?).map_err(|_| BinaryError { message: "Kind is too large".to_string(), offset: start })?;
+        let start = self.position;
+        let index = self.length()?;
+        let label = self.labels.get(index).cloned().ok_or(BinaryError { message: format!("No label {}", index), offset: start })?;
+        let value = self.value()?;
+        let (start, len) = (self.length()?, self.length()?);
+        let span = Span::new(start, start.saturating_add(len), self.length()?, self.length()?).with_source(self.length()?);
+        let mut node = Node::new(kind, &label, value).with_span(span);
+        for _ in 0..self.length()? {
+            // pushed directly so that the span stays the stored one
+            node.children.push(self.node()?.into());
+        }
+        self.depth -= 1;
+        Ok(node)
+    }
+
+    fn value(&mut self) -> Result<Value, BinaryError> {
+        self.enter()?;
+        let start = self.position;
+        let value = match self.byte()? {
+            0 => Value::None,
+            1 => Value::String(self.string()?),
+            2 => {
+                let n = self.varint()?;
+                Value::Integer((n >> 1) as i64 ^ -((n & 1) as i64))
+            }
+            3 => Value::Float(f64::from_bits(u64::from_le_bytes(self.take(8)?.try_into().unwrap()))),
+            4 => Value::Bool(false),
+            5 => Value::Bool(true),
+            6 => {
+                let n = self.varint()?;
+                let c = u32::try_from(n).ok().and_then(char::from_u32);
+                Value::Char(c.ok_or(BinaryError { message: format!("`{}` is not a char", n), offset: start + 1 })?)
+            }
+            7 => {
+                let len = self.length()?;
+                let mut items = vec![];
+                for _ in 0..len {
+                    items.push(self.value()?);
+                }
+                Value::List(items)
+            }
+            8 => {
+                let len = self.length()?;
+                let mut entries = BTreeMap::new();
+                for _ in 0..len {
+                    let key = self.string()?;
+                    entries.insert(key, self.value()?);
+                }
+                Value::Map(entries)
+            }
+            tag => return Err(BinaryError { message: format!("Unknown value tag {}", tag), offset: start }),
+        };
+        self.depth -= 1;
+        Ok(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Node {
+        let map = [("k".to_string(), Value::from(-3)), ("l".to_string(), Value::List(vec![Value::from('é'), Value::from(true)]))].into();
+        let mut root = Node::new(3, "Call", Value::Map(map)).with_span(Span::new(0, 12, 1, 1));
+        root.add_child(Node::new(1, "NUMBER", Value::from(i64::MIN)).with_span(Span::new(5, 7, 1, 6).with_source(2)));
+        root.add_child(Node::new(1, "NUMBER", Value::from(f64::NAN)));
+        root.add_child(Node::new(2, "ID", Value::from("naïve")));
+        root
+    }
+
+    fn spans(node: &Node) -> Vec<Span> {
+        node.descendants().map(|node| node.span).collect()
+    }
+
+    #[test]
+    fn test_round_trip() {
+        let node = tree();
+        let bytes = to_bytes(&node);
+        let read = from_bytes(&bytes).unwrap();
+        assert_eq!(spans(&read), spans(&node));
+        // NaN is not equal to itself, compare it apart
+        assert!(matches!(read.children[1].value, Value::Float(f) if f.is_nan()));
+        assert_eq!(read.children[0], node.children[0]);
+        assert_eq!(read.children[2], node.children[2]);
+        assert_eq!(read.value, node.value);
+        // `NUMBER` is stored once
+        assert_eq!(String::from_utf8_lossy(&bytes).matches("NUMBER").count(), 1);
+    }
+
+    #[test]
+    fn test_errors() {
+        let bytes = to_bytes(&tree());
+        assert_eq!(from_bytes(b"{}").unwrap_err().to_string(), "Not an encoded tree at byte 0");
+        assert_eq!(from_bytes(&bytes[..bytes.len() - 1]).unwrap_err().message, "Unexpected end of the input");
+        let mut longer = bytes.clone();
+        longer.push(0);
+        assert_eq!(from_bytes(&longer).unwrap_err().offset, bytes.len());
+    }
+
+    #[test]
+    fn test_nesting_limit() {
+        // a label table with `A`, then a node whose value is a list of a list of ...
+        let mut bytes = [MAGIC.as_slice(), &[1, 1, b'A', 0, 0]].concat();
+        bytes.extend([7, 1].repeat(MAX_DEPTH));
+        let error = from_bytes(&bytes).unwrap_err();
+        assert_eq!(error.message, format!("Nesting is deeper than {} levels", MAX_DEPTH));
+        assert_eq!(error.offset, 9 + (MAX_DEPTH - 1) * 2);
+
+        // the deepest tree that fits, the value of its innermost node being the last level
+        let mut node = Node::new(0, "A", Value::None);
+        for _ in 0..MAX_DEPTH - 2 {
+            let mut parent = Node::new(0, "A", Value::None);
+            parent.add_child(node);
+            node = parent;
+        }
+        assert!(from_bytes(&to_bytes(&node)).is_ok());
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde() {
+        let mut node = tree();
+        node.children.remove(1);
+        let json = serde_json::to_string(&node).unwrap();
+        let read: Node = serde_json::from_str(&json).unwrap();
+        assert_eq!(read, node);
+        assert_eq!(spans(&read), spans(&node));
+    }
+}
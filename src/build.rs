@@ -0,0 +1,38 @@
+use std::path::{Path, PathBuf};
+
+use crate::grammar::{codegen, Grammar};
+
+/// generates Rust code for an `.ars` grammar from a build script
+///
+/// the code is written to `$OUT_DIR/<grammar name>.rs` and can be included with
+/// `mod lang { include!(concat!(env!("OUT_DIR"), "/lang.rs")); }`
+pub fn compile_grammar(path: impl AsRef<Path>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let out_dir = std::env::var("OUT_DIR").map_err(|_| "OUT_DIR is not set, `compile_grammar` must run from a build script")?;
+    let path = path.as_ref();
+    println!("cargo:rerun-if-changed={}", path.display());
+    let grammar = load(path)?;
+    // imported files are part of the grammar too
+    for source in grammar.sources.iter().skip(1) {
+        println!("cargo:rerun-if-changed={}", source.display());
+    }
+    write(path, &grammar, out_dir.as_ref())
+}
+
+/// same as `compile_grammar` but writes into the given directory
+pub fn compile_grammar_to(path: impl AsRef<Path>, out_dir: impl AsRef<Path>) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let path = path.as_ref();
+    write(path, &load(path)?, out_dir.as_ref())
+}
+
+/// errors of loaded files already say which file they are in
+fn load(path: &Path) -> Result<Grammar, String> {
+    Grammar::load(path).map_err(|e| e.to_string())
+}
+
+fn write(path: &Path, grammar: &Grammar, out_dir: &Path) -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let source = codegen::generate(grammar).map_err(|e| format!("{} at {}", e.message, grammar.location(e.span)))?;
+    let stem = path.file_stem().ok_or_else(|| format!("{} has no file name", path.display()))?;
+    let output = out_dir.join(stem).with_extension("rs");
+    std::fs::write(&output, source)?;
+    Ok(output)
+}
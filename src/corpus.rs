@@ -0,0 +1,457 @@
+use std::{
+    any::Any,
+    fmt::Display,
+    ops::Range,
+    panic::AssertUnwindSafe,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    ast::Node,
+    grammar::{interpreter::Interpreter, Grammar, GrammarError},
+    lexer::Lexer,
+    parser::{ParseError, Parser, ParserState},
+    sexp::{from_sexp, to_sexp},
+};
+
+/// one test of a corpus file
+///
+/// ```text
+/// ==========
+/// name
+/// ==========
+/// input
+/// ---
+/// (Expected (Tree))
+/// ```
+///
+/// the newline before `---` is not part of the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Case {
+    pub name: String,
+    pub input: String,
+    pub expected: String,
+    /// line of the header
+    pub line: usize,
+    /// byte range of the expected tree in the file, replaced when updating
+    expected_range: Range<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorpusError {
+    pub message: String,
+    pub file: Option<PathBuf>,
+    pub line: usize,
+}
+
+impl Display for CorpusError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{} at {}:{}", self.message, file.display(), self.line),
+            None => write!(f, "{} at line {}", self.message, self.line),
+        }
+    }
+}
+
+impl std::error::Error for CorpusError {}
+
+fn is_rule(line: &str, c: char) -> bool {
+    line.trim_end().len() >= 3 && line.trim_end().chars().all(|x| x == c)
+}
+
+/// splits the text of a corpus file into its cases
+pub fn parse_corpus(text: &str) -> Result<Vec<Case>, CorpusError> {
+    // (byte offset, line) of every line
+    let mut lines = vec![];
+    let mut offset = 0;
+    for line in text.split_inclusive('\n') {
+        lines.push((offset, line));
+        offset += line.len();
+    }
+    let error = |message: &str, line: usize| CorpusError { message: message.to_string(), file: None, line: line + 1 };
+
+    let mut cases = vec![];
+    let mut i = 0;
+    while i < lines.len() {
+        if lines[i].1.trim().is_empty() {
+            i += 1;
+            continue;
+        }
+        if !is_rule(lines[i].1, '=') {
+            return Err(error("Expected a `===` header", i));
+        }
+        let header = i;
+        let name = lines.get(i + 1).filter(|_| lines.get(i + 2).is_some_and(|line| is_rule(line.1, '=')));
+        let name = name.ok_or_else(|| error("Expected a test name between two `===` lines", header))?.1.trim().to_string();
+        i += 3;
+
+        let start = lines.get(i).map_or(text.len(), |line| line.0);
+        while i < lines.len() && !is_rule(lines[i].1, '-') {
+            i += 1;
+        }
+        if i == lines.len() {
+            return Err(error(&format!("Test `{}` has no `---` line", name), header));
+        }
+        let input = &text[start..lines[i].0];
+        let input = input.strip_suffix('\n').unwrap_or(input);
+        let input = input.strip_suffix('\r').unwrap_or(input);
+        i += 1;
+
+        let start = lines.get(i).map_or(text.len(), |line| line.0);
+        while i < lines.len() && !is_rule(lines[i].1, '=') {
+            i += 1;
+        }
+        let end = lines.get(i).map_or(text.len(), |line| line.0);
+        // blank lines around the tree do not belong to it
+        let start = end - text[start..end].trim_start().len();
+        let expected = text[start..end].trim_end();
+        cases.push(Case {
+            name,
+            input: input.to_string(),
+            expected: expected.to_string(),
+            line: header + 1,
+            expected_range: start..start + expected.len(),
+        });
+    }
+    Ok(cases)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Status {
+    Passed,
+    /// the tree differs, with a line diff of expected (`-`) and produced (`+`) trees
+    Failed { diff: String },
+    /// the input did not lex or parse, or the expectation is not a valid tree
+    Error { message: String },
+    /// the expectation was rewritten with the produced tree
+    Updated,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Outcome {
+    pub file: Option<PathBuf>,
+    pub name: String,
+    pub line: usize,
+    pub status: Status,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Report {
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Report {
+    pub fn passed(&self) -> usize {
+        self.outcomes.iter().filter(|outcome| matches!(outcome.status, Status::Passed | Status::Updated)).count()
+    }
+
+    pub fn failed(&self) -> usize {
+        self.outcomes.len() - self.passed()
+    }
+
+    pub fn is_ok(&self) -> bool {
+        self.failed() == 0
+    }
+}
+
+impl Display for Report {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for outcome in self.outcomes.iter() {
+            let location = match &outcome.file {
+                Some(file) => format!("{}:{}", file.display(), outcome.line),
+                None => format!("line {}", outcome.line),
+            };
+            match &outcome.status {
+                Status::Passed => writeln!(f, "ok      {}", outcome.name)?,
+                Status::Updated => writeln!(f, "updated {} ({})", outcome.name, location)?,
+                Status::Failed { diff } => {
+                    writeln!(f, "FAIL    {} ({})", outcome.name, location)?;
+                    for line in diff.lines() {
+                        writeln!(f, "    {}", line)?;
+                    }
+                }
+                Status::Error { message } => writeln!(f, "ERROR   {} ({}): {}", outcome.name, location, message)?,
+            }
+        }
+        write!(f, "{} passed, {} failed", self.passed(), self.failed())
+    }
+}
+
+type ParseFn<'p> = Box<dyn Fn(&str) -> Result<Node, String> + 'p>;
+
+/// runs corpus files against a grammar or a hand written parser
+pub struct Runner<'p> {
+    parse: ParseFn<'p>,
+    update: bool,
+}
+
+impl<'p> Runner<'p> {
+    /// `parse` turns an input into its tree or an error message
+    pub fn new(parse: impl Fn(&str) -> Result<Node, String> + 'p) -> Self {
+        Self { parse: Box::new(parse), update: false }
+    }
+
+    /// parses with the first rule of the grammar
+    pub fn from_grammar(grammar: &'p Grammar) -> Result<Self, GrammarError> {
+        let interpreter = Interpreter::new(grammar)?;
+        let lexer = grammar.lexer()?;
+        let rule = grammar.start_rule().ok_or_else(|| GrammarError::new("Grammar has no rules".to_string(), Default::default()))?;
+        Ok(Self::new(move |input| {
+            let tokens = lex(&lexer, input)?;
+            interpreter.parse_tokens(&rule.name, tokens).map_err(|e| e.to_string())
+        }))
+    }
+
+    /// parses with `parser` over the tokens of `lexer`, without the `skip_kinds`
+    ///
+    /// the whole input must be parsed, and a panicking parser fails its test instead of the run
+    pub fn from_parser(parser: &'p dyn Parser, lexer: Lexer, skip_kinds: Vec<u32>) -> Self {
+        Self::new(move |input| {
+            let mut state = ParserState::new(lex(&lexer, input)?, Some(skip_kinds.clone()));
+            let node = match std::panic::catch_unwind(AssertUnwindSafe(|| state.try_parse(parser))) {
+                Ok(result) => result.map_err(|e| e.to_string())?,
+                Err(panic) => return Err(panic_message(panic)),
+            };
+            state.skip_trivia();
+            match state.lookahead(0) {
+                Some(token) => Err(ParseError::Unexpected { expected: vec![], found: token.clone() }.to_string()),
+                None => Ok(node),
+            }
+        })
+    }
+
+    /// rewrites failing expectations with the trees the parser produced
+    pub fn with_update(mut self, update: bool) -> Self {
+        self.update = update;
+        self
+    }
+
+    /// runs the cases of a corpus text, returning the updated text when anything was rewritten
+    pub fn run_text(&self, text: &str) -> Result<(Report, Option<String>), CorpusError> {
+        let mut report = Report::default();
+        let mut replacements = vec![];
+        for case in parse_corpus(text)? {
+            let mut status = self.run_case(&case);
+            if self.update && !matches!(status, Status::Passed) {
+                if let Some(tree) = self.produced(&case) {
+                    replacements.push((case.expected_range.clone(), tree));
+                    status = Status::Updated;
+                }
+            }
+            report.outcomes.push(Outcome { file: None, name: case.name, line: case.line, status });
+        }
+        if replacements.is_empty() {
+            return Ok((report, None));
+        }
+        // rewriting from the end keeps the earlier ranges valid
+        let mut updated = text.to_string();
+        for (range, tree) in replacements.into_iter().rev() {
+            updated.replace_range(range, &tree);
+        }
+        Ok((report, Some(updated)))
+    }
+
+    /// runs a corpus file, writing it back in update mode
+    pub fn run_file(&self, path: impl AsRef<Path>) -> Result<Report, Box<dyn std::error::Error>> {
+        let path = path.as_ref();
+        let text = std::fs::read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let (mut report, updated) = self.run_text(&text).map_err(|e| CorpusError { file: Some(path.to_path_buf()), ..e })?;
+        if let Some(updated) = updated {
+            std::fs::write(path, updated)?;
+        }
+        for outcome in report.outcomes.iter_mut() {
+            outcome.file = Some(path.to_path_buf());
+        }
+        Ok(report)
+    }
+
+    /// runs every `.txt` file of a directory and its subdirectories, in name order
+    pub fn run_dir(&self, dir: impl AsRef<Path>) -> Result<Report, Box<dyn std::error::Error>> {
+        let mut files = vec![];
+        collect_files(dir.as_ref(), &mut files)?;
+        files.sort();
+        let mut report = Report::default();
+        for file in files {
+            report.outcomes.extend(self.run_file(file)?.outcomes);
+        }
+        Ok(report)
+    }
+
+    fn produced(&self, case: &Case) -> Option<String> {
+        (self.parse)(&case.input).ok().map(|node| to_sexp(&node, false))
+    }
+
+    fn run_case(&self, case: &Case) -> Status {
+        let actual = match (self.parse)(&case.input) {
+            Ok(node) => to_sexp(&node, false),
+            Err(message) => return Status::Error { message },
+        };
+        // kinds and layout of the expectation do not matter
+        let expected = match from_sexp(&case.expected) {
+            Ok(node) => to_sexp(&node, false),
+            Err(e) => return Status::Error { message: format!("Invalid expected tree: {}", e) },
+        };
+        match expected == actual {
+            true => Status::Passed,
+            false => Status::Failed { diff: diff(&expected, &actual) },
+        }
+    }
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => format!("Parser panicked: {}", message),
+        Err(panic) => format!("Parser panicked: {}", panic.downcast_ref::<&str>().copied().unwrap_or("no message")),
+    }
+}
+
+fn lex(lexer: &Lexer, input: &str) -> Result<Vec<crate::token::TokenData>, String> {
+    let mut lexer = lexer.clone();
+    lexer.begin(input);
+    lexer.try_all().map_err(|(line, column)| format!("Invalid character at line {}, column {}", line, column))
+}
+
+fn collect_files(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+    for entry in std::fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_files(&path, files)?;
+        } else if path.extension().is_some_and(|extension| extension == "txt") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// line diff through the longest common subsequence, unchanged lines start with two spaces
+pub fn diff(expected: &str, actual: &str) -> String {
+    let a = expected.lines().collect::<Vec<_>>();
+    let b = actual.lines().collect::<Vec<_>>();
+    // lengths[i][j] is the LCS of a[i..] and b[j..]
+    let mut lengths = vec![vec![0usize; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lengths[i][j] = match a[i] == b[j] {
+                true => lengths[i + 1][j + 1] + 1,
+                false => lengths[i + 1][j].max(lengths[i][j + 1]),
+            };
+        }
+    }
+    let mut out = String::new();
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() || j < b.len() {
+        if i < a.len() && j < b.len() && a[i] == b[j] {
+            out.push_str(&format!("  {}\n", a[i]));
+            i += 1;
+            j += 1;
+        } else if j < b.len() && (i == a.len() || lengths[i][j + 1] >= lengths[i + 1][j]) {
+            out.push_str(&format!("+ {}\n", b[j]));
+            j += 1;
+        } else {
+            out.push_str(&format!("- {}\n", a[i]));
+            i += 1;
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Value, create_parser, testing::TempDir, token::Token};
+
+    const GRAMMAR: &str = "NUM: /\\d+/;\n@drop(WS) List: \"[\" NUM (WS? \",\" WS? NUM)* \"]\";\nWS: /\\s+/;";
+
+    const CORPUS: &str = "\
+==========
+single
+==========
+[1]
+---
+(List
+  (\"\\\"[\\\"\" \"[\")
+  (NUM \"1\")
+  (\"\\\"]\\\"\" \"]\"))
+
+===
+wrong
+===
+[1, 2]
+---
+
+(List (NUM \"1\"))
+";
+
+    #[test]
+    fn test_parse_corpus() {
+        let cases = parse_corpus(CORPUS).unwrap();
+        assert_eq!(cases.len(), 2);
+        assert_eq!(cases[0].name, "single");
+        assert_eq!(cases[0].input, "[1]");
+        assert_eq!(cases[1].line, 11);
+        assert_eq!(cases[1].expected, "(List (NUM \"1\"))");
+
+        let error = parse_corpus("===\nname\n===\ninput\n").unwrap_err();
+        assert_eq!(error.to_string(), "Test `name` has no `---` line at line 1");
+    }
+
+    #[test]
+    fn test_run() {
+        let grammar = Grammar::parse(GRAMMAR).unwrap();
+        let runner = Runner::from_grammar(&grammar).unwrap();
+        let (report, updated) = runner.run_text(CORPUS).unwrap();
+        assert_eq!(updated, None);
+        assert_eq!(report.outcomes[0].status, Status::Passed);
+        let Status::Failed { diff } = &report.outcomes[1].status else { panic!("{}", report) };
+        assert!(diff.contains("+   (\"\\\",\\\"\" \",\")"));
+        assert!(diff.contains("  (List"));
+        assert!(report.to_string().ends_with("1 passed, 1 failed"));
+
+        let (report, _) = runner.run_text("===\nbad\n===\n[1\n---\n(List)").unwrap();
+        assert!(matches!(&report.outcomes[0].status, Status::Error { message } if message.starts_with("Expected")));
+    }
+
+    create_parser!(Number, 1, |_, state: &mut ParserState| {
+        let token = state.eat();
+        match token.kind {
+            1 => Node::new(1, "NUM", Value::String(token.value)),
+            _ => panic!("`{}` is not a number", token.value),
+        }
+    });
+
+    #[test]
+    fn test_from_parser() {
+        let lexer = Lexer::new(vec![
+            Token::new_regex_from_str("WS", 0, "\\s+"),
+            Token::new_regex_from_str("NUM", 1, "\\d+"),
+            Token::new_regex_from_str("ID", 2, "[a-z]+"),
+        ]);
+        let runner = Runner::from_parser(&Number, lexer, vec![0]);
+        let text = "===\nnumber\n===\n12 \n---\n(NUM \"12\")\n\n===\ntrailing\n===\n12 34\n---\n(NUM \"12\")\n\n===\npanics\n===\nx\n---\n(NUM)";
+        let (report, _) = runner.run_text(text).unwrap();
+        assert_eq!(report.outcomes[0].status, Status::Passed);
+        let message = "Expected end of input but found \"NUM\"(1) at line 1, column 4".to_string();
+        assert_eq!(report.outcomes[1].status, Status::Error { message });
+        let message = "Parser panicked: `x` is not a number".to_string();
+        assert_eq!(report.outcomes[2].status, Status::Error { message });
+    }
+
+    #[test]
+    fn test_update() {
+        let grammar = Grammar::parse(GRAMMAR).unwrap();
+        let dir = TempDir::new("corpus");
+        std::fs::create_dir_all(dir.join("nested")).unwrap();
+        std::fs::write(dir.join("nested/lists.txt"), CORPUS).unwrap();
+
+        let runner = Runner::from_grammar(&grammar).unwrap().with_update(true);
+        let report = runner.run_dir(&dir).unwrap();
+        assert_eq!(report.outcomes[1].status, Status::Updated);
+        assert!(report.is_ok());
+
+        let runner = Runner::from_grammar(&grammar).unwrap();
+        let report = runner.run_dir(&dir).unwrap();
+        assert_eq!(report.passed(), 2);
+        let text = std::fs::read_to_string(dir.join("nested/lists.txt")).unwrap();
+        assert!(text.starts_with(&CORPUS[..CORPUS.find("(List (NUM").unwrap()]));
+    }
+}
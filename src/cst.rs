@@ -0,0 +1,502 @@
+use std::{collections::HashMap, fmt::Display, ops::Range, rc::Rc, sync::Arc};
+
+use crate::{ast::Node, token::TokenData};
+
+/// a token of the concrete syntax tree, it knows its text but not where it is
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenToken {
+    pub kind: u32,
+    pub text: String,
+}
+
+/// an immutable node of the concrete syntax tree, shared between trees that contain it
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GreenNode {
+    pub kind: u32,
+    pub children: Vec<GreenElement>,
+    text_len: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum GreenElement {
+    Node(Arc<GreenNode>),
+    Token(Arc<GreenToken>),
+}
+
+impl GreenNode {
+    pub fn new(kind: u32, children: Vec<GreenElement>) -> Self {
+        let text_len = children.iter().map(GreenElement::text_len).sum();
+        Self { kind, children, text_len }
+    }
+
+    /// length in bytes of the text under the node
+    pub fn text_len(&self) -> usize {
+        self.text_len
+    }
+}
+
+impl GreenElement {
+    pub fn kind(&self) -> u32 {
+        match self {
+            GreenElement::Node(node) => node.kind,
+            GreenElement::Token(token) => token.kind,
+        }
+    }
+
+    pub fn text_len(&self) -> usize {
+        match self {
+            GreenElement::Node(node) => node.text_len,
+            GreenElement::Token(token) => token.text.len(),
+        }
+    }
+}
+
+impl Display for GreenNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for child in self.children.iter() {
+            match child {
+                GreenElement::Node(node) => write!(f, "{}", node)?,
+                GreenElement::Token(token) => write!(f, "{}", token.text)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+/// builds a green tree from the top down, equal tokens are stored once
+#[derive(Debug, Default)]
+pub struct GreenBuilder {
+    /// (kind, index of its first child in `children`) of the open nodes
+    parents: Vec<(u32, usize)>,
+    children: Vec<GreenElement>,
+    tokens: HashMap<(u32, String), Arc<GreenToken>>,
+}
+
+/// a position in the children of the open node, to wrap what follows it later
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Checkpoint(usize);
+
+impl GreenBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn start_node(&mut self, kind: u32) {
+        self.parents.push((kind, self.children.len()));
+    }
+
+    pub fn token(&mut self, kind: u32, text: &str) {
+        let token = self.tokens
+            .entry((kind, text.to_string()))
+            .or_insert_with(|| Arc::new(GreenToken { kind, text: text.to_string() }))
+            .clone();
+        self.children.push(GreenElement::Token(token));
+    }
+
+    pub fn finish_node(&mut self) {
+        let (kind, first) = self.parents.pop().expect("`finish_node` without `start_node`");
+        let children = self.children.split_off(first);
+        self.children.push(GreenElement::Node(Arc::new(GreenNode::new(kind, children))));
+    }
+
+    pub fn checkpoint(&self) -> Checkpoint {
+        Checkpoint(self.children.len())
+    }
+
+    /// starts a node holding everything added since `checkpoint`, for operators found after their left operand
+    pub fn start_node_at(&mut self, checkpoint: Checkpoint, kind: u32) {
+        let first = self.parents.last().map_or(0, |(_, first)| *first);
+        assert!(checkpoint.0 >= first && checkpoint.0 <= self.children.len(), "Checkpoint is outside of the open node");
+        self.parents.push((kind, checkpoint.0));
+    }
+
+    /// the single root node, every started node must be finished
+    pub fn finish(mut self) -> Arc<GreenNode> {
+        assert!(self.parents.is_empty(), "Unfinished nodes in the builder");
+        match (self.children.pop(), self.children.is_empty()) {
+            (Some(GreenElement::Node(root)), true) => root,
+            _ => panic!("The builder must hold exactly one root node"),
+        }
+    }
+}
+
+/// rebuilds the concrete tree of a parsed `root` from the spans of its nodes and all of the tokens
+///
+/// tokens not covered by a child, trivia and dropped punctuation, belong to the closest node around
+/// them, leaves matching a single token become that token and the root holds the whole input
+///
+/// the tree is only as lossless as the tokens: they must follow each other without gaps from the
+/// start of the input, so a lexer that skips text gives an error here. nodes without a span, such
+/// as ones added by a rewrite, are not rebuilt and their tokens go to the closest node around them
+/// that has one
+pub fn from_ast(root: &Node, tokens: &[TokenData]) -> Result<Arc<GreenNode>, CstError> {
+    let mut end = 0;
+    for token in tokens {
+        if token.span.0 != end || token.span.1 != end + token.value.len() {
+            let message = format!("Tokens must cover the input without gaps, `{}` at {}..{} does not follow", token.value, token.span.0, token.span.1);
+            return Err(CstError { message, offset: end });
+        }
+        end = token.span.1;
+    }
+    let mut builder = GreenBuilder::new();
+    let mut cursor = 0;
+    builder.start_node(root.kind);
+    ast_children(&mut builder, root, tokens, &mut cursor);
+    while cursor < tokens.len() {
+        builder.token(tokens[cursor].kind, &tokens[cursor].value);
+        cursor += 1;
+    }
+    builder.finish_node();
+    Ok(builder.finish())
+}
+
+/// tokens a concrete tree cannot be rebuilt from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CstError {
+    pub message: String,
+    /// byte offset where the tokens stop covering the input
+    pub offset: usize,
+}
+
+impl Display for CstError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at byte {}", self.message, self.offset)
+    }
+}
+
+impl std::error::Error for CstError {}
+
+fn ast_children(builder: &mut GreenBuilder, node: &Node, tokens: &[TokenData], cursor: &mut usize) {
+    for child in node.children.iter().filter(|child| child.span.is_known()) {
+        while *cursor < tokens.len() && tokens[*cursor].span.0 < child.span.start {
+            builder.token(tokens[*cursor].kind, &tokens[*cursor].value);
+            *cursor += 1;
+        }
+        let single = tokens.get(*cursor).filter(|token| token.span == (child.span.start, child.span.end));
+        match single {
+            Some(token) if child.children.is_empty() => {
+                builder.token(token.kind, &token.value);
+                *cursor += 1;
+            }
+            _ => {
+                builder.start_node(child.kind);
+                ast_children(builder, child, tokens, cursor);
+                while *cursor < tokens.len() && tokens[*cursor].span.1 <= child.span.end {
+                    builder.token(tokens[*cursor].kind, &tokens[*cursor].value);
+                    *cursor += 1;
+                }
+                builder.finish_node();
+            }
+        }
+    }
+}
+
+/// a node of the green tree seen from the root, knowing its parent and offset
+#[derive(Clone)]
+pub struct SyntaxNode(Rc<NodeData>);
+
+struct NodeData {
+    green: Arc<GreenNode>,
+    parent: Option<SyntaxNode>,
+    /// index in the children of the parent
+    index: usize,
+    offset: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntaxToken {
+    parent: SyntaxNode,
+    index: usize,
+    offset: usize,
+    green: Arc<GreenToken>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SyntaxElement {
+    Node(SyntaxNode),
+    Token(SyntaxToken),
+}
+
+impl SyntaxNode {
+    pub fn new_root(green: Arc<GreenNode>) -> Self {
+        Self(Rc::new(NodeData { green, parent: None, index: 0, offset: 0 }))
+    }
+
+    pub fn kind(&self) -> u32 {
+        self.0.green.kind
+    }
+
+    pub fn green(&self) -> &Arc<GreenNode> {
+        &self.0.green
+    }
+
+    pub fn parent(&self) -> Option<&SyntaxNode> {
+        self.0.parent.as_ref()
+    }
+
+    /// index of the node in the children of its parent, with tokens
+    pub fn index(&self) -> usize {
+        self.0.index
+    }
+
+    /// byte range of the node in the source
+    pub fn text_range(&self) -> Range<usize> {
+        self.0.offset..self.0.offset + self.0.green.text_len
+    }
+
+    pub fn text(&self) -> String {
+        self.0.green.to_string()
+    }
+
+    pub fn children_with_tokens(&self) -> Vec<SyntaxElement> {
+        let mut offset = self.0.offset;
+        let mut elements = vec![];
+        for (index, child) in self.0.green.children.iter().enumerate() {
+            elements.push(match child {
+                GreenElement::Node(green) => SyntaxElement::Node(SyntaxNode(Rc::new(NodeData {
+                    green: green.clone(),
+                    parent: Some(self.clone()),
+                    index,
+                    offset,
+                }))),
+                GreenElement::Token(green) => SyntaxElement::Token(SyntaxToken { parent: self.clone(), index, offset, green: green.clone() }),
+            });
+            offset += child.text_len();
+        }
+        elements
+    }
+
+    pub fn children(&self) -> Vec<SyntaxNode> {
+        self.children_with_tokens()
+            .into_iter()
+            .filter_map(|element| match element {
+                SyntaxElement::Node(node) => Some(node),
+                SyntaxElement::Token(_) => None,
+            })
+            .collect()
+    }
+
+    /// the node and every node under it, parents before their children
+    pub fn descendants(&self) -> Vec<SyntaxNode> {
+        let mut nodes = vec![self.clone()];
+        for child in self.children() {
+            nodes.extend(child.descendants());
+        }
+        nodes
+    }
+
+    /// every token under the node in source order
+    pub fn tokens(&self) -> Vec<SyntaxToken> {
+        let mut tokens = vec![];
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => tokens.extend(node.tokens()),
+                SyntaxElement::Token(token) => tokens.push(token),
+            }
+        }
+        tokens
+    }
+
+    /// the deepest token covering `offset`
+    pub fn token_at(&self, offset: usize) -> Option<SyntaxToken> {
+        self.tokens().into_iter().find(|token| token.text_range().contains(&offset))
+    }
+
+    /// one line per node and token, `kind@start..end` followed by the text of tokens
+    pub fn debug_tree(&self) -> String {
+        let mut out = String::new();
+        self.write_tree(&mut out, 0);
+        out
+    }
+
+    fn write_tree(&self, out: &mut String, depth: usize) {
+        let range = self.text_range();
+        out.push_str(&format!("{}{}@{}..{}\n", "  ".repeat(depth), self.kind(), range.start, range.end));
+        for element in self.children_with_tokens() {
+            match element {
+                SyntaxElement::Node(node) => node.write_tree(out, depth + 1),
+                SyntaxElement::Token(token) => {
+                    let range = token.text_range();
+                    out.push_str(&format!("{}{}@{}..{} {:?}\n", "  ".repeat(depth + 1), token.kind(), range.start, range.end, token.text()));
+                }
+            }
+        }
+    }
+}
+
+impl PartialEq for SyntaxNode {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.0.green, &other.0.green) && self.0.offset == other.0.offset
+    }
+}
+
+impl Eq for SyntaxNode {}
+
+impl std::fmt::Debug for SyntaxNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let range = self.text_range();
+        write!(f, "{}@{}..{}", self.kind(), range.start, range.end)
+    }
+}
+
+/// the exact text under the node
+impl Display for SyntaxNode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.0.green)
+    }
+}
+
+impl SyntaxToken {
+    pub fn kind(&self) -> u32 {
+        self.green.kind
+    }
+
+    pub fn text(&self) -> &str {
+        &self.green.text
+    }
+
+    pub fn text_range(&self) -> Range<usize> {
+        self.offset..self.offset + self.green.text.len()
+    }
+
+    pub fn parent(&self) -> &SyntaxNode {
+        &self.parent
+    }
+
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+/// a typed view over syntax nodes of one kind, see `cst_node!`
+pub trait AstNode: Sized {
+    fn can_cast(kind: u32) -> bool;
+    fn cast(node: SyntaxNode) -> Option<Self>;
+    fn syntax(&self) -> &SyntaxNode;
+
+    /// the first child that is an `N`
+    fn child<N: AstNode>(&self) -> Option<N> {
+        self.syntax().children().into_iter().find_map(N::cast)
+    }
+
+    /// every child that is an `N`
+    fn children<N: AstNode>(&self) -> Vec<N> {
+        self.syntax().children().into_iter().filter_map(N::cast).collect()
+    }
+
+    /// the first direct token of the given kind
+    fn token(&self, kind: u32) -> Option<SyntaxToken> {
+        self.syntax().children_with_tokens().into_iter().find_map(|element| match element {
+            SyntaxElement::Token(token) if token.kind() == kind => Some(token),
+            _ => None,
+        })
+    }
+}
+
+#[macro_export]
+macro_rules! cst_node {
+    ( $name:ident, $kind:expr ) => {
+        #[derive(Debug, Clone, PartialEq, Eq)]
+        pub struct $name($crate::cst::SyntaxNode);
+
+        impl $crate::cst::AstNode for $name {
+            fn can_cast(kind: u32) -> bool {
+                kind == $kind
+            }
+
+            fn cast(node: $crate::cst::SyntaxNode) -> Option<Self> {
+                Self::can_cast(node.kind()).then(|| Self(node))
+            }
+
+            fn syntax(&self) -> &$crate::cst::SyntaxNode {
+                &self.0
+            }
+        }
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ast::Value, grammar::{interpreter::Interpreter, Grammar}};
+
+    #[test]
+    fn test_builder() {
+        let mut builder = GreenBuilder::new();
+        builder.start_node(0);
+        builder.token(1, "1");
+        let checkpoint = builder.checkpoint();
+        builder.token(2, " ");
+        builder.start_node_at(checkpoint, 3);
+        builder.token(1, "1");
+        builder.finish_node();
+        builder.finish_node();
+        let green = builder.finish();
+        assert_eq!(green.to_string(), "1 1");
+        assert_eq!(green.text_len(), 3);
+        // both `1` tokens are the same allocation
+        let GreenElement::Token(first) = &green.children[0] else { panic!() };
+        let GreenElement::Node(inner) = &green.children[1] else { panic!() };
+        let GreenElement::Token(second) = &inner.children[1] else { panic!() };
+        assert!(Arc::ptr_eq(first, second));
+
+        let root = SyntaxNode::new_root(green);
+        let inner = &root.children()[0];
+        assert_eq!(inner.text_range(), 1..3);
+        assert_eq!(inner.parent(), Some(&root));
+        assert_eq!(root.token_at(2).map(|token| token.text_range()), Some(2..3));
+        assert_eq!(root.debug_tree(), "0@0..3\n  1@0..1 \"1\"\n  3@1..3\n    2@1..2 \" \"\n    1@2..3 \"1\"\n");
+    }
+
+    #[test]
+    fn test_from_ast() {
+        let grammar = Grammar::parse("
+            WS: /\\s+/;
+            NUM: /\\d+/;
+            @drop(WS) List: WS? ~\"[\" (Item (WS? \",\" WS? Item)*)? \"]\" WS?;
+            Item: NUM | List;
+        ").unwrap();
+        let interpreter = Interpreter::new(&grammar).unwrap();
+        let input = " [1, [2 ,3],  4] ";
+        let tokens = interpreter.tokenize(input).unwrap();
+        let node = interpreter.parse(input).unwrap();
+        let green = from_ast(&node, &tokens).unwrap();
+        assert_eq!(green.to_string(), input);
+
+        let root = SyntaxNode::new_root(green);
+        let items = root.children();
+        assert_eq!(items.iter().map(|item| item.text()).collect::<Vec<_>>(), vec!["1", "[2 ,3]", "4"]);
+        // the nested list keeps its dropped `[` and the space before `,`
+        let nested = &items[1].children()[0];
+        assert_eq!(nested.text_range(), 5..11);
+        assert_eq!(nested.tokens().len(), 6);
+    }
+
+    #[test]
+    fn test_from_ast_with_gaps() {
+        let tokens = vec![TokenData::new(1, "a".to_string(), "A".to_string(), (1, 1), (0, 1)), TokenData::new(1, "b".to_string(), "B".to_string(), (1, 3), (2, 3))];
+        let error = from_ast(&Node::new(0, "Root", Value::None), &tokens).unwrap_err();
+        assert_eq!(error.to_string(), "Tokens must cover the input without gaps, `b` at 2..3 does not follow at byte 1");
+    }
+
+    cst_node!(Item, 3);
+
+    #[test]
+    fn test_typed_view() {
+        let mut builder = GreenBuilder::new();
+        builder.start_node(0);
+        for text in ["a", "b"] {
+            builder.start_node(3);
+            builder.token(1, text);
+            builder.finish_node();
+            builder.token(2, ",");
+        }
+        builder.finish_node();
+        let root = SyntaxNode::new_root(builder.finish());
+        assert!(Item::cast(root.clone()).is_none());
+        let items = root.children().into_iter().filter_map(Item::cast).collect::<Vec<_>>();
+        assert_eq!(items.len(), 2);
+        assert_eq!(items[1].token(1).map(|token| token.text().to_string()), Some("b".to_string()));
+        assert_eq!(items[1].syntax().text_range(), 2..3);
+    }
+}
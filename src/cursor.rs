@@ -0,0 +1,261 @@
+use std::collections::VecDeque;
+
+use crate::ast::Node;
+
+/// a position in a tree of nodes that can move up, down and sideways
+///
+/// the cursor remembers the path from the root, so nodes do not need parent links
+#[derive(Debug, Clone)]
+pub struct Cursor<'a> {
+    node: &'a Node,
+    /// (parent, index of the next node down in its children) from the root to the current node
+    stack: Vec<(&'a Node, usize)>,
+}
+
+impl<'a> Cursor<'a> {
+    pub fn new(root: &'a Node) -> Self {
+        Self { node: root, stack: vec![] }
+    }
+
+    pub fn node(&self) -> &'a Node {
+        self.node
+    }
+
+    pub fn root(&self) -> &'a Node {
+        self.stack.first().map_or(self.node, |(root, _)| root)
+    }
+
+    /// 0 for the root
+    pub fn depth(&self) -> usize {
+        self.stack.len()
+    }
+
+    /// index of the node in the children of its parent
+    pub fn index(&self) -> Option<usize> {
+        self.stack.last().map(|(_, index)| *index)
+    }
+
+    /// indices of the children to follow from the root to reach the node
+    pub fn path(&self) -> Vec<usize> {
+        self.stack.iter().map(|(_, index)| *index).collect()
+    }
+
+    pub fn parent(&self) -> Option<&'a Node> {
+        self.stack.last().map(|(parent, _)| *parent)
+    }
+
+    pub fn next_sibling(&self) -> Option<&'a Node> {
+        let (parent, index) = self.stack.last()?;
+        parent.children.get(index + 1).map(|child| child.as_ref())
+    }
+
+    pub fn prev_sibling(&self) -> Option<&'a Node> {
+        let (parent, index) = self.stack.last()?;
+        parent.children.get(index.checked_sub(1)?).map(|child| child.as_ref())
+    }
+
+    /// the parent first, the root last
+    pub fn ancestors(&self) -> impl Iterator<Item = &'a Node> + '_ {
+        self.stack.iter().rev().map(|(parent, _)| *parent)
+    }
+
+    pub fn goto_parent(&mut self) -> bool {
+        match self.stack.pop() {
+            Some((parent, _)) => {
+                self.node = parent;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn goto_child(&mut self, index: usize) -> bool {
+        let node = self.node;
+        match node.children.get(index) {
+            Some(child) => {
+                self.stack.push((node, index));
+                self.node = child;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn goto_first_child(&mut self) -> bool {
+        self.goto_child(0)
+    }
+
+    pub fn goto_last_child(&mut self) -> bool {
+        match self.node.children.len() {
+            0 => false,
+            len => self.goto_child(len - 1),
+        }
+    }
+
+    pub fn goto_next_sibling(&mut self) -> bool {
+        self.goto_sibling(1)
+    }
+
+    pub fn goto_prev_sibling(&mut self) -> bool {
+        self.goto_sibling(-1)
+    }
+
+    fn goto_sibling(&mut self, step: isize) -> bool {
+        let Some((parent, index)) = self.stack.last_mut() else { return false };
+        let Some(sibling) = index.checked_add_signed(step).and_then(|next| parent.children.get(next).map(|child| (next, child))) else {
+            return false;
+        };
+        *index = sibling.0;
+        self.node = sibling.1;
+        true
+    }
+
+    pub fn goto_root(&mut self) {
+        self.node = self.root();
+        self.stack.clear();
+    }
+
+    /// follows child indices from the current node, staying put when one is missing
+    pub fn goto_path(&mut self, path: &[usize]) -> bool {
+        let saved = self.clone();
+        for index in path {
+            if !self.goto_child(*index) {
+                *self = saved;
+                return false;
+            }
+        }
+        true
+    }
+
+    /// cursors on the node and everything under it, parents before children
+    pub fn depth_first(&self) -> DepthFirst<'a> {
+        DepthFirst { next: Some(self.clone()), depth: self.depth() }
+    }
+
+    /// cursors on the node and everything under it, level by level
+    pub fn breadth_first(&self) -> BreadthFirst<'a> {
+        BreadthFirst { queue: VecDeque::from([self.clone()]) }
+    }
+}
+
+pub struct DepthFirst<'a> {
+    next: Option<Cursor<'a>>,
+    /// depth of the node the walk started from, it never goes above it
+    depth: usize,
+}
+
+impl<'a> Iterator for DepthFirst<'a> {
+    type Item = Cursor<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.next.take()?;
+        let mut cursor = current.clone();
+        if cursor.goto_first_child() {
+            self.next = Some(cursor);
+            return Some(current);
+        }
+        while cursor.depth() > self.depth {
+            if cursor.goto_next_sibling() {
+                self.next = Some(cursor);
+                break;
+            }
+            cursor.goto_parent();
+        }
+        Some(current)
+    }
+}
+
+pub struct BreadthFirst<'a> {
+    queue: VecDeque<Cursor<'a>>,
+}
+
+impl<'a> Iterator for BreadthFirst<'a> {
+    type Item = Cursor<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let current = self.queue.pop_front()?;
+        for index in 0..current.node().children.len() {
+            let mut child = current.clone();
+            child.goto_child(index);
+            self.queue.push_back(child);
+        }
+        Some(current)
+    }
+}
+
+impl Node {
+    pub fn cursor(&self) -> Cursor<'_> {
+        Cursor::new(self)
+    }
+
+    /// the node and every node under it, parents before children
+    pub fn descendants(&self) -> impl Iterator<Item = &Node> {
+        self.cursor().depth_first().map(|cursor| cursor.node())
+    }
+
+    /// the node and every node under it, level by level
+    pub fn breadth_first(&self) -> impl Iterator<Item = &Node> {
+        self.cursor().breadth_first().map(|cursor| cursor.node())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ast::Value;
+
+    /// a(b(d e) c(f))
+    fn tree() -> Node {
+        let leaf = |label: &str| Node::new(0, label, Value::None);
+        let mut b = leaf("b");
+        b.add_child(leaf("d"));
+        b.add_child(leaf("e"));
+        let mut c = leaf("c");
+        c.add_child(leaf("f"));
+        let mut a = leaf("a");
+        a.add_child(b);
+        a.add_child(c);
+        a
+    }
+
+    fn labels<'a>(nodes: impl Iterator<Item = &'a Node>) -> String {
+        nodes.map(|node| node.label.as_str()).collect()
+    }
+
+    #[test]
+    fn test_moves() {
+        let tree = tree();
+        let mut cursor = tree.cursor();
+        assert!(!cursor.goto_parent() && !cursor.goto_next_sibling());
+        assert!(cursor.goto_first_child() && cursor.goto_first_child());
+        assert_eq!(cursor.node().label, "d");
+        assert_eq!(cursor.next_sibling().map(|node| node.label.as_str()), Some("e"));
+        assert!(cursor.prev_sibling().is_none());
+        assert_eq!(labels(cursor.ancestors()), "ba");
+
+        assert!(cursor.goto_next_sibling() && !cursor.goto_next_sibling());
+        assert_eq!(cursor.path(), vec![0, 1]);
+        assert!(cursor.goto_parent() && cursor.goto_next_sibling() && cursor.goto_last_child());
+        assert_eq!((cursor.node().label.as_str(), cursor.depth(), cursor.index()), ("f", 2, Some(0)));
+
+        cursor.goto_root();
+        assert!(!cursor.goto_path(&[1, 1]));
+        assert_eq!(cursor.depth(), 0);
+        assert!(cursor.goto_path(&[0, 1]));
+        assert_eq!(cursor.node().label, "e");
+    }
+
+    #[test]
+    fn test_traversals() {
+        let tree = tree();
+        assert_eq!(labels(tree.descendants()), "abdecf");
+        assert_eq!(labels(tree.breadth_first()), "abcdef");
+
+        // a walk from inside the tree stays under its node and keeps the way up
+        let mut cursor = tree.cursor();
+        cursor.goto_first_child();
+        assert_eq!(labels(cursor.depth_first().map(|cursor| cursor.node())), "bde");
+        let deepest = cursor.breadth_first().last().unwrap();
+        assert_eq!(labels(deepest.ancestors()), "ba");
+    }
+}
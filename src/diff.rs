@@ -0,0 +1,474 @@
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    fmt::Display,
+    hash::{Hash, Hasher},
+};
+
+use crate::{ast::Node, printer::ColorChoice, sexp::write_value};
+
+/// one change from the old tree to the new one, paths are child indices from the root
+#[derive(Debug, Clone, PartialEq)]
+pub enum Edit {
+    /// `node` became child `index` of the node at `parent` in the new tree, without its
+    /// children when some of them come from the old tree
+    Insert { parent: Vec<usize>, index: usize, node: Node },
+    /// the node at `path` in the old tree was removed, the same way
+    Delete { path: Vec<usize>, node: Node },
+    /// the node kept its identity but its label, kind or value changed
+    Update { old: Vec<usize>, new: Vec<usize>, before: Node, after: Node },
+    /// the node went to another parent or another place among its siblings
+    Move { old: Vec<usize>, new: Vec<usize>, node: Node },
+}
+
+impl Display for Edit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Edit::Insert { parent, index, node } => write!(f, "insert {} into {} at {}", header(node), path_text(parent), index),
+            Edit::Delete { path, node } => write!(f, "delete {} at {}", header(node), path_text(path)),
+            Edit::Update { new, before, after, .. } => write!(f, "update {} to {} at {}", header(before), header(after), path_text(new)),
+            Edit::Move { old, new, node } => write!(f, "move {} from {} to {}", header(node), path_text(old), path_text(new)),
+        }
+    }
+}
+
+/// the edits between two trees and the new tree annotated with them
+#[derive(Debug, Clone)]
+pub struct TreeDiff {
+    /// in the order of the new tree, deletions last
+    pub edits: Vec<Edit>,
+    lines: Vec<Line>,
+}
+
+#[derive(Debug, Clone)]
+struct Line {
+    marker: char,
+    depth: usize,
+    text: String,
+}
+
+impl TreeDiff {
+    /// true when the trees only differ in spans or ignored nodes
+    pub fn is_empty(&self) -> bool {
+        self.edits.is_empty()
+    }
+
+    /// the new tree one node per line, `+` inserted, `-` deleted, `~` updated and `>` moved
+    pub fn render(&self, color: ColorChoice) -> String {
+        let color = color.enabled();
+        let mut out = String::new();
+        for line in self.lines.iter() {
+            let (start, stop) = match (color, line.marker) {
+                (true, '+') => ("\x1b[32m", "\x1b[0m"),
+                (true, '-') => ("\x1b[31m", "\x1b[0m"),
+                (true, '~') => ("\x1b[33m", "\x1b[0m"),
+                (true, '>') => ("\x1b[36m", "\x1b[0m"),
+                _ => ("", ""),
+            };
+            out.push_str(&format!("{}{} {}{}{}\n", start, line.marker, "  ".repeat(line.depth), line.text, stop));
+        }
+        out
+    }
+}
+
+impl Display for TreeDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.render(ColorChoice::Auto))
+    }
+}
+
+type Ignore = Box<dyn Fn(&Node) -> bool>;
+
+/// matches two trees GumTree style: identical subtrees first, from the largest, then their
+/// containers by the share of matched descendants, then the remaining children of matched nodes
+#[derive(Default)]
+pub struct Differ {
+    ignore: Vec<Ignore>,
+}
+
+/// the diff of two trees where every node counts
+pub fn diff(old: &Node, new: &Node) -> TreeDiff {
+    Differ::new().diff(old, new)
+}
+
+impl Differ {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// leaves out the nodes the predicate accepts with everything under them, like whitespace
+    /// and comments so that only the changes that matter remain
+    pub fn ignore(mut self, predicate: impl Fn(&Node) -> bool + 'static) -> Self {
+        self.ignore.push(Box::new(predicate));
+        self
+    }
+
+    pub fn diff(&self, old: &Node, new: &Node) -> TreeDiff {
+        let mut src = vec![];
+        self.flatten(old, None, vec![], &mut src);
+        let mut dst = vec![];
+        self.flatten(new, None, vec![], &mut dst);
+        let mut matching = Matching { to_dst: vec![None; src.len()], to_src: vec![None; dst.len()], src: &src, dst: &dst };
+        matching.top_down();
+        matching.bottom_up();
+        matching.finish()
+    }
+
+    /// the nodes in pre-order, so the subtree of `i` is `i..i + size`
+    fn flatten<'a>(&self, node: &'a Node, parent: Option<usize>, path: Vec<usize>, entries: &mut Vec<Entry<'a>>) -> usize {
+        let id = entries.len();
+        entries.push(Entry { node, parent, children: vec![], path: path.clone(), height: 1, size: 1, hash: 0 });
+        let mut children = vec![];
+        for (index, child) in node.children.iter().enumerate() {
+            if self.ignore.iter().any(|ignore| ignore(child)) {
+                continue;
+            }
+            let mut path = path.clone();
+            path.push(index);
+            children.push(self.flatten(child, Some(id), path, entries));
+        }
+        let mut hasher = DefaultHasher::new();
+        header(node).hash(&mut hasher);
+        children.iter().for_each(|&child| entries[child].hash.hash(&mut hasher));
+        let height = 1 + children.iter().map(|&child| entries[child].height).max().unwrap_or(0);
+        let size = entries.len() - id;
+        let entry = &mut entries[id];
+        (entry.children, entry.height, entry.size, entry.hash) = (children, height, size, hasher.finish());
+        id
+    }
+}
+
+struct Entry<'a> {
+    node: &'a Node,
+    parent: Option<usize>,
+    children: Vec<usize>,
+    /// in the whole tree, ignored nodes included
+    path: Vec<usize>,
+    height: usize,
+    size: usize,
+    /// of the node and everything under it
+    hash: u64,
+}
+
+struct Matching<'m, 'a> {
+    src: &'m [Entry<'a>],
+    dst: &'m [Entry<'a>],
+    to_dst: Vec<Option<usize>>,
+    to_src: Vec<Option<usize>>,
+}
+
+/// share of matched descendants needed to match two containers
+const MIN_DICE: f64 = 0.5;
+
+impl Matching<'_, '_> {
+    fn link(&mut self, s: usize, d: usize) {
+        self.to_dst[s] = Some(d);
+        self.to_src[d] = Some(s);
+    }
+
+    /// identical subtrees have the same shape, so their nodes pair up in order
+    fn link_subtree(&mut self, s: usize, d: usize) {
+        for k in 0..self.src[s].size {
+            self.link(s + k, d + k);
+        }
+    }
+
+    fn same_kind(&self, s: usize, d: usize) -> bool {
+        let (a, b) = (self.src[s].node, self.dst[d].node);
+        a.label == b.label && a.kind == b.kind
+    }
+
+    fn top_down(&mut self) {
+        let mut by_hash: HashMap<u64, Vec<usize>> = HashMap::new();
+        self.dst.iter().enumerate().for_each(|(d, entry)| by_hash.entry(entry.hash).or_default().push(d));
+        let mut counts: HashMap<u64, usize> = HashMap::new();
+        self.src.iter().for_each(|entry| *counts.entry(entry.hash).or_default() += 1);
+
+        let mut order = (0..self.src.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&s| std::cmp::Reverse(self.src[s].height));
+        for s in order {
+            if self.to_dst[s].is_some() {
+                continue;
+            }
+            let Some(candidates) = by_hash.get(&self.src[s].hash) else { continue };
+            let free = candidates.iter().copied().filter(|&d| self.to_src[d].is_none()).collect::<Vec<_>>();
+            // repeated leaves like `,` would match anywhere, they are left to the recovery
+            if free.is_empty() || self.src[s].height < 2 && (free.len() > 1 || counts[&self.src[s].hash] > 1) {
+                continue;
+            }
+            let score = |d: usize| match (self.src[s].parent, self.dst[d].parent) {
+                (Some(ps), Some(pd)) if self.to_dst[ps] == Some(pd) => 2,
+                (Some(ps), Some(pd)) if self.same_kind(ps, pd) => 1,
+                _ => 0,
+            };
+            let best = free.iter().copied().min_by_key(|&d| std::cmp::Reverse(score(d))).unwrap();
+            self.link_subtree(s, best);
+        }
+    }
+
+    fn dice(&self, s: usize, d: usize) -> f64 {
+        let (s_size, d_size) = (self.src[s].size, self.dst[d].size);
+        let inside = |x: usize| x > d && x < d + d_size;
+        let common = (s + 1..s + s_size).filter(|&x| self.to_dst[x].is_some_and(inside)).count();
+        2.0 * common as f64 / (s_size + d_size - 2) as f64
+    }
+
+    fn bottom_up(&mut self) {
+        for s in (0..self.src.len()).rev() {
+            if s == 0 {
+                if self.to_dst[0].is_none() && self.to_src[0].is_none() {
+                    self.link(0, 0);
+                }
+                if let Some(d) = self.to_dst[0] {
+                    self.recover(0, d);
+                }
+                continue;
+            }
+            if self.to_dst[s].is_some() || self.src[s].children.is_empty() {
+                continue;
+            }
+            let candidates = (0..self.dst.len()).filter(|&d| self.to_src[d].is_none() && self.same_kind(s, d) && !self.dst[d].children.is_empty());
+            let best = candidates.map(|d| (d, self.dice(s, d))).filter(|(_, dice)| *dice >= MIN_DICE).fold(None, |best: Option<(usize, f64)>, (d, dice)| match best {
+                Some((_, top)) if top >= dice => best,
+                _ => Some((d, dice)),
+            });
+            if let Some((d, _)) = best {
+                self.link(s, d);
+                self.recover(s, d);
+            }
+        }
+    }
+
+    /// pairs the children left over under two matched nodes, identical ones first
+    fn recover(&mut self, s: usize, d: usize) {
+        for &sc in self.src[s].children.iter() {
+            let free = self.dst[d].children.iter().copied().find(|&dc| self.to_src[dc].is_none() && self.dst[dc].hash == self.src[sc].hash);
+            if let (None, Some(dc)) = (self.to_dst[sc], free) {
+                self.link_subtree(sc, dc);
+            }
+        }
+        for &sc in self.src[s].children.iter() {
+            let free = self.dst[d].children.iter().copied().find(|&dc| self.to_src[dc].is_none() && self.same_kind(sc, dc));
+            if let (None, Some(dc)) = (self.to_dst[sc], free) {
+                self.link(sc, dc);
+                self.recover(sc, dc);
+            }
+        }
+    }
+
+    fn updated(&self, s: usize, d: usize) -> bool {
+        let (a, b) = (self.src[s].node, self.dst[d].node);
+        a.label != b.label || a.kind != b.kind || a.value != b.value
+    }
+
+    /// the matched nodes that changed parent or left the longest common order of their siblings
+    fn moved(&self) -> Vec<bool> {
+        let mut moved = vec![false; self.dst.len()];
+        for (d, entry) in self.dst.iter().enumerate() {
+            let (Some(pd), Some(s)) = (entry.parent, self.to_src[d]) else { continue };
+            moved[d] = self.src[s].parent.and_then(|ps| self.to_dst[ps]) != Some(pd);
+        }
+        for (pd, entry) in self.dst.iter().enumerate() {
+            let Some(ps) = self.to_src[pd] else { continue };
+            let stayed = |&&c: &&usize| !moved[c] && self.to_src[c].is_some();
+            let new_order = entry.children.iter().filter(stayed).map(|&c| self.to_src[c].unwrap()).collect::<Vec<_>>();
+            let mut old_order = self.src[ps].children.iter().copied().filter(|s| new_order.contains(s)).collect::<Vec<_>>();
+            old_order.sort();
+            let common = lcs(&old_order, &new_order);
+            for s in new_order.into_iter().filter(|s| !common.contains(s)) {
+                moved[self.to_dst[s].unwrap()] = true;
+            }
+        }
+        moved
+    }
+
+    /// no node under `id` is matched
+    fn fresh(entries: &[Entry], matched: &[Option<usize>], id: usize) -> bool {
+        (id..id + entries[id].size).all(|x| matched[x].is_none())
+    }
+
+    /// the node with its children when they are all new, without them otherwise
+    fn shown(entries: &[Entry], matched: &[Option<usize>], id: usize) -> Node {
+        let mut node = entries[id].node.clone();
+        if !Self::fresh(entries, matched, id) {
+            node.children.clear();
+        }
+        node
+    }
+
+    fn finish(&self) -> TreeDiff {
+        let moved = self.moved();
+        let mut edits = vec![];
+        for (d, entry) in self.dst.iter().enumerate() {
+            match self.to_src[d] {
+                None => {
+                    let covered = entry.parent.is_some_and(|p| self.to_src[p].is_none() && Self::fresh(self.dst, &self.to_src, p));
+                    if !covered {
+                        let (parent, index) = match entry.path.split_last() {
+                            Some((index, parent)) => (parent.to_vec(), *index),
+                            None => (vec![], 0),
+                        };
+                        edits.push(Edit::Insert { parent, index, node: Self::shown(self.dst, &self.to_src, d) });
+                    }
+                }
+                Some(s) => {
+                    let (old, new) = (self.src[s].path.clone(), entry.path.clone());
+                    if self.updated(s, d) {
+                        let before = Node { children: vec![], ..self.src[s].node.clone() };
+                        let after = Node { children: vec![], ..entry.node.clone() };
+                        edits.push(Edit::Update { old: old.clone(), new: new.clone(), before, after });
+                    }
+                    if moved[d] {
+                        edits.push(Edit::Move { old, new, node: Node { children: vec![], ..entry.node.clone() } });
+                    }
+                }
+            }
+        }
+        for (s, entry) in self.src.iter().enumerate() {
+            let covered = entry.parent.is_some_and(|p| self.to_dst[p].is_none() && Self::fresh(self.src, &self.to_dst, p));
+            if self.to_dst[s].is_none() && !covered {
+                edits.push(Edit::Delete { path: entry.path.clone(), node: Self::shown(self.src, &self.to_dst, s) });
+            }
+        }
+
+        let mut lines = vec![];
+        // an old root that was replaced is shown before the new one
+        if self.to_dst[0].is_none() {
+            self.deleted_lines(0, 0, &mut lines);
+        }
+        self.lines(0, 0, &moved, &mut lines);
+        TreeDiff { edits, lines }
+    }
+
+    fn lines(&self, d: usize, depth: usize, moved: &[bool], lines: &mut Vec<Line>) {
+        let entry = &self.dst[d];
+        let (marker, text) = match self.to_src[d] {
+            None => ('+', header(entry.node)),
+            Some(s) if self.updated(s, d) => ('~', format!("{} → {}", header(self.src[s].node), header(entry.node))),
+            Some(s) if moved[d] => ('>', format!("{} (from {})", header(entry.node), path_text(&self.src[s].path))),
+            Some(_) => (' ', header(entry.node)),
+        };
+        lines.push(Line { marker, depth, text });
+
+        // the deleted children of the old node go back between the others, in their old order
+        let deleted = match self.to_src[d] {
+            Some(s) => self.src[s].children.iter().copied().filter(|&c| self.to_dst[c].is_none()).collect(),
+            None => vec![],
+        };
+        let mut deleted = deleted.into_iter().peekable();
+        for &child in entry.children.iter() {
+            if let Some(sc) = self.to_src[child].filter(|&sc| self.src[sc].parent == self.to_src[d]) {
+                while let Some(gone) = deleted.next_if(|&gone| gone < sc) {
+                    self.deleted_lines(gone, depth + 1, lines);
+                }
+            }
+            self.lines(child, depth + 1, moved, lines);
+        }
+        deleted.for_each(|gone| self.deleted_lines(gone, depth + 1, lines));
+    }
+
+    fn deleted_lines(&self, s: usize, depth: usize, lines: &mut Vec<Line>) {
+        lines.push(Line { marker: '-', depth, text: header(self.src[s].node) });
+        for &child in self.src[s].children.iter().filter(|&&child| self.to_dst[child].is_none()) {
+            self.deleted_lines(child, depth + 1, lines);
+        }
+    }
+}
+
+/// longest common subsequence of two sequences of distinct items, keeping the earlier items of `a` on ties
+fn lcs(a: &[usize], b: &[usize]) -> Vec<usize> {
+    let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            table[i][j] = if a[i] == b[j] { table[i + 1][j + 1] + 1 } else { table[i + 1][j].max(table[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j, mut common) = (0, 0, vec![]);
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            common.push(a[i]);
+            (i, j) = (i + 1, j + 1);
+        } else if table[i + 1][j] > table[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    common
+}
+
+/// `Label #kind value`, the value as in S-expressions
+fn header(node: &Node) -> String {
+    let mut text = format!("{} #{}", node.label, node.kind);
+    if !node.value.is_none() {
+        text.push(' ');
+        write_value(&mut text, &node.value);
+    }
+    text
+}
+
+fn path_text(path: &[usize]) -> String {
+    match path.is_empty() {
+        true => "root".to_string(),
+        false => path.iter().map(|index| index.to_string()).collect::<Vec<_>>().join("."),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{sexp::from_sexp, span::Span};
+
+    fn tree(text: &str) -> Node {
+        from_sexp(text).unwrap()
+    }
+
+    #[test]
+    fn test_edits() {
+        let old = tree("(Call (ID \"f\") (Args (N 1) (N 2) (Neg (N 5))))");
+        let new = tree("(Call (ID \"g\") (Args (N 1) (N 3) (N 4)))");
+        let result = diff(&old, &new);
+        let edits = result.edits.iter().map(|edit| edit.to_string()).collect::<Vec<_>>();
+        assert_eq!(
+            edits,
+            vec![
+                "update ID #0 \"f\" to ID #0 \"g\" at 0",
+                "update N #0 2 to N #0 3 at 1.1",
+                "insert N #0 4 into 1 at 2",
+                "delete Neg #0 at 1.2",
+            ]
+        );
+        assert!(matches!(&result.edits[3], Edit::Delete { node, .. } if node.children.len() == 1));
+
+        let expected = "  Call #0
+~   ID #0 \"f\" → ID #0 \"g\"
+    Args #0
+      N #0 1
+~     N #0 2 → N #0 3
++     N #0 4
+-     Neg #0
+-       N #0 5
+";
+        assert_eq!(result.render(ColorChoice::Never), expected);
+        assert!(result.render(ColorChoice::Always).contains("\x1b[32m+     N #0 4\x1b[0m"));
+    }
+
+    #[test]
+    fn test_moves() {
+        let old = tree("(Block (Let \"a\" (N 1)) (Let \"b\" (N 2)) (Let \"c\" (N 3)) (If (Cond)))");
+        let new = tree("(Block (Let \"c\" (N 3)) (Let \"a\" (N 1)) (If (Cond) (Let \"b\" (N 2))))");
+        let edits = diff(&old, &new).edits.iter().map(|edit| edit.to_string()).collect::<Vec<_>>();
+        assert_eq!(edits, vec!["move Let #0 \"c\" from 2 to 0", "move Let #0 \"b\" from 1 to 2.1"]);
+        assert!(diff(&old, &old).is_empty());
+    }
+
+    #[test]
+    fn test_formatting_only() {
+        let mut old = tree("(Sum (N 1) (WS \" \") (Plus) (N 2))");
+        old.span = Span::new(0, 5, 1, 1);
+        let new = tree("(Sum (N 1) (Plus) (WS \"  \") (N 2) (Comment \"; two\"))");
+        assert!(!diff(&old, &new).is_empty());
+        let trivia = Differ::new().ignore(|node| node.label == "WS").ignore(|node| node.label == "Comment");
+        assert!(trivia.diff(&old, &new).is_empty());
+        assert!(!trivia.diff(&old, &tree("(Sum (N 1) (Minus) (N 2))")).is_empty());
+    }
+}
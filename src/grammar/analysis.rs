@@ -0,0 +1,303 @@
+use std::{collections::{BTreeMap, BTreeSet, HashMap, HashSet}, fmt::Display};
+
+use crate::{parser::Fixity, span::Span};
+
+use super::{Expr, Grammar};
+
+/// marks the end of the input in FOLLOW sets
+pub const END: &str = "$";
+
+/// nullable, FIRST and FOLLOW sets of every rule of a grammar
+///
+/// the sets hold single tokens, so conflicts are LL(1) ones, deciding with more lookahead
+/// (LL(k) FIRST sets) is not done here, `ParserState::attempt` and `choice` backtrack instead
+#[derive(Debug, Clone)]
+pub struct Analysis<'g> {
+    grammar: &'g Grammar,
+    pub nullable: HashSet<&'g str>,
+    /// tokens that can start each rule
+    pub first: HashMap<&'g str, BTreeSet<&'g str>>,
+    /// tokens that can come right after each rule, `END` included
+    pub follow: HashMap<&'g str, BTreeSet<&'g str>>,
+}
+
+/// a place where one token of lookahead cannot decide between two ways to continue
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Conflict {
+    pub rule: String,
+    pub span: Span,
+    /// the two competing alternatives in `.ars` syntax, an empty one means skipping the construct
+    pub alternatives: (String, String),
+    /// tokens that start both alternatives
+    pub tokens: Vec<String>,
+}
+
+impl Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let describe = |alternative: &str| if alternative.is_empty() { "skipping it".to_string() } else { format!("`{}`", alternative) };
+        write!(
+            f,
+            "{}: LL(1) conflict in `{}` between {} and {} on {}",
+            self.span,
+            self.rule,
+            describe(&self.alternatives.0),
+            describe(&self.alternatives.1),
+            self.tokens.join(", ")
+        )
+    }
+}
+
+impl<'g> Analysis<'g> {
+    pub fn new(grammar: &'g Grammar) -> Self {
+        let mut analysis = Self {
+            grammar,
+            nullable: grammar.nullable_rules(),
+            first: grammar.rules.iter().map(|rule| (rule.name.as_str(), BTreeSet::new())).collect(),
+            follow: grammar.rules.iter().map(|rule| (rule.name.as_str(), BTreeSet::new())).collect(),
+        };
+        // a `@pratt` rule can start with a prefix operator and be followed by the others
+        for rule in grammar.rules.iter().filter(|rule| rule.attributes.pratt) {
+            for precedence in grammar.precedence.iter() {
+                let set = match precedence.fixity {
+                    Fixity::Prefix => &mut analysis.first,
+                    _ => &mut analysis.follow,
+                };
+                set.get_mut(rule.name.as_str()).unwrap().extend(precedence.operators.iter().map(String::as_str));
+            }
+        }
+        loop {
+            let mut changed = false;
+            for rule in grammar.rules.iter() {
+                let first = analysis.first_of(&rule.expr);
+                let entry = analysis.first.get_mut(rule.name.as_str()).unwrap();
+                let before = entry.len();
+                entry.extend(first);
+                changed |= entry.len() != before;
+            }
+            if !changed {
+                break;
+            }
+        }
+        if let Some(start) = grammar.start_rule() {
+            analysis.follow.get_mut(start.name.as_str()).unwrap().insert(END);
+        }
+        loop {
+            let mut changed = false;
+            for rule in grammar.rules.iter() {
+                let after = analysis.follow[rule.name.as_str()].clone();
+                changed |= analysis.spread_follow(&rule.expr, &after);
+            }
+            if !changed {
+                break;
+            }
+        }
+        analysis
+    }
+
+    pub fn is_nullable(&self, expr: &Expr) -> bool {
+        expr.is_nullable(&self.nullable)
+    }
+
+    /// tokens that can start `expr`
+    pub fn first_of(&self, expr: &'g Expr) -> BTreeSet<&'g str> {
+        match expr {
+            Expr::Ref(name, _) => match self.first.get(name.as_str()) {
+                Some(first) => first.clone(),
+                None => BTreeSet::from([name.as_str()]),
+            },
+            Expr::Sequence(items) => self.first_of_sequence(items),
+            Expr::Choice(alternatives) => alternatives.iter().flat_map(|alternative| self.first_of(alternative)).collect(),
+            Expr::Optional(inner) | Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => self.first_of(inner),
+            Expr::Capture(_, inner) | Expr::Drop(inner) => self.first_of(inner),
+        }
+    }
+
+    fn first_of_sequence(&self, items: &'g [Expr]) -> BTreeSet<&'g str> {
+        let mut first = BTreeSet::new();
+        for item in items {
+            first.extend(self.first_of(item));
+            if !self.is_nullable(item) {
+                break;
+            }
+        }
+        first
+    }
+
+    /// tokens that can be seen after `items` when `after` can follow the whole sequence
+    fn follow_of_sequence(&self, items: &'g [Expr], after: &BTreeSet<&'g str>) -> BTreeSet<&'g str> {
+        let mut follow = self.first_of_sequence(items);
+        if items.iter().all(|item| self.is_nullable(item)) {
+            follow.extend(after.iter().copied());
+        }
+        follow
+    }
+
+    /// adds `after` to the FOLLOW set of every rule that can end `expr`, returns whether anything changed
+    fn spread_follow(&mut self, expr: &'g Expr, after: &BTreeSet<&'g str>) -> bool {
+        match expr {
+            Expr::Ref(name, _) => match self.follow.get_mut(name.as_str()) {
+                Some(follow) => {
+                    let before = follow.len();
+                    follow.extend(after.iter().copied());
+                    follow.len() != before
+                }
+                None => false,
+            },
+            Expr::Sequence(items) => {
+                let mut changed = false;
+                for (i, item) in items.iter().enumerate() {
+                    let follow = self.follow_of_sequence(&items[i + 1..], after);
+                    changed |= self.spread_follow(item, &follow);
+                }
+                changed
+            }
+            Expr::Choice(alternatives) => {
+                let mut changed = false;
+                for alternative in alternatives {
+                    changed |= self.spread_follow(alternative, after);
+                }
+                changed
+            }
+            Expr::Optional(inner) | Expr::Capture(_, inner) | Expr::Drop(inner) => self.spread_follow(inner, after),
+            Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => {
+                let mut follow = self.first_of(inner);
+                follow.extend(after.iter().copied());
+                self.spread_follow(inner, &follow)
+            }
+        }
+    }
+
+    /// every LL(1) conflict of the grammar, some may be resolved by a second token of lookahead
+    pub fn conflicts(&self) -> Vec<Conflict> {
+        let mut conflicts = vec![];
+        for rule in self.grammar.rules.iter() {
+            let after = &self.follow[rule.name.as_str()];
+            self.expr_conflicts(&rule.name, &rule.expr, after, rule.span, &mut conflicts);
+        }
+        conflicts
+    }
+
+    fn expr_conflicts(&self, rule: &str, expr: &'g Expr, after: &BTreeSet<&'g str>, span: Span, conflicts: &mut Vec<Conflict>) {
+        let span = expr.first_span().unwrap_or(span);
+        let mut report = |left: String, right: String, tokens: BTreeSet<&str>| {
+            if !tokens.is_empty() {
+                conflicts.push(Conflict {
+                    rule: rule.to_string(),
+                    span,
+                    alternatives: (left, right),
+                    tokens: tokens.into_iter().map(|token| token.to_string()).collect(),
+                });
+            }
+        };
+        match expr {
+            Expr::Ref(_, _) => {}
+            Expr::Capture(_, inner) | Expr::Drop(inner) => self.expr_conflicts(rule, inner, after, span, conflicts),
+            Expr::Sequence(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    let follow = self.follow_of_sequence(&items[i + 1..], after);
+                    self.expr_conflicts(rule, item, &follow, span, conflicts);
+                }
+            }
+            Expr::Choice(alternatives) => {
+                // an alternative that matches nothing is chosen by what follows the choice
+                let lookahead = alternatives.iter().map(|alternative| {
+                    let mut lookahead = self.first_of(alternative);
+                    if self.is_nullable(alternative) {
+                        lookahead.extend(after.iter().copied());
+                    }
+                    lookahead
+                }).collect::<Vec<_>>();
+                for i in 0..alternatives.len() {
+                    for j in i + 1..alternatives.len() {
+                        let tokens = lookahead[i].intersection(&lookahead[j]).copied().collect();
+                        report(alternatives[i].to_string(), alternatives[j].to_string(), tokens);
+                    }
+                }
+                for alternative in alternatives {
+                    self.expr_conflicts(rule, alternative, after, span, conflicts);
+                }
+            }
+            Expr::Optional(inner) | Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => {
+                let tokens = self.first_of(inner).intersection(after).copied().collect();
+                report(inner.to_string(), String::new(), tokens);
+                let mut follow = after.clone();
+                if !matches!(expr, Expr::Optional(_)) {
+                    follow.extend(self.first_of(inner));
+                }
+                self.expr_conflicts(rule, inner, &follow, span, conflicts);
+            }
+        }
+    }
+
+    /// which alternative of each rule to take for a lookahead token, the whole rule counts as
+    /// alternative 0 when it is not a choice, conflicting entries keep the first alternative
+    pub fn prediction_table(&self) -> BTreeMap<(&'g str, &'g str), usize> {
+        let mut table = BTreeMap::new();
+        for rule in self.grammar.rules.iter() {
+            let after = &self.follow[rule.name.as_str()];
+            let alternatives = match &rule.expr {
+                Expr::Choice(alternatives) => alternatives.iter().collect(),
+                expr => vec![expr],
+            };
+            for (i, alternative) in alternatives.into_iter().enumerate() {
+                let mut lookahead = self.first_of(alternative);
+                if self.is_nullable(alternative) {
+                    lookahead.extend(after.iter().copied());
+                }
+                for token in lookahead {
+                    table.entry((rule.name.as_str(), token)).or_insert(i);
+                }
+            }
+        }
+        table
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set<'a>(items: &[&'a str]) -> BTreeSet<&'a str> {
+        items.iter().copied().collect()
+    }
+
+    #[test]
+    fn test_sets() {
+        let grammar = Grammar::parse("
+            NUM: /\\d+/;
+            Expr: Term Rest;
+            Rest: (\"+\" Term)*;
+            Term: NUM | \"(\" Expr \")\";
+        ").unwrap();
+        let analysis = Analysis::new(&grammar);
+        assert!(analysis.nullable.contains("Rest"));
+        assert_eq!(analysis.first["Expr"], set(&["\"(\"", "NUM"]));
+        assert_eq!(analysis.first["Rest"], set(&["\"+\""]));
+        assert_eq!(analysis.follow["Expr"], set(&["$", "\")\""]));
+        assert_eq!(analysis.follow["Term"], set(&["$", "\")\"", "\"+\""]));
+        assert!(analysis.conflicts().is_empty());
+        let table = analysis.prediction_table();
+        assert_eq!(table[&("Term", "\"(\"")], 1);
+        assert_eq!(table[&("Rest", "$")], 0);
+    }
+
+    #[test]
+    fn test_conflicts() {
+        let grammar = Grammar::parse("
+            ID: /[a-z]+/;
+            Stmt: ID \"=\" ID | ID \"(\" \")\" | Block;
+            Block: (\"{\" Stmt)? \"{\";
+        ").unwrap();
+        let conflicts = Analysis::new(&grammar).conflicts().iter().map(|conflict| conflict.to_string()).collect::<Vec<_>>();
+        assert_eq!(conflicts, vec![
+            "3:19: LL(1) conflict in `Stmt` between `ID \"=\" ID` and `ID \"(\" \")\"` on ID",
+            "4:21: LL(1) conflict in `Block` between `\"{\" Stmt` and skipping it on \"{\"",
+        ]);
+    }
+
+    #[test]
+    fn test_example_is_ll1() {
+        let grammar = Grammar::parse(include_str!("../../assets/grammars/small_example_lang.ars")).unwrap();
+        assert!(Analysis::new(&grammar).conflicts().is_empty());
+    }
+}
@@ -0,0 +1,268 @@
+use std::fmt::Write;
+
+use super::{Expr, Grammar, GrammarError, Pattern};
+
+/// name of the enum variant generated for a token or a rule
+fn variant_name(grammar: &Grammar, name: &str) -> String {
+    let name = unqualified(name);
+    match grammar.token(&name) {
+        Some(token) if !is_identifier(&name) => format!("Lit{}", token.kind),
+        _ => name,
+    }
+}
+
+/// `namespace.Name` becomes `namespace_Name`
+fn unqualified(name: &str) -> String {
+    if name.starts_with('"') { name.to_string() } else { name.replace('.', "_") }
+}
+
+fn is_identifier(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_') && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+fn camel_case(name: &str) -> String {
+    unqualified(name).split('_')
+        .filter(|part| !part.is_empty())
+        .map(|part| {
+            let mut chars = part.chars();
+            let first = chars.next().unwrap().to_ascii_uppercase();
+            std::iter::once(first).chain(chars).collect::<String>()
+        })
+        .collect()
+}
+
+fn snake_case(name: &str) -> String {
+    let mut result = String::new();
+    for (i, c) in unqualified(name).chars().enumerate() {
+        if c.is_ascii_uppercase() {
+            if i > 0 && !result.ends_with('_') {
+                result.push('_');
+            }
+            result.push(c.to_ascii_lowercase());
+        } else {
+            result.push(c);
+        }
+    }
+    result
+}
+
+fn parse_fn(rule: &str) -> String {
+    format!("parse_{}", snake_case(rule))
+}
+
+/// turns a grammar into Rust source that declares a kind enum, a configured lexer
+/// and one `Parser` per rule, meant to be `include!`d from `OUT_DIR`
+pub fn generate(grammar: &Grammar) -> Result<String, GrammarError> {
+    for rule in grammar.rules.iter() {
+        let mut error = None;
+        rule.expr.walk(&mut |expr| {
+            if let Expr::Ref(name, span) = expr {
+                if error.is_none() && grammar.token(name).is_none() && grammar.rule(name).is_none() {
+                    error = Some(GrammarError::new(format!("`{}` is not defined", name), *span));
+                }
+            }
+        });
+        if let Some(error) = error {
+            return Err(error);
+        }
+        if let Some(name) = rule.attributes.drop.iter().find(|name| grammar.token(name).is_none()) {
+            return Err(GrammarError::new(format!("`@drop` of `{}` which is not a token", name), rule.span));
+        }
+    }
+    // fail early on patterns the lexer would reject at runtime
+    grammar.token_patterns()?;
+    let operators = grammar.operators()?;
+
+    let mut out = String::new();
+    writeln!(out, "// generated by ars, do not edit").unwrap();
+    writeln!(out).unwrap();
+
+    let variants = grammar.tokens.iter().map(|token| variant_name(grammar, &token.name))
+        .chain(grammar.rules.iter().map(|rule| unqualified(&rule.name)))
+        .collect::<Vec<_>>();
+    writeln!(out, "#[allow(non_camel_case_types, clippy::upper_case_acronyms, clippy::wrong_self_convention)]").unwrap();
+    writeln!(out, "mod kinds {{").unwrap();
+    writeln!(out, "    ars::build_kinds!(Kind, {});", variants.join(", ")).unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out, "pub use kinds::Kind;").unwrap();
+    writeln!(out).unwrap();
+
+    writeln!(out, "pub fn lexer() -> ars::lexer::Lexer {{").unwrap();
+    writeln!(out, "    ars::build_lexer!(").unwrap();
+    for (i, token) in grammar.tokens.iter().enumerate() {
+        let separator = if i + 1 < grammar.tokens.len() { "," } else { "" };
+        match &token.pattern {
+            Pattern::Literal(lit) => writeln!(out, "        ars::token::Token::new_lit({:?}, {}, {:?}){}", token.name, token.kind, lit, separator),
+            Pattern::Regex(regex) => writeln!(out, "        ars::token::Token::new_regex_from_str({:?}, {}, {:?}){}", token.name, token.kind, regex, separator),
+        }.unwrap();
+    }
+    writeln!(out, "    )").unwrap();
+    writeln!(out, "}}").unwrap();
+
+    if !operators.is_empty() {
+        writeln!(out).unwrap();
+        writeln!(out, "const OPERATORS: &[ars::parser::Operator] = &[").unwrap();
+        for operator in operators.iter() {
+            writeln!(out, "    ars::parser::Operator::new({}, ars::parser::Fixity::{:?}, {}),", operator.kind, operator.fixity, operator.precedence).unwrap();
+        }
+        writeln!(out, "];").unwrap();
+    }
+
+    if let Some(start) = grammar.start_rule() {
+        writeln!(out).unwrap();
+        writeln!(out, "/// parses the whole input with `{}`", start.name).unwrap();
+        writeln!(out, "pub fn parse(input: &str) -> ars::parser::ParseResult {{").unwrap();
+        writeln!(out, "    let mut lexer = lexer();").unwrap();
+        writeln!(out, "    lexer.begin(input);").unwrap();
+        writeln!(out, "    let tokens = lexer.try_all().map_err(|location| ars::parser::ParseError::InvalidCharacter {{ location }})?;").unwrap();
+        writeln!(out, "    let mut state = ars::parser::ParserState::new(tokens, None).with_memo();").unwrap();
+        writeln!(out, "    let node = {}(&mut state)?;", parse_fn(&start.name)).unwrap();
+        writeln!(out, "    match state.lookahead(0) {{").unwrap();
+        writeln!(out, "        Some(token) => Err(ars::parser::ParseError::Unexpected {{ expected: vec![], found: token.clone() }}),").unwrap();
+        writeln!(out, "        None => Ok(node),").unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+
+    for rule in grammar.rules.iter() {
+        let name = camel_case(&rule.name);
+        let function = parse_fn(&rule.name);
+        writeln!(out).unwrap();
+        writeln!(out, "/// `{}` as a `Parser`, whose `parse` panics on errors, `try_parse` and `{}` return them", rule.name, function).unwrap();
+        writeln!(out, "pub struct {};", name).unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "impl ars::parser::Parser for {} {{", name).unwrap();
+        writeln!(out, "    fn parse(&self, state: &mut ars::parser::ParserState) -> ars::ast::Node {{").unwrap();
+        writeln!(out, "        {}(state).unwrap_or_else(|e| panic!(\"{{}}\", e))", function).unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "    fn try_parse(&self, state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {{").unwrap();
+        writeln!(out, "        {}(state)", function).unwrap();
+        writeln!(out, "    }}").unwrap();
+        writeln!(out, "}}").unwrap();
+        writeln!(out).unwrap();
+        writeln!(out, "pub fn {}(state: &mut ars::parser::ParserState) -> ars::parser::ParseResult {{", function).unwrap();
+        let drop = rule.attributes.drop.iter().map(|name| grammar.token(name).unwrap().kind.to_string()).collect::<Vec<_>>();
+        let shape = format!(
+            "{:?}, {:?}, &[{}], children?",
+            rule.node_label(),
+            rule.attributes.value.as_deref(),
+            drop.join(", ")
+        );
+        writeln!(out, "    state.rule({}, |state| {{", rule.kind).unwrap();
+        if rule.attributes.pratt {
+            let operators = if operators.is_empty() { "&[]" } else { "OPERATORS" };
+            writeln!(out, "        state.pratt({}, {}, &|state: &mut ars::parser::ParserState| {{", operators, rule.node_kind()).unwrap();
+            writeln!(out, "            let children: ars::parser::ParseResult<Vec<ars::ast::Node>> = {};", expr(grammar, &rule.expr, 3)).unwrap();
+            writeln!(out, "            Ok(ars::grammar::interpreter::operand_node({}, {}))", rule.node_kind(), shape).unwrap();
+            writeln!(out, "        }})").unwrap();
+        } else {
+            writeln!(out, "        let children: ars::parser::ParseResult<Vec<ars::ast::Node>> = {};", expr(grammar, &rule.expr, 2)).unwrap();
+            writeln!(out, "        Ok(ars::grammar::interpreter::shape_node({}, {}))", rule.node_kind(), shape).unwrap();
+        }
+        writeln!(out, "    }})").unwrap();
+        writeln!(out, "}}").unwrap();
+    }
+    Ok(out)
+}
+
+/// Rust expression that evaluates `expr` with `state` in scope into a `ParseResult<Vec<Node>>`
+fn expr(grammar: &Grammar, expr: &Expr, depth: usize) -> String {
+    let indent = "    ".repeat(depth + 1);
+    let close = "    ".repeat(depth);
+    let closure = |inner: &Expr| format!("|state: &mut ars::parser::ParserState| {}", self::expr(grammar, inner, depth + 1));
+    match expr {
+        Expr::Ref(name, _) => match grammar.token(name) {
+            Some(token) => format!(
+                "state.expect_any(&[{}]).map(|token| vec![ars::ast::Node::from_token(&token)])",
+                token.kind
+            ),
+            None if grammar.rule(name).unwrap().attributes.inline => format!("{}(state).map(|node| node.into_children())", parse_fn(name)),
+            None => format!("{}(state).map(|node| vec![node])", parse_fn(name)),
+        },
+        Expr::Capture(name, inner) => format!(
+            "{}.map(|mut nodes| {{ for node in nodes.iter_mut() {{ node.label = {:?}.to_string(); }} nodes }})",
+            self::expr(grammar, inner, depth),
+            name
+        ),
+        Expr::Drop(inner) => format!("{}.map(|_| vec![])", self::expr(grammar, inner, depth)),
+        Expr::Sequence(items) => {
+            let mut out = String::from("(|state: &mut ars::parser::ParserState| -> ars::parser::ParseResult<Vec<ars::ast::Node>> {\n");
+            writeln!(out, "{indent}let mut nodes = vec![];").unwrap();
+            for item in items {
+                writeln!(out, "{indent}nodes.extend({}?);", self::expr(grammar, item, depth + 1)).unwrap();
+            }
+            writeln!(out, "{indent}Ok(nodes)").unwrap();
+            write!(out, "{close}}})(state)").unwrap();
+            out
+        }
+        Expr::Choice(alternatives) => {
+            let mut out = String::from("state.choice(&[\n");
+            for alternative in alternatives {
+                writeln!(out, "{indent}&{},", closure(alternative)).unwrap();
+            }
+            write!(out, "{close}])").unwrap();
+            out
+        }
+        Expr::Optional(inner) => format!("Ok(state.attempt({}).unwrap_or_default())", closure(inner)),
+        Expr::ZeroOrMore(inner) => format!("Ok(state.many({}))", closure(inner)),
+        Expr::OneOrMore(inner) => format!(
+            "{}.map(|mut nodes| {{ nodes.extend(state.many({})); nodes }})",
+            self::expr(grammar, inner, depth),
+            closure(inner)
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{grammar::interpreter::Interpreter, parser::{ParseError, ParserState}};
+
+    #[test]
+    fn test_names() {
+        assert_eq!(camel_case("variable_definition"), "VariableDefinition");
+        assert_eq!(camel_case("Expr"), "Expr");
+        assert_eq!(snake_case("VariableDefinition"), "variable_definition");
+        assert_eq!(snake_case("expr_list"), "expr_list");
+    }
+
+    #[allow(dead_code, unused_imports)]
+    mod calc {
+        include!("../../assets/generated/calc.rs");
+    }
+
+    const CALC: &str = include_str!("../../assets/grammars/calc.ars");
+
+    #[test]
+    fn test_generated_is_current() {
+        let grammar = Grammar::parse(CALC).unwrap();
+        assert_eq!(generate(&grammar).unwrap(), include_str!("../../assets/generated/calc.rs"));
+    }
+
+    #[test]
+    fn test_generated_parser() {
+        let grammar = Grammar::parse(CALC).unwrap();
+        let input = "f(1+2*3,-x());\n(4);";
+        let node = calc::parse(input).unwrap();
+        assert_eq!(node, grammar.interpret(input).unwrap());
+        assert_eq!(node.children[0].children[0].value.as_str(), Some("f"));
+        assert!(calc::parse("1+;").is_err());
+        assert_eq!(calc::parse("1 $;"), Err(ParseError::InvalidCharacter { location: (1, 3) }));
+
+        // a generated `Parser` is run the same way as a hand written one, left recursion included
+        let mut lexer = calc::lexer();
+        lexer.begin("1,2,3");
+        let mut state = ParserState::new(lexer.all(), None).with_memo();
+        let list = state.parse(calc::List);
+        assert_eq!(list, Interpreter::new(&grammar).unwrap().parse_rule("List", "1,2,3").unwrap());
+        assert!(state.is_at_end());
+    }
+
+    #[test]
+    fn test_generate_undefined() {
+        let grammar = Grammar::parse("Sum: NUM;").unwrap();
+        assert_eq!(generate(&grammar).unwrap_err().message, "`NUM` is not defined");
+    }
+}
@@ -0,0 +1,1120 @@
+use std::collections::{HashMap, HashSet};
+
+use crate::{
+    lexer::Lexer,
+    parser::ParserState,
+    span::Span,
+    token::{Token, TokenData},
+};
+
+use super::{lint::Diagnostic, loader, Attributes, Expr, Grammar, GrammarError, Pattern, RuleDef, TokenDef};
+
+/// an `.ars` grammar converted from another format, with what could not be carried over
+#[derive(Debug, Clone)]
+pub struct Conversion {
+    pub grammar: Grammar,
+    /// the grammar as `.ars` text
+    pub text: String,
+    pub warnings: Vec<Diagnostic>,
+}
+
+/// converts an ANTLR4 `.g4` grammar, lexer rules become tokens and parser rules become rules
+pub fn from_antlr(source: &str) -> Result<Conversion, GrammarError> {
+    let mut reader = Reader::new(source, antlr_lexer())?;
+    let definitions = reader.antlr()?;
+    build(definitions, reader.warnings)
+}
+
+/// converts a W3C (`name ::= ...`) or ISO 14977 (`name = ... ;`) EBNF grammar, rules made only
+/// of characters become tokens and the first rule stays the start rule
+pub fn from_ebnf(source: &str) -> Result<Conversion, GrammarError> {
+    let w3c = source.contains("::=");
+    let mut reader = Reader::new(source, if w3c { w3c_lexer() } else { iso_lexer() })?;
+    let mut definitions = if w3c { reader.w3c()? } else { reader.iso()? };
+    classify(&mut definitions);
+    build(definitions, reader.warnings)
+}
+
+/// a piece of a foreign grammar, before it is split into tokens and rules
+#[derive(Debug, Clone, PartialEq)]
+enum Term {
+    Ref(String, Span),
+    Literal(String),
+    /// the inside of a regex character class
+    Set { body: String, negated: bool },
+    /// anything else that only makes sense in a regex
+    Regex(String),
+    Sequence(Vec<Term>),
+    Choice(Vec<Term>),
+    /// `?`, `*` or `+`, followed by `?` when it is not greedy
+    Suffix(Box<Term>, String),
+    Capture(String, Box<Term>),
+    Empty,
+}
+
+#[derive(Debug, Clone)]
+struct Definition {
+    name: String,
+    term: Term,
+    span: Span,
+    /// matches characters rather than tokens
+    lexical: bool,
+    /// only used inside other lexical definitions
+    fragment: bool,
+}
+
+const WHITESPACE: u32 = 0;
+const COMMENT: u32 = 1;
+const STRING: u32 = 2;
+const SET: u32 = 3;
+const IDENT: u32 = 4;
+const INT: u32 = 5;
+const HEX: u32 = 6;
+const DEFINE: u32 = 7;
+const COLON: u32 = 8;
+const SEMICOLON: u32 = 9;
+const PIPE: u32 = 10;
+const LPAREN: u32 = 11;
+const RPAREN: u32 = 12;
+const QUESTION: u32 = 13;
+const STAR: u32 = 14;
+const PLUS: u32 = 15;
+const TILDE: u32 = 16;
+const DOT: u32 = 17;
+const RANGE: u32 = 18;
+const ASSIGN: u32 = 19;
+const PLUS_ASSIGN: u32 = 20;
+const ARROW: u32 = 21;
+const HASH: u32 = 22;
+const LBRACE: u32 = 23;
+const RBRACE: u32 = 24;
+const LBRACKET: u32 = 25;
+const RBRACKET: u32 = 26;
+const AT: u32 = 27;
+const LT: u32 = 28;
+const GT: u32 = 29;
+const COMMA: u32 = 30;
+const MINUS: u32 = 31;
+const SPECIAL: u32 = 32;
+const UNKNOWN: u32 = 33;
+
+fn antlr_lexer() -> Lexer {
+    Lexer::new(vec![
+        Token::new_regex_from_str("whitespace", WHITESPACE, "\\s+"),
+        Token::new_regex_from_str("comment", COMMENT, "//[^\\n]*|/\\*(?s:.*?)\\*/"),
+        Token::new_regex_from_str("string", STRING, "'(\\\\.|[^'\\\\])*'"),
+        Token::new_regex_from_str("set", SET, "\\[(\\\\.|[^\\]\\\\])*\\]"),
+        Token::new_regex_from_str("ident", IDENT, "[a-zA-Z_][a-zA-Z0-9_]*"),
+        Token::new_regex_from_str("int", INT, "\\d+"),
+        Token::new_lit("arrow", ARROW, "->"),
+        Token::new_lit("range", RANGE, ".."),
+        Token::new_lit("plus_assign", PLUS_ASSIGN, "+="),
+        Token::new_lit("assign", ASSIGN, "="),
+        Token::new_lit("colon", COLON, ":"),
+        Token::new_lit("semicolon", SEMICOLON, ";"),
+        Token::new_lit("pipe", PIPE, "|"),
+        Token::new_lit("lparen", LPAREN, "("),
+        Token::new_lit("rparen", RPAREN, ")"),
+        Token::new_lit("question", QUESTION, "?"),
+        Token::new_lit("star", STAR, "*"),
+        Token::new_lit("plus", PLUS, "+"),
+        Token::new_lit("tilde", TILDE, "~"),
+        Token::new_lit("dot", DOT, "."),
+        Token::new_lit("hash", HASH, "#"),
+        Token::new_lit("lbrace", LBRACE, "{"),
+        Token::new_lit("rbrace", RBRACE, "}"),
+        Token::new_lit("at", AT, "@"),
+        Token::new_lit("lt", LT, "<"),
+        Token::new_lit("gt", GT, ">"),
+        Token::new_lit("comma", COMMA, ","),
+        Token::new_regex_from_str("unknown", UNKNOWN, "(?s)."),
+    ])
+}
+
+fn w3c_lexer() -> Lexer {
+    Lexer::new(vec![
+        Token::new_regex_from_str("whitespace", WHITESPACE, "\\s+"),
+        Token::new_regex_from_str("comment", COMMENT, "/\\*(?s:.*?)\\*/"),
+        Token::new_regex_from_str("string", STRING, "\"[^\"]*\"|'[^']*'"),
+        Token::new_regex_from_str("set", SET, "\\[[^\\]]*\\]"),
+        Token::new_regex_from_str("hex", HEX, "#x[0-9a-fA-F]+"),
+        Token::new_regex_from_str("ident", IDENT, "[a-zA-Z_][a-zA-Z0-9_.\\-]*"),
+        Token::new_lit("define", DEFINE, "::="),
+        Token::new_lit("pipe", PIPE, "|"),
+        Token::new_lit("lparen", LPAREN, "("),
+        Token::new_lit("rparen", RPAREN, ")"),
+        Token::new_lit("question", QUESTION, "?"),
+        Token::new_lit("star", STAR, "*"),
+        Token::new_lit("plus", PLUS, "+"),
+        Token::new_lit("minus", MINUS, "-"),
+        Token::new_regex_from_str("unknown", UNKNOWN, "(?s)."),
+    ])
+}
+
+fn iso_lexer() -> Lexer {
+    Lexer::new(vec![
+        Token::new_regex_from_str("whitespace", WHITESPACE, "\\s+"),
+        Token::new_regex_from_str("comment", COMMENT, "\\(\\*(?s:.*?)\\*\\)"),
+        Token::new_regex_from_str("string", STRING, "\"[^\"]*\"|'[^']*'"),
+        Token::new_regex_from_str("special", SPECIAL, "\\?[^?]*\\?"),
+        Token::new_regex_from_str("ident", IDENT, "[a-zA-Z_][a-zA-Z0-9_\\-]*"),
+        Token::new_regex_from_str("int", INT, "\\d+"),
+        Token::new_lit("define", DEFINE, "="),
+        Token::new_lit("pipe", PIPE, "|"),
+        Token::new_lit("comma", COMMA, ","),
+        Token::new_lit("semicolon", SEMICOLON, ";"),
+        Token::new_lit("dot", DOT, "."),
+        Token::new_lit("lparen", LPAREN, "("),
+        Token::new_lit("rparen", RPAREN, ")"),
+        Token::new_lit("lbrace", LBRACE, "{"),
+        Token::new_lit("rbrace", RBRACE, "}"),
+        Token::new_lit("lbracket", LBRACKET, "["),
+        Token::new_lit("rbracket", RBRACKET, "]"),
+        Token::new_lit("star", STAR, "*"),
+        Token::new_lit("minus", MINUS, "-"),
+        Token::new_regex_from_str("unknown", UNKNOWN, "(?s)."),
+    ])
+}
+
+/// reads the foreign grammar, one method per format
+struct Reader {
+    state: ParserState,
+    warnings: Vec<Diagnostic>,
+}
+
+impl Reader {
+    fn new(source: &str, mut lexer: Lexer) -> Result<Self, GrammarError> {
+        lexer.begin(source);
+        let tokens = lexer.all();
+        if let Some(token) = tokens.iter().find(|token| token.kind == UNKNOWN) {
+            return Err(GrammarError::new(format!("Unexpected character `{}`", token.value), Span::from_token(token)));
+        }
+        Ok(Self { state: ParserState::new(tokens, Some(vec![WHITESPACE, COMMENT])), warnings: vec![] })
+    }
+
+    fn warn(&mut self, message: impl Into<String>, span: Span) {
+        self.warnings.push(Diagnostic::warning(message, span));
+    }
+
+    fn expect(&mut self, kinds: &[u32]) -> Result<TokenData, GrammarError> {
+        self.state.expect_any(kinds).map_err(|error| match self.state.lookahead(0) {
+            Some(found) => GrammarError::new(format!("Unexpected `{}`", found.value), Span::from_token(found)),
+            None => GrammarError::new(format!("Unexpected end of input in {:?}", error.expected()), Span::default()),
+        })
+    }
+
+    fn is(&self, kind: u32) -> bool {
+        self.state.lookahead(0).is_some_and(|token| token.kind == kind)
+    }
+
+    fn span(&self) -> Span {
+        self.state.lookahead(0).map(Span::from_token).unwrap_or_default()
+    }
+
+    /// skips a `{...}` block with nested braces, the opening brace included
+    fn skip_block(&mut self) -> Result<(), GrammarError> {
+        self.expect(&[LBRACE])?;
+        let mut depth = 1;
+        while depth > 0 {
+            match self.state.lookahead(0).map(|token| token.kind) {
+                Some(LBRACE) => depth += 1,
+                Some(RBRACE) => depth -= 1,
+                Some(_) => {}
+                None => return Err(GrammarError::new("Unclosed `{`", Span::default())),
+            }
+            self.state.skip_trivia();
+            self.state.eat();
+        }
+        Ok(())
+    }
+
+    /// skips `<...>` options up to and including the closing `>`
+    fn skip_options(&mut self) -> Result<(), GrammarError> {
+        while self.state.eat_if(GT).is_none() {
+            if self.state.lookahead(0).is_none() {
+                return Err(GrammarError::new("Unclosed `<`", Span::default()));
+            }
+            self.state.skip_trivia();
+            self.state.eat();
+        }
+        Ok(())
+    }
+
+    /// skips tokens up to and including the next `;`
+    fn skip_statement(&mut self) -> Result<(), GrammarError> {
+        while !self.is(SEMICOLON) {
+            if self.state.lookahead(0).is_none() {
+                return Err(GrammarError::new("Expected `;`", Span::default()));
+            }
+            self.state.skip_trivia();
+            self.state.eat();
+        }
+        self.expect(&[SEMICOLON])?;
+        Ok(())
+    }
+
+    fn antlr(&mut self) -> Result<Vec<Definition>, GrammarError> {
+        let mut definitions = vec![];
+        while let Some(token) = self.state.lookahead(0).cloned() {
+            let span = Span::from_token(&token);
+            match (token.kind, token.value.as_str()) {
+                (IDENT, "lexer" | "parser" | "grammar") => self.skip_statement()?,
+                (IDENT, "import" | "mode") => {
+                    self.warn(format!("`{}` is not supported and is left out", token.value), span);
+                    self.skip_statement()?;
+                }
+                (IDENT, "options" | "tokens" | "channels") if self.state.lookahead(1).is_some_and(|next| next.kind == LBRACE) => {
+                    self.warn(format!("`{}` block is left out", token.value), span);
+                    self.state.eat_if(IDENT);
+                    self.skip_block()?;
+                }
+                (AT, _) => {
+                    self.state.eat_if(AT);
+                    let name = self.expect(&[IDENT])?;
+                    while self.state.eat_if(COLON).is_some() {
+                        self.expect(&[IDENT])?;
+                    }
+                    self.warn(format!("Action `@{}` is left out", name.value), span);
+                    self.skip_block()?;
+                }
+                _ => definitions.push(self.antlr_rule()?),
+            }
+        }
+        Ok(definitions)
+    }
+
+    fn antlr_rule(&mut self) -> Result<Definition, GrammarError> {
+        let fragment = self.state.lookahead(0).is_some_and(|token| token.value == "fragment")
+            && self.state.lookahead(1).is_some_and(|token| token.kind == IDENT);
+        if fragment {
+            self.state.eat_if(IDENT);
+        }
+        let name = self.expect(&[IDENT])?;
+        let span = Span::from_token(&name);
+        let lexical = name.value.starts_with(|c: char| c.is_ascii_uppercase());
+        // arguments, return values, locals, options and actions of the rule
+        loop {
+            if self.state.eat_if(SET).is_some() {
+                self.warn(format!("Arguments of `{}` are left out", name.value), span);
+            } else if let Some(keyword) = self.state.lookahead(0).filter(|token| token.kind == IDENT).map(|token| token.value.clone()) {
+                self.state.eat_if(IDENT);
+                match keyword.as_str() {
+                    "returns" | "locals" => {
+                        self.expect(&[SET])?;
+                    }
+                    "options" => self.skip_block()?,
+                    "throws" => {
+                        self.expect(&[IDENT])?;
+                        while self.state.eat_if(COMMA).is_some() {
+                            self.expect(&[IDENT])?;
+                        }
+                    }
+                    _ => return Err(GrammarError::new(format!("Unexpected `{}`", keyword), span)),
+                }
+                self.warn(format!("`{}` of `{}` is left out", keyword, name.value), span);
+            } else if self.state.eat_if(AT).is_some() {
+                self.expect(&[IDENT])?;
+                self.skip_block()?;
+                self.warn(format!("Action of `{}` is left out", name.value), span);
+            } else {
+                break;
+            }
+        }
+        self.expect(&[COLON])?;
+        let term = self.antlr_alternatives(&name.value)?;
+        self.expect(&[SEMICOLON])?;
+        // exception handlers after the rule
+        while self.state.lookahead(0).is_some_and(|token| token.kind == IDENT && (token.value == "catch" || token.value == "finally")) {
+            self.warn(format!("Exception handlers of `{}` are left out", name.value), span);
+            self.state.eat_if(IDENT);
+            self.state.eat_if(SET);
+            self.skip_block()?;
+        }
+        if let Term::Choice(alternatives) = &term {
+            let recursive = alternatives.iter().filter(|alternative| first_ref(alternative) == Some(name.value.as_str())).count();
+            if recursive > 1 {
+                self.warn(format!("`{}` relies on ANTLR giving earlier left recursive alternatives a higher precedence, consider `%left` and `@pratt`", name.value), span);
+            }
+        }
+        Ok(Definition { name: name.value, term, span, lexical, fragment })
+    }
+
+    fn antlr_alternatives(&mut self, rule: &str) -> Result<Term, GrammarError> {
+        let mut alternatives = vec![self.antlr_alternative(rule)?];
+        while self.state.eat_if(PIPE).is_some() {
+            alternatives.push(self.antlr_alternative(rule)?);
+        }
+        Ok(choice(alternatives))
+    }
+
+    fn antlr_alternative(&mut self, rule: &str) -> Result<Term, GrammarError> {
+        let span = self.span();
+        if self.is(LT) {
+            self.warn("Alternative options are left out", span);
+            self.skip_options()?;
+        }
+        let mut items = vec![];
+        while self.state.lookahead(0).is_some_and(|token| [IDENT, STRING, SET, LPAREN, TILDE, DOT, LBRACE].contains(&token.kind)) {
+            if let Some(item) = self.antlr_element(rule)? {
+                items.push(item);
+            }
+        }
+        if self.state.eat_if(ARROW).is_some() {
+            let command = self.span();
+            let mut text = vec![];
+            // commands take arguments, as in `-> channel(HIDDEN)`
+            let mut depth = 0;
+            loop {
+                match self.state.lookahead(0).map(|token| token.kind) {
+                    Some(PIPE | SEMICOLON | RPAREN) if depth == 0 => break,
+                    Some(LPAREN) => depth += 1,
+                    Some(RPAREN) => depth -= 1,
+                    Some(_) => {}
+                    None => return Err(GrammarError::new("Expected `;`", Span::default())),
+                }
+                self.state.skip_trivia();
+                text.push(self.state.eat().value);
+            }
+            self.warn(format!("Lexer command `-> {}` of `{}` is left out", text.concat(), rule), command);
+        }
+        if self.state.eat_if(HASH).is_some() {
+            let label = self.expect(&[IDENT])?;
+            self.warn(format!("Alternative label `#{}` is left out", label.value), Span::from_token(&label));
+        }
+        Ok(sequence(items))
+    }
+
+    fn antlr_element(&mut self, rule: &str) -> Result<Option<Term>, GrammarError> {
+        let span = self.span();
+        if self.is(LBRACE) {
+            self.skip_block()?;
+            if self.state.eat_if(QUESTION).is_some() {
+                self.warn(format!("Semantic predicate in `{}` is left out", rule), span);
+            } else {
+                self.warn(format!("Action in `{}` is left out", rule), span);
+            }
+            return Ok(None);
+        }
+        let is_label = self.state.lookahead(0).is_some_and(|token| token.kind == IDENT)
+            && self.state.lookahead(1).is_some_and(|token| token.kind == ASSIGN || token.kind == PLUS_ASSIGN);
+        if is_label {
+            let label = self.expect(&[IDENT])?;
+            self.expect(&[ASSIGN, PLUS_ASSIGN])?;
+            let inner = self.antlr_suffixed(rule)?;
+            return Ok(Some(Term::Capture(label.value, Box::new(inner))));
+        }
+        self.antlr_suffixed(rule).map(Some)
+    }
+
+    fn antlr_suffixed(&mut self, rule: &str) -> Result<Term, GrammarError> {
+        let term = self.antlr_atom(rule)?;
+        if self.is(LT) {
+            let span = self.span();
+            self.warn("Element options are left out", span);
+            self.skip_options()?;
+        }
+        let suffix = match self.state.lookahead(0).map(|token| token.kind) {
+            Some(QUESTION) => "?",
+            Some(STAR) => "*",
+            Some(PLUS) => "+",
+            _ => return Ok(term),
+        };
+        self.state.skip_trivia();
+        self.state.eat();
+        let lazy = if self.state.eat_if(QUESTION).is_some() { "?" } else { "" };
+        Ok(Term::Suffix(Box::new(term), format!("{}{}", suffix, lazy)))
+    }
+
+    fn antlr_atom(&mut self, rule: &str) -> Result<Term, GrammarError> {
+        let token = self.expect(&[IDENT, STRING, SET, LPAREN, TILDE, DOT])?;
+        let span = Span::from_token(&token);
+        match token.kind {
+            IDENT => Ok(Term::Ref(token.value, span)),
+            STRING => {
+                let literal = antlr_literal(&token.value[1..token.value.len() - 1]);
+                if self.state.eat_if(RANGE).is_none() {
+                    return Ok(Term::Literal(literal));
+                }
+                let end = self.expect(&[STRING])?;
+                let end = antlr_literal(&end.value[1..end.value.len() - 1]);
+                Ok(Term::Set { body: format!("{}-{}", class_escape(&literal), class_escape(&end)), negated: false })
+            }
+            SET => Ok(Term::Set { body: antlr_set(&token.value[1..token.value.len() - 1]), negated: false }),
+            LPAREN => {
+                let term = self.antlr_alternatives(rule)?;
+                self.expect(&[RPAREN])?;
+                Ok(term)
+            }
+            TILDE => {
+                let inner = self.antlr_atom(rule)?;
+                match set_body(&inner) {
+                    Some(body) => Ok(Term::Set { body, negated: true }),
+                    None => {
+                        self.warn(format!("`~` in `{}` only works on characters here and is left out", rule), span);
+                        Ok(inner)
+                    }
+                }
+            }
+            _ => Ok(Term::Regex("(?s:.)".to_string())),
+        }
+    }
+
+    fn w3c(&mut self) -> Result<Vec<Definition>, GrammarError> {
+        let mut definitions = vec![];
+        while self.state.lookahead(0).is_some() {
+            let name = self.expect(&[IDENT])?;
+            self.expect(&[DEFINE])?;
+            let term = self.w3c_choice()?;
+            definitions.push(Definition { name: name.value.clone(), term, span: Span::from_token(&name), lexical: false, fragment: false });
+        }
+        Ok(definitions)
+    }
+
+    fn w3c_choice(&mut self) -> Result<Term, GrammarError> {
+        let mut alternatives = vec![self.w3c_sequence()?];
+        while self.state.eat_if(PIPE).is_some() {
+            alternatives.push(self.w3c_sequence()?);
+        }
+        Ok(choice(alternatives))
+    }
+
+    fn w3c_sequence(&mut self) -> Result<Term, GrammarError> {
+        let mut items = vec![];
+        // `Name ::=` starts the next rule
+        while self.state.lookahead(0).is_some_and(|token| [IDENT, STRING, SET, HEX, LPAREN].contains(&token.kind))
+            && !self.state.is_seq(&[IDENT, DEFINE])
+        {
+            let mut item = self.w3c_suffixed()?;
+            if self.is(MINUS) {
+                let span = self.span();
+                self.state.eat_if(MINUS);
+                self.w3c_suffixed()?;
+                self.warn("Exceptions (`A - B`) have no .ars equivalent, only `A` is kept", span);
+            }
+            if let Term::Set { body, .. } = &item {
+                if body.starts_with("wfc:") || body.starts_with("vc:") || body.starts_with(" wfc:") || body.starts_with(" vc:") {
+                    self.warn("Constraint notes are left out", self.span());
+                    item = Term::Empty;
+                }
+            }
+            items.push(item);
+        }
+        Ok(sequence(items))
+    }
+
+    fn w3c_suffixed(&mut self) -> Result<Term, GrammarError> {
+        let token = self.expect(&[IDENT, STRING, SET, HEX, LPAREN])?;
+        let term = match token.kind {
+            IDENT => {
+                let span = Span::from_token(&token);
+                Term::Ref(token.value, span)
+            }
+            STRING => Term::Literal(token.value[1..token.value.len() - 1].to_string()),
+            SET => {
+                let body = &token.value[1..token.value.len() - 1];
+                match body.strip_prefix('^') {
+                    Some(body) => Term::Set { body: w3c_set(body), negated: true },
+                    None => Term::Set { body: w3c_set(body), negated: false },
+                }
+            }
+            HEX => Term::Literal(hex_char(&token.value[2..]).to_string()),
+            _ => {
+                let term = self.w3c_choice()?;
+                self.expect(&[RPAREN])?;
+                term
+            }
+        };
+        let suffix = match self.state.lookahead(0).map(|token| token.kind) {
+            Some(QUESTION) => "?",
+            Some(STAR) => "*",
+            Some(PLUS) => "+",
+            _ => return Ok(term),
+        };
+        self.state.skip_trivia();
+        self.state.eat();
+        Ok(Term::Suffix(Box::new(term), suffix.to_string()))
+    }
+
+    fn iso(&mut self) -> Result<Vec<Definition>, GrammarError> {
+        let mut definitions = vec![];
+        while self.state.lookahead(0).is_some() {
+            let (name, span) = self.iso_name()?;
+            self.expect(&[DEFINE])?;
+            let term = self.iso_choice()?;
+            self.expect(&[SEMICOLON, DOT])?;
+            definitions.push(Definition { name, term, span, lexical: false, fragment: false });
+        }
+        Ok(definitions)
+    }
+
+    /// meta identifiers may be several words, `digit excluding zero` becomes `digit_excluding_zero`
+    fn iso_name(&mut self) -> Result<(String, Span), GrammarError> {
+        let first = self.expect(&[IDENT])?;
+        let mut words = vec![first.value.clone()];
+        while let Some(word) = self.state.eat_if(IDENT) {
+            words.push(word.value);
+        }
+        Ok((words.join("_"), Span::from_token(&first)))
+    }
+
+    fn iso_choice(&mut self) -> Result<Term, GrammarError> {
+        let mut alternatives = vec![self.iso_sequence()?];
+        while self.state.eat_if(PIPE).is_some() {
+            alternatives.push(self.iso_sequence()?);
+        }
+        Ok(choice(alternatives))
+    }
+
+    fn iso_sequence(&mut self) -> Result<Term, GrammarError> {
+        let mut items = vec![self.iso_term()?];
+        while self.state.eat_if(COMMA).is_some() {
+            items.push(self.iso_term()?);
+        }
+        Ok(sequence(items))
+    }
+
+    /// a factor, maybe with an exception
+    fn iso_term(&mut self) -> Result<Term, GrammarError> {
+        let term = self.iso_factor()?;
+        if self.is(MINUS) {
+            let span = self.span();
+            self.state.eat_if(MINUS);
+            self.iso_factor()?;
+            self.warn("Exceptions (`A - B`) have no .ars equivalent, only `A` is kept", span);
+        }
+        Ok(term)
+    }
+
+    /// `3 * x` repeats `x` three times
+    fn iso_factor(&mut self) -> Result<Term, GrammarError> {
+        if self.state.is_seq(&[INT, STAR]) {
+            let count = self.expect(&[INT])?.value.parse::<usize>().unwrap_or(1);
+            self.expect(&[STAR])?;
+            let term = self.iso_primary()?;
+            return Ok(sequence(vec![term; count]));
+        }
+        self.iso_primary()
+    }
+
+    fn iso_primary(&mut self) -> Result<Term, GrammarError> {
+        let Some(token) = self.state.lookahead(0).cloned() else {
+            return Ok(Term::Empty);
+        };
+        let span = Span::from_token(&token);
+        let term = match token.kind {
+            IDENT => Term::Ref(self.iso_name()?.0, span),
+            STRING => {
+                self.state.eat_if(STRING);
+                Term::Literal(token.value[1..token.value.len() - 1].to_string())
+            }
+            SPECIAL => {
+                self.state.eat_if(SPECIAL);
+                self.warn(format!("Special sequence `{}` is left out", token.value), span);
+                Term::Empty
+            }
+            LPAREN | LBRACKET | LBRACE => {
+                self.state.eat_if(token.kind);
+                let inner = self.iso_choice()?;
+                match token.kind {
+                    LPAREN => {
+                        self.expect(&[RPAREN])?;
+                        inner
+                    }
+                    LBRACKET => {
+                        self.expect(&[RBRACKET])?;
+                        Term::Suffix(Box::new(inner), "?".to_string())
+                    }
+                    _ => {
+                        self.expect(&[RBRACE])?;
+                        // `{ x }-` means at least one
+                        let suffix = if self.state.eat_if(MINUS).is_some() { "+" } else { "*" };
+                        Term::Suffix(Box::new(inner), suffix.to_string())
+                    }
+                }
+            }
+            // an empty sequence, as in `a = | b ;`
+            _ => Term::Empty,
+        };
+        Ok(term)
+    }
+}
+
+fn sequence(mut items: Vec<Term>) -> Term {
+    items.retain(|item| *item != Term::Empty);
+    match items.len() {
+        0 => Term::Empty,
+        1 => items.pop().unwrap(),
+        _ => Term::Sequence(items),
+    }
+}
+
+fn choice(mut alternatives: Vec<Term>) -> Term {
+    match alternatives.len() {
+        1 => alternatives.pop().unwrap(),
+        _ => Term::Choice(alternatives),
+    }
+}
+
+/// name the term starts with, used to spot left recursion
+fn first_ref(term: &Term) -> Option<&str> {
+    match term {
+        Term::Ref(name, _) => Some(name),
+        Term::Sequence(items) => items.first().and_then(first_ref),
+        Term::Capture(_, inner) => first_ref(inner),
+        _ => None,
+    }
+}
+
+/// the class body of a term made of single characters, used for negation
+fn set_body(term: &Term) -> Option<String> {
+    match term {
+        Term::Literal(lit) if lit.chars().count() == 1 => Some(class_escape(lit)),
+        Term::Set { body, negated: false } => Some(body.clone()),
+        Term::Choice(alternatives) => alternatives.iter().map(set_body).collect(),
+        _ => None,
+    }
+}
+
+/// resolves the escapes of an ANTLR `'...'` literal
+fn antlr_literal(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('r') => result.push('\r'),
+            Some('t') => result.push('\t'),
+            Some('b') => result.push('\u{8}'),
+            Some('f') => result.push('\u{c}'),
+            Some('u') => result.push(unicode_escape(&mut chars)),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// the code point of `\uXXXX` or `\u{X...}` after the `\u`
+fn unicode_escape(chars: &mut std::iter::Peekable<std::str::Chars>) -> char {
+    let digits = if chars.peek() == Some(&'{') {
+        chars.next();
+        chars.by_ref().take_while(|c| *c != '}').collect::<String>()
+    } else {
+        chars.by_ref().take(4).collect::<String>()
+    };
+    hex_char(&digits)
+}
+
+fn hex_char(digits: &str) -> char {
+    u32::from_str_radix(digits, 16).ok().and_then(char::from_u32).unwrap_or('\u{FFFD}')
+}
+
+/// a character as it is written inside a regex class
+fn class_escape(text: &str) -> String {
+    text.chars().map(|c| match c {
+        '\\' | ']' | '[' | '^' | '-' | '&' | '~' => format!("\\{}", c),
+        '\n' => "\\n".to_string(),
+        '\r' => "\\r".to_string(),
+        '\t' => "\\t".to_string(),
+        c if c.is_control() => format!("\\x{{{:X}}}", c as u32),
+        c => c.to_string(),
+    }).collect()
+}
+
+/// the inside of an ANTLR `[...]` set as the inside of a regex class
+fn antlr_set(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => result.push_str("\\n"),
+                Some('r') => result.push_str("\\r"),
+                Some('t') => result.push_str("\\t"),
+                Some('b') => result.push_str("\\x{8}"),
+                Some('f') => result.push_str("\\x{C}"),
+                Some('u') => result.push_str(&class_escape(&unicode_escape(&mut chars).to_string())),
+                // `\p{...}` means the same in both
+                Some('p') => result.push_str("\\p"),
+                Some(other) => result.push_str(&class_escape(&other.to_string())),
+                None => {}
+            },
+            // ranges keep their dash
+            '-' if !result.is_empty() && chars.peek().is_some() => result.push('-'),
+            c => result.push_str(&class_escape(&c.to_string())),
+        }
+    }
+    result
+}
+
+/// the inside of a W3C `[...]` set, where `#xN` stands for a character
+fn w3c_set(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '#' if chars.peek() == Some(&'x') => {
+                chars.next();
+                let mut digits = String::new();
+                while let Some(digit) = chars.next_if(|c| c.is_ascii_hexdigit()) {
+                    digits.push(digit);
+                }
+                result.push_str(&class_escape(&hex_char(&digits).to_string()));
+            }
+            '-' if !result.is_empty() && chars.peek().is_some() => result.push('-'),
+            c => result.push_str(&class_escape(&c.to_string())),
+        }
+    }
+    result
+}
+
+/// marks EBNF rules that only describe characters as lexical, except the start rule,
+/// the ones no other kind of rule uses are merged into the rules using them
+///
+/// a rule joining several other rules with literals in between, like `name '=' value`,
+/// reads as syntax rather than as a word and is kept as a rule
+fn classify(definitions: &mut [Definition]) {
+    let mut lexical: HashSet<String> = HashSet::new();
+    loop {
+        let before = lexical.len();
+        for definition in definitions.iter().skip(1) {
+            let mut refs = vec![];
+            refs_of(&definition.term, &mut refs);
+            let resolved = refs.iter().all(|name| lexical.contains(*name) && *name != definition.name);
+            let distinct = refs.iter().collect::<HashSet<_>>().len();
+            let syntax = distinct > 1 && has_literal(&definition.term);
+            if resolved && !syntax && !lexical.contains(&definition.name) {
+                lexical.insert(definition.name.clone());
+            }
+        }
+        if lexical.len() == before {
+            break;
+        }
+    }
+    let mut used_by_rules = HashSet::new();
+    for definition in definitions.iter().filter(|definition| !lexical.contains(&definition.name)) {
+        let mut refs = vec![];
+        refs_of(&definition.term, &mut refs);
+        used_by_rules.extend(refs.into_iter().map(str::to_string));
+    }
+    for definition in definitions.iter_mut() {
+        definition.lexical = lexical.contains(&definition.name);
+        definition.fragment = definition.lexical && !used_by_rules.contains(&definition.name);
+    }
+}
+
+fn has_literal(term: &Term) -> bool {
+    match term {
+        Term::Literal(_) => true,
+        Term::Sequence(items) | Term::Choice(items) => items.iter().any(has_literal),
+        Term::Suffix(inner, _) | Term::Capture(_, inner) => has_literal(inner),
+        Term::Ref(_, _) | Term::Set { .. } | Term::Regex(_) | Term::Empty => false,
+    }
+}
+
+fn refs_of<'t>(term: &'t Term, refs: &mut Vec<&'t str>) {
+    match term {
+        Term::Ref(name, _) => refs.push(name),
+        Term::Sequence(items) | Term::Choice(items) => items.iter().for_each(|item| refs_of(item, refs)),
+        Term::Suffix(inner, _) | Term::Capture(_, inner) => refs_of(inner, refs),
+        Term::Literal(_) | Term::Set { .. } | Term::Regex(_) | Term::Empty => {}
+    }
+}
+
+/// `NameStartChar` becomes `NAME_START_CHAR`
+fn token_name(name: &str) -> String {
+    let mut result = String::new();
+    let mut previous: Option<char> = None;
+    for c in name.chars() {
+        let c = if c.is_ascii_alphanumeric() { c } else { '_' };
+        if c.is_ascii_uppercase() && previous.is_some_and(|p| p.is_ascii_lowercase() || p.is_ascii_digit()) {
+            result.push('_');
+        }
+        result.push(c.to_ascii_uppercase());
+        previous = Some(c);
+    }
+    if !result.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        result.insert(0, 'T');
+    }
+    result
+}
+
+/// rule names must not look like token names, `EXPR` becomes `expr`
+fn rule_name(name: &str) -> String {
+    let mut result = name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect::<String>();
+    if loader::is_token_name(&result) {
+        result = result.to_ascii_lowercase();
+    }
+    if !result.starts_with(|c: char| c.is_ascii_alphabetic()) {
+        result.insert(0, 'r');
+    }
+    result
+}
+
+/// regex text and how tightly it binds, 0 for alternations, 1 for concatenations, 2 for atoms
+type Regex = (String, u8);
+
+/// turns definitions into tokens and rules
+struct Builder<'d> {
+    definitions: HashMap<&'d str, &'d Definition>,
+    tokens: Vec<TokenDef>,
+    warnings: Vec<Diagnostic>,
+}
+
+fn build(definitions: Vec<Definition>, warnings: Vec<Diagnostic>) -> Result<Conversion, GrammarError> {
+    let mut builder = Builder { definitions: definitions.iter().map(|definition| (definition.name.as_str(), definition)).collect(), tokens: vec![], warnings };
+    for definition in definitions.iter().filter(|definition| definition.lexical && !definition.fragment) {
+        let pattern = match &definition.term {
+            Term::Literal(lit) => Pattern::Literal(lit.clone()),
+            term => Pattern::Regex(builder.regex(term, &mut vec![definition.name.clone()]).0),
+        };
+        builder.tokens.push(TokenDef { name: token_name(&definition.name), pattern, kind: 0, span: definition.span });
+    }
+    let mut rules = vec![];
+    for definition in definitions.iter().filter(|definition| !definition.lexical) {
+        match builder.expr(&definition.term) {
+            Some(expr) => rules.push(RuleDef { name: rule_name(&definition.name), expr, kind: 0, span: definition.span, attributes: Attributes::default() }),
+            None => builder.warnings.push(Diagnostic::warning(format!("Rule `{}` matches nothing and is left out", definition.name), definition.span)),
+        }
+    }
+    // fragments are only kept inlined into the tokens and rules that reach them
+    let mut used = HashSet::new();
+    let mut pending = definitions.iter().filter(|definition| !definition.fragment).map(|definition| &definition.term).collect::<Vec<_>>();
+    while let Some(term) = pending.pop() {
+        let mut refs = vec![];
+        refs_of(term, &mut refs);
+        for name in refs {
+            if let Some(definition) = builder.definitions.get(name).filter(|_| used.insert(name)) {
+                pending.push(&definition.term);
+            }
+        }
+    }
+    for definition in definitions.iter().filter(|definition| definition.fragment && !used.contains(definition.name.as_str())) {
+        builder.warn(format!("`{}` is not used by any rule and is left out", definition.name), definition.span);
+    }
+
+    let grammar = Grammar { tokens: builder.tokens, rules, ..Grammar::default() };
+    let text = grammar.to_string();
+    // loading the text numbers the tokens and merges literals with the tokens they match
+    let grammar = Grammar::parse(&text)?;
+    Ok(Conversion { grammar, text, warnings: builder.warnings })
+}
+
+impl Builder<'_> {
+    fn warn(&mut self, message: impl Into<String>, span: Span) {
+        self.warnings.push(Diagnostic::warning(message, span));
+    }
+
+    /// the regex of a lexical term, `stack` holds the definitions being inlined
+    fn regex(&mut self, term: &Term, stack: &mut Vec<String>) -> Regex {
+        let group = |(text, precedence): Regex, outer: u8| if precedence < outer { format!("(?:{})", text) } else { text };
+        match term {
+            Term::Literal(lit) => (regex::escape(lit), if lit.chars().count() == 1 { 2 } else { 1 }),
+            Term::Set { body, negated: false } => (format!("[{}]", body), 2),
+            Term::Set { body, negated: true } => (format!("[^{}]", body), 2),
+            Term::Regex(regex) => (regex.clone(), 2),
+            Term::Empty => (String::new(), 2),
+            Term::Ref(name, span) => {
+                let Some(definition) = self.definitions.get(name.as_str()).copied() else {
+                    self.warn(format!("`{}` is not defined", name), *span);
+                    return (String::new(), 2);
+                };
+                if !definition.lexical || stack.contains(&definition.name) {
+                    self.warn(format!("`{}` cannot be part of a token", name), *span);
+                    return (String::new(), 2);
+                }
+                stack.push(definition.name.clone());
+                let regex = self.regex(&definition.term, stack);
+                stack.pop();
+                regex
+            }
+            Term::Sequence(items) => {
+                let parts = items.iter().map(|item| {
+                    let part = self.regex(item, stack);
+                    group(part, 1)
+                }).collect::<Vec<_>>();
+                (parts.concat(), 1)
+            }
+            Term::Choice(alternatives) => {
+                let parts = alternatives.iter()
+                    .filter(|alternative| **alternative != Term::Empty)
+                    .map(|alternative| self.regex(alternative, stack).0)
+                    .collect::<Vec<_>>();
+                match parts.len() < alternatives.len() {
+                    // an empty alternative makes the rest optional
+                    true => (format!("(?:{})?", parts.join("|")), 2),
+                    false => (parts.join("|"), 0),
+                }
+            }
+            Term::Suffix(inner, suffix) => {
+                let inner = self.regex(inner, stack);
+                (format!("{}{}", group(inner, 2), suffix), 2)
+            }
+            Term::Capture(_, inner) => self.regex(inner, stack),
+        }
+    }
+
+    /// the expression of a parser term, `None` when it matches nothing
+    fn expr(&mut self, term: &Term) -> Option<Expr> {
+        match term {
+            Term::Literal(lit) => Some(Expr::Ref(format!("\"{}\"", loader::escape_literal(lit)), Span::default())),
+            Term::Set { .. } | Term::Regex(_) => {
+                let regex = self.regex(term, &mut vec![]).0;
+                Some(Expr::Ref(self.token_for(regex), Span::default()))
+            }
+            Term::Empty => None,
+            Term::Ref(name, span) => match self.definitions.get(name.as_str()).copied() {
+                Some(definition) if definition.lexical && definition.fragment => {
+                    let regex = self.regex(&definition.term, &mut vec![definition.name.clone()]).0;
+                    Some(Expr::Ref(self.token_for(regex), *span))
+                }
+                Some(definition) if definition.lexical => Some(Expr::Ref(token_name(name), *span)),
+                Some(_) => Some(Expr::Ref(rule_name(name), *span)),
+                // the whole input is always parsed
+                None if name == "EOF" => None,
+                None => {
+                    self.warn(format!("`{}` is not defined", name), *span);
+                    Some(Expr::Ref(name.clone(), *span))
+                }
+            },
+            Term::Sequence(items) => {
+                let mut items = items.iter().filter_map(|item| self.expr(item)).collect::<Vec<_>>();
+                match items.len() {
+                    0 => None,
+                    1 => items.pop(),
+                    _ => Some(Expr::Sequence(items)),
+                }
+            }
+            Term::Choice(alternatives) => {
+                let exprs = alternatives.iter().map(|alternative| self.expr(alternative)).collect::<Vec<_>>();
+                let optional = exprs.iter().any(Option::is_none);
+                let mut exprs = exprs.into_iter().flatten().collect::<Vec<_>>();
+                let expr = match exprs.len() {
+                    0 => return None,
+                    1 => exprs.pop().unwrap(),
+                    _ => Expr::Choice(exprs),
+                };
+                Some(if optional { Expr::Optional(Box::new(expr)) } else { expr })
+            }
+            Term::Suffix(inner, suffix) => {
+                let inner = Box::new(self.expr(inner)?);
+                if suffix.len() > 1 {
+                    let span = inner.first_span().unwrap_or_default();
+                    self.warn(format!("Non-greedy `{}` is treated as `{}`", suffix, &suffix[..1]), span);
+                }
+                Some(match &suffix[..1] {
+                    "?" => Expr::Optional(inner),
+                    "*" => Expr::ZeroOrMore(inner),
+                    _ => Expr::OneOrMore(inner),
+                })
+            }
+            Term::Capture(name, inner) => Some(Expr::Capture(name.clone(), Box::new(self.expr(inner)?))),
+        }
+    }
+
+    /// a token for a piece of regex written inside a rule, shared by equal pieces
+    fn token_for(&mut self, regex: String) -> String {
+        let pattern = Pattern::Regex(regex);
+        if let Some(token) = self.tokens.iter().find(|token| token.pattern == pattern) {
+            return token.name.clone();
+        }
+        let name = format!("TOKEN_{}", self.tokens.len());
+        self.tokens.push(TokenDef { name: name.clone(), pattern, kind: 0, span: Span::default() });
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_from_antlr() {
+        let conversion = from_antlr("
+            grammar Calc;
+            options { language = Java; }
+            @header { package calc; }
+
+            prog: stat+ EOF;
+            stat: lhs=ID '=' expr ';'     # assign
+                | expr ';'                # print
+                ;
+            expr: expr ('*' | '/') expr
+                | expr ('+' | '-') expr
+                | INT
+                | '(' expr ')'
+                ;
+            ID: Letter (Letter | [0-9])*;
+            INT: [0-9]+ {count++;};
+            fragment Letter: [a-zA-Z_];
+            WS: [ \\t\\r\\n]+ -> skip;
+            STR: '\"' ~[\"\\n]* '\"';
+        ").unwrap();
+        assert_eq!(conversion.text, [
+            "ID: /[a-zA-Z_](?:[a-zA-Z_]|[0-9])*/;",
+            "INT: /[0-9]+/;",
+            "WS: /[ \\t\\r\\n]+/;",
+            "STR: /\"[^\"\\n]*\"/;",
+            "prog: stat+;",
+            "stat: lhs:ID \"=\" expr \";\" | expr \";\";",
+            "expr: expr (\"*\" | \"/\") expr | expr (\"+\" | \"-\") expr | INT | \"(\" expr \")\";",
+            "",
+        ].join("\n"));
+        let warnings = conversion.warnings.iter().map(|warning| warning.message.as_str()).collect::<Vec<_>>();
+        assert!(warnings.contains(&"`options` block is left out"));
+        assert!(warnings.contains(&"Action `@header` is left out"));
+        assert!(warnings.contains(&"Alternative label `#assign` is left out"));
+        assert!(warnings.contains(&"Action in `INT` is left out"));
+        assert!(warnings.contains(&"Lexer command `-> skip` of `WS` is left out"));
+        assert!(warnings.iter().any(|warning| warning.starts_with("`expr` relies on ANTLR")));
+
+        let node = conversion.grammar.interpret("x=(1+2)*3;").unwrap();
+        assert_eq!(node.label, "prog");
+    }
+
+    #[test]
+    fn test_from_antlr_commands() {
+        let conversion = from_antlr("grammar T; a : A | B ; A : 'a' -> type(B), channel(HIDDEN) ; B : 'b' ;").unwrap();
+        let warnings = conversion.warnings.iter().map(|warning| warning.message.as_str()).collect::<Vec<_>>();
+        assert!(warnings.contains(&"Lexer command `-> type(B),channel(HIDDEN)` of `A` is left out"));
+        assert!(conversion.text.contains("a: A | B;"));
+
+        assert_eq!(from_antlr("grammar T; A : 'a' -> skip").unwrap_err().message, "Expected `;`");
+        assert_eq!(from_antlr("grammar T; a : <assoc=right").unwrap_err().message, "Unclosed `<`");
+        assert_eq!(from_antlr("grammar T; a : A<fail=true").unwrap_err().message, "Unclosed `<`");
+    }
+
+    #[test]
+    fn test_from_w3c_ebnf() {
+        let conversion = from_ebnf("
+            /* a list of numbers */
+            List ::= '[' (Number (',' Number)*)? ']'
+            Number ::= Digit+ ('.' Digit+)?
+            Digit ::= [0-9]
+            Name ::= [^#x0-#x20,] - 'x'
+        ").unwrap();
+        assert_eq!(conversion.text, "NUMBER: /[0-9]+(?:\\.[0-9]+)?/;\nList: \"[\" (NUMBER (\",\" NUMBER)*)? \"]\";\n");
+        assert!(conversion.warnings[0].message.starts_with("Exceptions"));
+        assert_eq!(conversion.warnings[1].message, "`Name` is not used by any rule and is left out");
+        assert_eq!(conversion.warnings.len(), 2);
+        assert!(conversion.grammar.interpret("[1,2.5]").is_ok());
+    }
+
+    #[test]
+    fn test_from_iso_ebnf() {
+        let conversion = from_ebnf("
+            (* assignments *)
+            program = { statement } ;
+            statement = identifier , '=' , digit , [ ';' ] , ? end of line ? ;
+            identifier = letter , { letter | digit } ;
+            letter = 'a' | 'b' | 'c' ;
+            digit = '0' | '1' ;
+        ").unwrap();
+        assert_eq!(conversion.text, "IDENTIFIER: /(?:a|b|c)(?:a|b|c|0|1)*/;\nDIGIT: /0|1/;\nprogram: statement*;\nstatement: IDENTIFIER \"=\" DIGIT \";\"?;\n");
+        assert_eq!(conversion.warnings[0].message, "Special sequence `? end of line ?` is left out");
+    }
+}
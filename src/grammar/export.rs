@@ -0,0 +1,564 @@
+use regex_syntax::hir::{Class, Hir, HirKind};
+
+use crate::{parser::Fixity, span::Span};
+
+use super::{lint::Diagnostic, Expr, Grammar, Pattern, RuleDef};
+
+/// text of an exported grammar and the parts of the `.ars` grammar it could not express
+#[derive(Debug, Clone)]
+pub struct Export {
+    pub text: String,
+    pub warnings: Vec<Diagnostic>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Format {
+    Ebnf,
+    Antlr,
+    TreeSitter,
+}
+
+impl Format {
+    fn name(&self) -> &'static str {
+        match self {
+            Format::Ebnf => "EBNF",
+            Format::Antlr => "ANTLR",
+            Format::TreeSitter => "tree-sitter",
+        }
+    }
+}
+
+/// W3C EBNF, one `name ::= expression` per rule followed by the tokens
+pub fn to_ebnf(grammar: &Grammar) -> Export {
+    Exporter::new(grammar, Format::Ebnf).ebnf()
+}
+
+/// an ANTLR4 combined grammar (`.g4`) called `name`
+pub fn to_antlr(grammar: &Grammar, name: &str) -> Export {
+    Exporter::new(grammar, Format::Antlr).antlr(name)
+}
+
+/// a tree-sitter `grammar.js` for a language called `name`
+pub fn to_tree_sitter(grammar: &Grammar, name: &str) -> Export {
+    Exporter::new(grammar, Format::TreeSitter).tree_sitter(name)
+}
+
+struct Exporter<'g> {
+    grammar: &'g Grammar,
+    format: Format,
+    warnings: Vec<Diagnostic>,
+}
+
+impl<'g> Exporter<'g> {
+    fn new(grammar: &'g Grammar, format: Format) -> Self {
+        Self { grammar, format, warnings: vec![] }
+    }
+
+    fn warn(&mut self, message: impl Into<String>, span: Span) {
+        self.warnings.push(Diagnostic::warning(message, span));
+    }
+
+    fn finish(self, text: String) -> Export {
+        Export { text, warnings: self.warnings }
+    }
+
+    /// warns about the attributes of a rule the format cannot express
+    fn attributes(&mut self, rule: &RuleDef) {
+        let attributes = &rule.attributes;
+        let mut unsupported = vec![];
+        if attributes.inline && self.format != Format::TreeSitter {
+            unsupported.push("@inline");
+        }
+        if attributes.label.is_some() {
+            unsupported.push("@label");
+        }
+        if attributes.kind.is_some() {
+            unsupported.push("@kind");
+        }
+        if attributes.value.is_some() {
+            unsupported.push("@value");
+        }
+        if !attributes.drop.is_empty() {
+            unsupported.push("@drop");
+        }
+        for attribute in unsupported {
+            self.warn(format!("`{}` of `{}` has no {} equivalent and is left out", attribute, rule.name, self.format.name()), rule.span);
+        }
+    }
+
+    fn ebnf(mut self) -> Export {
+        let mut lines = vec![];
+        for rule in self.grammar.rules.iter() {
+            self.attributes(rule);
+            if rule.attributes.pratt {
+                lines.extend(self.ebnf_ladder(rule));
+            } else {
+                let expr = self.expr(&rule.expr, 0);
+                lines.push(format!("{} ::= {}", plain_name(&rule.name), expr));
+            }
+        }
+        let tokens = self.grammar.tokens.iter().filter(|token| !is_anonymous(&token.name)).collect::<Vec<_>>();
+        if !tokens.is_empty() {
+            lines.push(String::new());
+            lines.push("/* tokens */".to_string());
+        }
+        for token in tokens {
+            let pattern = match &token.pattern {
+                Pattern::Literal(lit) => self.literal(lit),
+                Pattern::Regex(regex) => self.regex(regex, token.span),
+            };
+            lines.push(format!("{} ::= {}", plain_name(&token.name), pattern));
+        }
+        let text = lines.join("\n") + "\n";
+        self.finish(text)
+    }
+
+    /// EBNF has no precedence, so a `@pratt` rule becomes one rule per level, the tightest last
+    fn ebnf_ladder(&mut self, rule: &RuleDef) -> Vec<String> {
+        let name = plain_name(&rule.name);
+        let levels = self.grammar.precedence.clone();
+        let level_name = |level: usize| match level {
+            0 => name.clone(),
+            _ if level == levels.len() => format!("{}Operand", name),
+            _ => format!("{}{}", name, level + 1),
+        };
+        let mut lines = vec![];
+        for (level, precedence) in levels.iter().enumerate() {
+            let operators = precedence.operators.iter().map(|op| self.reference(op)).collect::<Vec<_>>();
+            let operators = match operators.len() {
+                1 => operators[0].clone(),
+                _ => format!("({})", operators.join(" | ")),
+            };
+            let (this, next) = (level_name(level), level_name(level + 1));
+            let body = match precedence.fixity {
+                Fixity::Left => format!("{} ({} {})*", next, operators, next),
+                Fixity::Right => format!("{} ({} {})?", next, operators, this),
+                Fixity::Prefix => format!("{} {} | {}", operators, this, next),
+                Fixity::Postfix => format!("{} {}*", next, operators),
+            };
+            lines.push(format!("{} ::= {}", this, body));
+        }
+        let operand = self.expr(&rule.expr, 0);
+        lines.push(format!("{} ::= {}", level_name(levels.len()), operand));
+        lines
+    }
+
+    fn antlr(mut self, name: &str) -> Export {
+        let mut lines = vec![format!("grammar {};", name), String::new()];
+        for rule in self.grammar.rules.iter() {
+            self.attributes(rule);
+            let body = if rule.attributes.pratt {
+                self.antlr_operators(rule)
+            } else {
+                match &rule.expr {
+                    Expr::Choice(alternatives) => alternatives.iter().map(|alternative| self.expr(alternative, 1)).collect(),
+                    expr => vec![self.expr(expr, 0)],
+                }
+            };
+            lines.push(format!("{}\n    : {}\n    ;", rule_name(&rule.name), body.join("\n    | ")));
+            lines.push(String::new());
+        }
+        for token in self.grammar.tokens.iter().filter(|token| !is_anonymous(&token.name)) {
+            let pattern = match &token.pattern {
+                Pattern::Literal(lit) => self.literal(lit),
+                Pattern::Regex(regex) => self.regex(regex, token.span),
+            };
+            lines.push(format!("{} : {} ;", plain_name(&token.name), pattern));
+        }
+        let text = lines.join("\n").trim_end().to_string() + "\n";
+        self.finish(text)
+    }
+
+    /// ANTLR gives earlier alternatives of a left recursive rule a higher precedence
+    fn antlr_operators(&mut self, rule: &RuleDef) -> Vec<String> {
+        let name = rule_name(&rule.name);
+        let mut alternatives = vec![];
+        for precedence in self.grammar.precedence.clone().iter().rev() {
+            let operators = precedence.operators.iter().map(|op| self.reference(op)).collect::<Vec<_>>();
+            let operators = match operators.len() {
+                1 => operators[0].clone(),
+                _ => format!("({})", operators.join(" | ")),
+            };
+            alternatives.push(match precedence.fixity {
+                Fixity::Left => format!("{} {} {}", name, operators, name),
+                Fixity::Right => format!("<assoc=right> {} {} {}", name, operators, name),
+                Fixity::Prefix => format!("{} {}", operators, name),
+                Fixity::Postfix => format!("{} {}", name, operators),
+            });
+        }
+        match &rule.expr {
+            Expr::Choice(operands) => alternatives.extend(operands.iter().map(|operand| self.expr(operand, 1))),
+            expr => alternatives.push(self.expr(expr, 0)),
+        }
+        alternatives
+    }
+
+    fn tree_sitter(mut self, name: &str) -> Export {
+        let mut lines = vec![
+            "module.exports = grammar({".to_string(),
+            format!("  name: '{}',", name),
+            // whitespace is part of `.ars` grammars, nothing is skipped implicitly
+            "  extras: $ => [],".to_string(),
+            "  rules: {".to_string(),
+        ];
+        for rule in self.grammar.rules.iter() {
+            self.attributes(rule);
+            let body = if rule.attributes.pratt { self.tree_sitter_operators(rule) } else { self.expr(&rule.expr, 0) };
+            lines.push(format!("    {}: $ => {},", tree_sitter_name(self.grammar, &rule.name), body));
+        }
+        for token in self.grammar.tokens.iter().filter(|token| !is_anonymous(&token.name)) {
+            let pattern = match &token.pattern {
+                Pattern::Literal(lit) => self.literal(lit),
+                Pattern::Regex(regex) => {
+                    if ["(?", "\\A", "\\z"].iter().any(|construct| regex.contains(construct)) {
+                        self.warn(format!("The regex of `{}` uses syntax JavaScript may not support", token.name), token.span);
+                    }
+                    format!("/{}/", regex.replace('/', "\\/"))
+                }
+            };
+            lines.push(format!("    {}: $ => {},", plain_name(&token.name), pattern));
+        }
+        lines.push("  }".to_string());
+        lines.push("});".to_string());
+        let text = lines.join("\n") + "\n";
+        self.finish(text)
+    }
+
+    fn tree_sitter_operators(&mut self, rule: &RuleDef) -> String {
+        let name = format!("$.{}", tree_sitter_name(self.grammar, &rule.name));
+        let mut alternatives = vec![];
+        for (level, precedence) in self.grammar.precedence.clone().iter().enumerate() {
+            let operators = precedence.operators.iter().map(|op| self.reference(op)).collect::<Vec<_>>();
+            let operators = match operators.len() {
+                1 => operators[0].clone(),
+                _ => format!("choice({})", operators.join(", ")),
+            };
+            let level = level + 1;
+            alternatives.push(match precedence.fixity {
+                Fixity::Left => format!("prec.left({}, seq({}, {}, {}))", level, name, operators, name),
+                Fixity::Right => format!("prec.right({}, seq({}, {}, {}))", level, name, operators, name),
+                Fixity::Prefix => format!("prec({}, seq({}, {}))", level, operators, name),
+                Fixity::Postfix => format!("prec.left({}, seq({}, {}))", level, name, operators),
+            });
+        }
+        alternatives.push(self.expr(&rule.expr, 0));
+        format!("choice({})", alternatives.join(", "))
+    }
+
+    /// a token or rule reference written in the target format
+    fn reference(&mut self, name: &str) -> String {
+        if let Some(token) = self.grammar.token(name).filter(|token| is_anonymous(&token.name)) {
+            if let Pattern::Literal(lit) = &token.pattern {
+                return self.literal(lit);
+            }
+        }
+        match self.format {
+            Format::Ebnf => plain_name(name),
+            Format::Antlr if self.grammar.rule(name).is_some() => rule_name(name),
+            Format::Antlr => plain_name(name),
+            Format::TreeSitter => format!("$.{}", tree_sitter_name(self.grammar, name)),
+        }
+    }
+
+    fn literal(&self, lit: &str) -> String {
+        match self.format {
+            Format::Ebnf if !lit.contains('"') => format!("\"{}\"", lit),
+            Format::Ebnf if !lit.contains('\'') => format!("'{}'", lit),
+            // a literal with both quotes is written as a sequence of character codes
+            Format::Ebnf => lit.chars().map(|c| format!("#x{:X}", c as u32)).collect::<Vec<_>>().join(" "),
+            Format::Antlr | Format::TreeSitter => {
+                let mut out = String::from("'");
+                for c in lit.chars() {
+                    match c {
+                        '\'' => out.push_str("\\'"),
+                        '\\' => out.push_str("\\\\"),
+                        '\n' => out.push_str("\\n"),
+                        '\r' => out.push_str("\\r"),
+                        '\t' => out.push_str("\\t"),
+                        c if c.is_control() => out.push_str(&format!("\\u{:04X}", c as u32)),
+                        c => out.push(c),
+                    }
+                }
+                out.push('\'');
+                out
+            }
+        }
+    }
+
+    /// writes `expr` with parentheses when it binds looser than `outer`, using the
+    /// precedence levels of `Expr`'s own printer
+    fn expr(&mut self, expr: &Expr, outer: u8) -> String {
+        if self.format == Format::TreeSitter {
+            return self.tree_sitter_expr(expr);
+        }
+        let precedence = match expr {
+            Expr::Choice(_) => 0,
+            Expr::Sequence(_) => 1,
+            Expr::Optional(_) | Expr::ZeroOrMore(_) | Expr::OneOrMore(_) => 3,
+            Expr::Ref(_, _) => 4,
+            // captures and drops print their inner expression, maybe with a label
+            Expr::Capture(_, _) | Expr::Drop(_) => outer,
+        };
+        let text = match expr {
+            Expr::Ref(name, _) => self.reference(name),
+            Expr::Sequence(items) => items.iter().map(|item| self.expr(item, 2)).collect::<Vec<_>>().join(" "),
+            Expr::Choice(alternatives) => alternatives.iter().map(|alternative| self.expr(alternative, 1)).collect::<Vec<_>>().join(" | "),
+            Expr::Optional(inner) => format!("{}?", self.expr(inner, 4)),
+            Expr::ZeroOrMore(inner) => format!("{}*", self.expr(inner, 4)),
+            Expr::OneOrMore(inner) => format!("{}+", self.expr(inner, 4)),
+            Expr::Capture(name, inner) => self.capture(name, inner, outer),
+            Expr::Drop(inner) => {
+                let span = inner.first_span().unwrap_or_default();
+                self.warn(format!("`~` has no {} equivalent, the expression is kept", self.format.name()), span);
+                self.expr(inner, outer)
+            }
+        };
+        if precedence < outer { format!("({})", text) } else { text }
+    }
+
+    fn capture(&mut self, name: &str, inner: &Expr, outer: u8) -> String {
+        let span = inner.first_span().unwrap_or_default();
+        if self.format == Format::Antlr {
+            // ANTLR labels single elements, `+=` collects repeated ones
+            match inner {
+                Expr::Ref(_, _) | Expr::Optional(_) if is_element(inner) => return format!("{}={}", name, self.expr(inner, 4)),
+                Expr::ZeroOrMore(_) | Expr::OneOrMore(_) if is_element(inner) => return format!("{}+={}", name, self.expr(inner, 4)),
+                _ => {}
+            }
+        }
+        self.warn(format!("Capture `{}` has no {} equivalent and is left out", name, self.format.name()), span);
+        self.expr(inner, outer)
+    }
+
+    fn tree_sitter_expr(&mut self, expr: &Expr) -> String {
+        let list = |exprs: &[Expr], this: &mut Self| exprs.iter().map(|expr| this.tree_sitter_expr(expr)).collect::<Vec<_>>().join(", ");
+        match expr {
+            Expr::Ref(name, _) => self.reference(name),
+            Expr::Sequence(items) => format!("seq({})", list(items, self)),
+            Expr::Choice(alternatives) => format!("choice({})", list(alternatives, self)),
+            Expr::Optional(inner) => format!("optional({})", self.tree_sitter_expr(inner)),
+            Expr::ZeroOrMore(inner) => format!("repeat({})", self.tree_sitter_expr(inner)),
+            Expr::OneOrMore(inner) => format!("repeat1({})", self.tree_sitter_expr(inner)),
+            Expr::Capture(name, inner) => format!("field('{}', {})", name, self.tree_sitter_expr(inner)),
+            Expr::Drop(inner) => {
+                let span = inner.first_span().unwrap_or_default();
+                self.warn("`~` has no tree-sitter equivalent, the expression is kept", span);
+                self.tree_sitter_expr(inner)
+            }
+        }
+    }
+
+    /// a token regex in the lexer syntax of EBNF or ANTLR
+    fn regex(&mut self, pattern: &str, span: Span) -> String {
+        match regex_syntax::parse(pattern) {
+            Ok(hir) => self.hir(&hir, span).0,
+            Err(_) => {
+                self.warn(format!("Invalid regex `{}` is left out", pattern), span);
+                String::new()
+            }
+        }
+    }
+
+    /// the text of a regex and how tightly it binds, 0 for alternations, 1 for concatenations
+    fn hir(&mut self, hir: &Hir, span: Span) -> (String, u8) {
+        let group = |(text, precedence): (String, u8), outer: u8| if precedence < outer { format!("({})", text) } else { text };
+        match hir.kind() {
+            HirKind::Empty => (self.literal(""), 2),
+            HirKind::Literal(literal) => (self.literal(&String::from_utf8_lossy(&literal.0)), 2),
+            HirKind::Class(class) => (self.class(class, span), 2),
+            HirKind::Look(_) => {
+                self.warn(format!("Anchors and word boundaries have no {} equivalent and are left out", self.format.name()), span);
+                (self.literal(""), 2)
+            }
+            HirKind::Repetition(repetition) => {
+                let inner = self.hir(&repetition.sub, span);
+                let inner = group(inner, 2);
+                let lazy = if !repetition.greedy && self.format == Format::Antlr { "?" } else { "" };
+                let text = match (repetition.min, repetition.max) {
+                    (0, Some(1)) => format!("{}?{}", inner, lazy),
+                    (0, None) => format!("{}*{}", inner, lazy),
+                    (1, None) => format!("{}+{}", inner, lazy),
+                    // counted repetitions are spelled out
+                    (min, max) => {
+                        let mut parts = vec![inner.clone(); min as usize];
+                        match max {
+                            None => parts.push(format!("{}*", inner)),
+                            Some(max) => parts.extend((min..max).map(|_| format!("{}?", inner))),
+                        }
+                        return (parts.join(" "), 1);
+                    }
+                };
+                (text, 2)
+            }
+            HirKind::Capture(capture) => self.hir(&capture.sub, span),
+            HirKind::Concat(items) => {
+                let parts = items.iter().map(|item| {
+                    let part = self.hir(item, span);
+                    group(part, 1)
+                }).collect::<Vec<_>>();
+                (parts.join(" "), 1)
+            }
+            HirKind::Alternation(alternatives) => {
+                let parts = alternatives.iter().map(|alternative| self.hir(alternative, span).0).collect::<Vec<_>>();
+                (parts.join(" | "), 0)
+            }
+        }
+    }
+
+    fn class(&mut self, class: &Class, span: Span) -> String {
+        let mut ranges = match class {
+            Class::Unicode(class) => class.ranges().iter().map(|range| (range.start(), range.end())).collect::<Vec<_>>(),
+            Class::Bytes(class) => class.ranges().iter().map(|range| (range.start() as char, range.end() as char)).collect(),
+        };
+        if ranges.is_empty() {
+            self.warn("A character class that matches nothing is left out", span);
+            return self.literal("");
+        }
+        // classes reaching the last code point were most likely written negated
+        let negated = ranges.first().is_some_and(|range| range.0 == '\0') && ranges.last().is_some_and(|range| range.1 == char::MAX);
+        if negated {
+            let mut complement = class.clone();
+            complement.negate();
+            ranges = match complement {
+                Class::Unicode(class) => class.ranges().iter().map(|range| (range.start(), range.end())).collect(),
+                Class::Bytes(class) => class.ranges().iter().map(|range| (range.start() as char, range.end() as char)).collect(),
+            };
+            if ranges.is_empty() {
+                return match self.format {
+                    Format::Antlr => ".".to_string(),
+                    _ => "[#x0-#x10FFFF]".to_string(),
+                };
+            }
+        }
+        let body = ranges.iter().map(|(start, end)| match start == end {
+            true => self.class_char(*start),
+            false => format!("{}-{}", self.class_char(*start), self.class_char(*end)),
+        }).collect::<String>();
+        match (self.format, negated) {
+            (Format::Antlr, true) => format!("~[{}]", body),
+            (_, true) => format!("[^{}]", body),
+            _ => format!("[{}]", body),
+        }
+    }
+
+    fn class_char(&self, c: char) -> String {
+        match self.format {
+            Format::Antlr => match c {
+                ']' | '\\' | '-' => format!("\\{}", c),
+                '\n' => "\\n".to_string(),
+                '\r' => "\\r".to_string(),
+                '\t' => "\\t".to_string(),
+                c if c.is_ascii_graphic() || c == ' ' => c.to_string(),
+                c if (c as u32) <= 0xFFFF => format!("\\u{:04X}", c as u32),
+                c => format!("\\u{{{:X}}}", c as u32),
+            },
+            _ => match c {
+                ']' | '^' | '-' | '#' | '\\' => format!("#x{:X}", c as u32),
+                c if c.is_ascii_graphic() => c.to_string(),
+                c => format!("#x{:X}", c as u32),
+            },
+        }
+    }
+}
+
+/// literals used inside rules become tokens named by their quoted text
+fn is_anonymous(name: &str) -> bool {
+    name.starts_with('"')
+}
+
+/// whether ANTLR can put a label on the expression, a name with at most one suffix
+fn is_element(expr: &Expr) -> bool {
+    match expr {
+        Expr::Ref(_, _) => true,
+        Expr::Optional(inner) | Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => matches!(**inner, Expr::Ref(_, _)),
+        _ => false,
+    }
+}
+
+/// `namespace.Name` becomes `namespace_Name`
+fn plain_name(name: &str) -> String {
+    name.replace('.', "_")
+}
+
+/// ANTLR parser rules start with a lowercase letter
+fn rule_name(name: &str) -> String {
+    let name = plain_name(name);
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_lowercase().chain(chars).collect(),
+        None => name,
+    }
+}
+
+/// hidden rules start with `_` in tree-sitter, which matches what `@inline` does
+fn tree_sitter_name(grammar: &Grammar, name: &str) -> String {
+    match grammar.rule(name) {
+        Some(rule) if rule.attributes.inline => format!("_{}", plain_name(name)),
+        _ => plain_name(name),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grammar() -> Grammar {
+        Grammar::parse("
+            NUM: /[0-9]+(\\.[0-9]{1,2})?/;
+            STR: /\"[^\"]*\"/;
+            LET: \"let\";
+            %left \"+\" \"-\";
+            %right \"^\";
+            %prefix \"-\";
+            Program: Stmt*;
+            @label(\"Let\")
+            Stmt: ~LET name:ID \"=\" value:Expr;
+            ID: /[a-z_]+/;
+            @pratt Expr: NUM | STR | ID | ~\"(\" Expr ~\")\";
+        ").unwrap()
+    }
+
+    #[test]
+    fn test_ebnf() {
+        let export = to_ebnf(&grammar());
+        assert_eq!(export.text, [
+            "Program ::= Stmt*",
+            "Stmt ::= LET ID \"=\" Expr",
+            "Expr ::= Expr2 ((\"+\" | \"-\") Expr2)*",
+            "Expr2 ::= Expr3 (\"^\" Expr2)?",
+            "Expr3 ::= \"-\" Expr3 | ExprOperand",
+            "ExprOperand ::= NUM | STR | ID | \"(\" Expr \")\"",
+            "",
+            "/* tokens */",
+            "NUM ::= [0-9]+ (\".\" [0-9] [0-9]?)?",
+            "STR ::= '\"' [^\"]* '\"'",
+            "LET ::= \"let\"",
+            "ID ::= [_a-z]+",
+            "",
+        ].join("\n"));
+        let warnings = export.warnings.iter().map(|warning| warning.message.as_str()).collect::<Vec<_>>();
+        assert!(warnings.contains(&"`@label` of `Stmt` has no EBNF equivalent and is left out"));
+        assert!(warnings.contains(&"Capture `name` has no EBNF equivalent and is left out"));
+        assert!(warnings.contains(&"`~` has no EBNF equivalent, the expression is kept"));
+    }
+
+    #[test]
+    fn test_antlr() {
+        let export = to_antlr(&grammar(), "Lang");
+        assert!(export.text.starts_with("grammar Lang;\n\nprogram\n    : stmt*\n    ;\n"));
+        assert!(export.text.contains("stmt\n    : LET name=ID '=' value=expr\n    ;"));
+        assert!(export.text.contains("expr\n    : '-' expr\n    | <assoc=right> expr '^' expr\n    | expr ('+' | '-') expr\n    | NUM\n    | STR\n    | ID\n    | '(' expr ')'\n    ;"));
+        assert!(export.text.contains("NUM : [0-9]+ ('.' [0-9] [0-9]?)? ;"));
+        assert!(export.text.contains("STR : '\"' ~[\"]* '\"' ;"));
+        assert!(!export.warnings.iter().any(|warning| warning.message.contains("Capture")));
+    }
+
+    #[test]
+    fn test_tree_sitter() {
+        let export = to_tree_sitter(&grammar(), "lang");
+        assert!(export.text.contains("  name: 'lang',"));
+        assert!(export.text.contains("    Stmt: $ => seq($.LET, field('name', $.ID), '=', field('value', $.Expr)),"));
+        assert!(export.text.contains("    Expr: $ => choice(prec.left(1, seq($.Expr, choice('+', '-'), $.Expr)), prec.right(2, seq($.Expr, '^', $.Expr)), prec(3, seq('-', $.Expr)), choice($.NUM, $.STR, $.ID, seq('(', $.Expr, ')'))),"));
+        assert!(export.text.contains("    NUM: $ => /[0-9]+(\\.[0-9]{1,2})?/,"));
+    }
+}
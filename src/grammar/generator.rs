@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+
+use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, Hir, HirKind};
+
+use crate::{
+    ast::{Node, Value},
+    lexer::Lexer,
+    parser::{Fixity, Operator},
+    token::TokenData,
+};
+
+use super::{
+    interpreter::{operand_node, shape_node, Interpreter},
+    Expr, Grammar, GrammarError, Pattern, RuleDef,
+};
+
+/// SplitMix64, small and good enough to pick random paths through a grammar
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        Self(seed)
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// a number in `0..n`, `n` must not be 0
+    pub fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// an index picked with probability proportional to its weight
+    pub fn weighted(&mut self, weights: &[u32]) -> usize {
+        let total = weights.iter().map(|&w| w as u64).sum::<u64>();
+        if total == 0 {
+            return self.below(weights.len());
+        }
+        let mut pick = self.next_u64() % total;
+        for (i, &weight) in weights.iter().enumerate() {
+            if pick < weight as u64 {
+                return i;
+            }
+            pick -= weight as u64;
+        }
+        weights.len() - 1
+    }
+}
+
+/// a generated input and the tree the grammar parses it into
+#[derive(Debug, Clone)]
+pub struct Sample {
+    pub text: String,
+    pub node: Node,
+}
+
+/// one step of an operator chain of a `@pratt` rule, in input order
+enum Item {
+    Prefix(Operator, String),
+    Operand(Node),
+    /// a binary or postfix operator
+    Infix(Operator, String),
+}
+
+/// generates random inputs of a grammar, the same seed gives the same inputs
+///
+/// every sample is parsed back with the `Interpreter`, walks the grammar reads
+/// differently (an earlier alternative winning, tokens running into each other)
+/// are thrown away and tried again
+pub struct Generator<'g> {
+    grammar: &'g Grammar,
+    interpreter: Interpreter<'g>,
+    lexer: Lexer,
+    rng: Rng,
+    max_depth: usize,
+    max_repeat: usize,
+    attempts: usize,
+    /// (rule, alternative) -> weight of that alternative of the rule, 1 otherwise
+    weights: HashMap<(String, usize), u32>,
+    /// least number of nested rules needed to finish each rule
+    heights: HashMap<&'g str, usize>,
+    drops: HashMap<&'g str, Vec<u32>>,
+    operators: Vec<Operator>,
+    regexes: HashMap<u32, Hir>,
+}
+
+impl<'g> Generator<'g> {
+    pub fn new(grammar: &'g Grammar, seed: u64) -> Result<Self, GrammarError> {
+        let interpreter = Interpreter::new(grammar)?;
+        let mut regexes = HashMap::new();
+        for token in grammar.tokens.iter() {
+            if let Pattern::Regex(regex) = &token.pattern {
+                let hir = regex_syntax::parse(regex)
+                    .map_err(|e| GrammarError::new(format!("Invalid regex for `{}`: {}", token.name, e), token.span))?;
+                regexes.insert(token.kind, hir);
+            }
+        }
+        let mut drops = HashMap::new();
+        for rule in grammar.rules.iter() {
+            let kinds = rule.attributes.drop.iter().filter_map(|name| grammar.token(name)).map(|token| token.kind).collect();
+            drops.insert(rule.name.as_str(), kinds);
+        }
+        let heights = heights(grammar);
+        if let Some(rule) = grammar.rules.iter().find(|rule| !heights.contains_key(rule.name.as_str())) {
+            return Err(GrammarError::new(format!("`{}` cannot be generated, every way through it recurses", rule.name), rule.span));
+        }
+        Ok(Self {
+            grammar,
+            interpreter,
+            lexer: grammar.lexer()?,
+            rng: Rng::new(seed),
+            max_depth: 12,
+            max_repeat: 3,
+            attempts: 64,
+            weights: HashMap::new(),
+            heights,
+            drops,
+            operators: grammar.operators()?,
+            regexes,
+        })
+    }
+
+    /// rules nested deeper than this only take their shortest way out
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = depth;
+        self
+    }
+
+    /// most repetitions of `*`, `+`, unbounded regex repetitions and operator chains
+    pub fn with_max_repeat(mut self, repeat: usize) -> Self {
+        self.max_repeat = repeat;
+        self
+    }
+
+    /// how often the given alternative of a rule made of alternatives is picked, relative to the default of 1
+    pub fn with_weight(mut self, rule: &str, alternative: usize, weight: u32) -> Self {
+        self.weights.insert((rule.to_string(), alternative), weight);
+        self
+    }
+
+    /// generates an input for the first rule of the grammar
+    pub fn generate(&mut self) -> Result<Sample, GrammarError> {
+        let rule = self.grammar.start_rule().ok_or_else(|| GrammarError::new("Grammar has no rules".to_string(), Default::default()))?;
+        self.generate_rule(&rule.name.clone())
+    }
+
+    /// generates an input for the given rule
+    pub fn generate_rule(&mut self, name: &str) -> Result<Sample, GrammarError> {
+        let grammar = self.grammar;
+        let rule = grammar.rule(name).ok_or_else(|| GrammarError::new(format!("Rule `{}` not found", name), Default::default()))?;
+        for _ in 0..self.attempts {
+            let mut text = String::new();
+            let node = self.rule(rule, 0, &mut text)?;
+            let Some(tokens) = self.tokenize(&text) else { continue };
+            if self.interpreter.parse_tokens(name, tokens).is_ok_and(|parsed| parsed == node) {
+                return Ok(Sample { text, node });
+            }
+        }
+        Err(GrammarError::new(format!("Could not generate input that parses back as `{}` in {} attempts", name, self.attempts), rule.span))
+    }
+
+    fn rule(&mut self, rule: &'g RuleDef, depth: usize, text: &mut String) -> Result<Node, GrammarError> {
+        if rule.attributes.pratt {
+            return self.pratt(rule, depth, text);
+        }
+        let children = match &rule.expr {
+            Expr::Choice(alternatives) => {
+                let index = self.alternative(rule, alternatives, depth);
+                self.expr(&alternatives[index], depth, text)?
+            }
+            expr => self.expr(expr, depth, text)?,
+        };
+        let drop = &self.drops[rule.name.as_str()];
+        Ok(shape_node(rule.node_kind(), rule.node_label(), rule.attributes.value.as_deref(), drop, children))
+    }
+
+    fn operand(&mut self, rule: &'g RuleDef, depth: usize, text: &mut String) -> Result<Node, GrammarError> {
+        let children = self.expr(&rule.expr, depth, text)?;
+        let drop = &self.drops[rule.name.as_str()];
+        Ok(operand_node(rule.node_kind(), rule.node_label(), rule.attributes.value.as_deref(), drop, children))
+    }
+
+    /// picks the operators of a `@pratt` rule in input order and folds them the way `ParserState::pratt` does
+    fn pratt(&mut self, rule: &'g RuleDef, depth: usize, text: &mut String) -> Result<Node, GrammarError> {
+        // operators are found by kind, only the first one of a kind in each position can be read back
+        let first = |prefix: bool, op: &Operator| {
+            self.operators.iter().find(|other| other.kind == op.kind && (other.fixity == Fixity::Prefix) == prefix) == Some(op)
+        };
+        let prefixes = self.operators.iter().filter(|op| op.fixity == Fixity::Prefix && first(true, op)).copied().collect::<Vec<_>>();
+        let postfixes = self.operators.iter().filter(|op| op.fixity == Fixity::Postfix && first(false, op)).copied().collect::<Vec<_>>();
+        let binaries = self.operators.iter()
+            .filter(|op| matches!(op.fixity, Fixity::Left | Fixity::Right) && first(false, op))
+            .copied()
+            .collect::<Vec<_>>();
+
+        let chained = depth < self.max_depth;
+        let units = match chained && !binaries.is_empty() {
+            true => 1 + self.rng.below(self.max_repeat + 1),
+            false => 1,
+        };
+        let mut items = vec![];
+        for unit in 0..units {
+            if unit > 0 {
+                let op = binaries[self.rng.below(binaries.len())];
+                items.push(Item::Infix(op, self.token(op.kind, text)?));
+            }
+            if chained && !prefixes.is_empty() && self.rng.below(4) == 0 {
+                let op = prefixes[self.rng.below(prefixes.len())];
+                items.push(Item::Prefix(op, self.token(op.kind, text)?));
+            }
+            items.push(Item::Operand(self.operand(rule, depth, text)?));
+            if chained && !postfixes.is_empty() && self.rng.below(4) == 0 {
+                let op = postfixes[self.rng.below(postfixes.len())];
+                items.push(Item::Infix(op, self.token(op.kind, text)?));
+            }
+        }
+        let mut items = items.into_iter().peekable();
+        Ok(fold(&mut items, rule.node_kind(), 0))
+    }
+
+    /// index of the alternative to take, only the shortest ones once the depth is used up
+    fn alternative(&mut self, rule: &RuleDef, alternatives: &[Expr], depth: usize) -> usize {
+        let heights = alternatives.iter().map(|alternative| self.height(alternative)).collect::<Vec<_>>();
+        let budget = self.max_depth.saturating_sub(depth);
+        let lowest = heights.iter().flatten().copied().min().unwrap_or(0);
+        let weights = heights
+            .iter()
+            .enumerate()
+            .map(|(i, height)| match height {
+                Some(height) if *height <= budget.max(lowest) => *self.weights.get(&(rule.name.clone(), i)).unwrap_or(&1),
+                _ => 0,
+            })
+            .collect::<Vec<_>>();
+        // a weight of 0 everywhere still has to go somewhere finite
+        if weights.iter().all(|&w| w == 0) {
+            return heights.iter().position(|height| *height == Some(lowest)).unwrap_or(0);
+        }
+        self.rng.weighted(&weights)
+    }
+
+    fn height(&self, expr: &Expr) -> Option<usize> {
+        height(self.grammar, &self.heights, expr)
+    }
+
+    /// how many times an optional or repeated `inner` is generated
+    fn repeat(&mut self, inner: &Expr, depth: usize, min: usize) -> usize {
+        let fits = self.height(inner).is_some_and(|height| depth + height <= self.max_depth);
+        match fits {
+            true => min + self.rng.below(self.max_repeat + 1 - min.min(self.max_repeat)),
+            false => min,
+        }
+    }
+
+    fn expr(&mut self, expr: &'g Expr, depth: usize, text: &mut String) -> Result<Vec<Node>, GrammarError> {
+        match expr {
+            Expr::Ref(name, _) => match self.grammar.token(name) {
+                Some(token) => {
+                    let value = self.token(token.kind, text)?;
+                    Ok(vec![Node::new(token.kind, &token.name, Value::String(value))])
+                }
+                None => {
+                    let rule = self.grammar.rule(name).expect("Interpreter checks that references are defined");
+                    let node = self.rule(rule, depth + 1, text)?;
+                    match rule.attributes.inline {
+                        true => Ok(node.into_children()),
+                        false => Ok(vec![node]),
+                    }
+                }
+            },
+            Expr::Capture(name, inner) => {
+                let mut nodes = self.expr(inner, depth, text)?;
+                for node in nodes.iter_mut() {
+                    node.label = name.clone();
+                }
+                Ok(nodes)
+            }
+            Expr::Drop(inner) => self.expr(inner, depth, text).map(|_| vec![]),
+            Expr::Sequence(items) => {
+                let mut nodes = vec![];
+                for item in items {
+                    nodes.extend(self.expr(item, depth, text)?);
+                }
+                Ok(nodes)
+            }
+            Expr::Choice(alternatives) => {
+                let heights = alternatives.iter().map(|alternative| self.height(alternative)).collect::<Vec<_>>();
+                let budget = self.max_depth.saturating_sub(depth);
+                let lowest = heights.iter().flatten().copied().min().unwrap_or(0);
+                let fitting = (0..alternatives.len())
+                    .filter(|&i| heights[i].is_some_and(|height| height <= budget.max(lowest)))
+                    .collect::<Vec<_>>();
+                let index = fitting[self.rng.below(fitting.len())];
+                self.expr(&alternatives[index], depth, text)
+            }
+            Expr::Optional(inner) => match self.repeat(inner, depth, 0).min(1) {
+                0 => Ok(vec![]),
+                _ => self.expr(inner, depth, text),
+            },
+            Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => {
+                let min = matches!(expr, Expr::OneOrMore(_)) as usize;
+                let mut nodes = vec![];
+                for _ in 0..self.repeat(inner, depth, min) {
+                    nodes.extend(self.expr(inner, depth, text)?);
+                }
+                Ok(nodes)
+            }
+        }
+    }
+
+    /// appends the text of a token of the given kind, checked to lex back as that token alone
+    fn token(&mut self, kind: u32, text: &mut String) -> Result<String, GrammarError> {
+        let grammar = self.grammar;
+        let token = grammar.tokens.iter().find(|token| token.kind == kind).expect("Token kinds come from the grammar");
+        let value = match &token.pattern {
+            Pattern::Literal(lit) => Some(lit.clone()),
+            Pattern::Regex(_) => (0..self.attempts).find_map(|_| {
+                let mut value = String::new();
+                self.regex(&self.regexes[&kind].clone(), &mut value);
+                match self.tokenize(&value).as_deref() {
+                    Some([single]) if single.kind == kind && single.value == value => Some(value),
+                    _ => None,
+                }
+            }),
+        };
+        let value = value.ok_or_else(|| GrammarError::new(format!("Could not generate text for `{}` that the lexer reads back", token.name), token.span))?;
+        text.push_str(&value);
+        Ok(value)
+    }
+
+    fn tokenize(&mut self, text: &str) -> Option<Vec<TokenData>> {
+        self.lexer.begin(text);
+        self.lexer.try_all().ok()
+    }
+
+    fn regex(&mut self, hir: &Hir, out: &mut String) {
+        match hir.kind() {
+            HirKind::Empty | HirKind::Look(_) => {}
+            HirKind::Literal(literal) => out.push_str(&String::from_utf8_lossy(&literal.0)),
+            HirKind::Class(Class::Unicode(class)) => out.push(self.char(class)),
+            HirKind::Class(Class::Bytes(class)) => match class.ranges() {
+                [] => {}
+                ranges => {
+                    let range = ranges[self.rng.below(ranges.len())];
+                    let byte = range.start() + self.rng.below((range.end() - range.start()) as usize + 1) as u8;
+                    out.push(byte as char);
+                }
+            },
+            HirKind::Repetition(repetition) => {
+                let min = repetition.min as usize;
+                let max = repetition.max.map_or(min + self.max_repeat, |max| max as usize);
+                let count = min + self.rng.below(max - min + 1);
+                for _ in 0..count {
+                    self.regex(&repetition.sub, out);
+                }
+            }
+            HirKind::Capture(capture) => self.regex(&capture.sub, out),
+            HirKind::Concat(items) => items.iter().for_each(|item| self.regex(item, out)),
+            HirKind::Alternation(alternatives) => {
+                let index = self.rng.below(alternatives.len());
+                self.regex(&alternatives[index], out);
+            }
+        }
+    }
+
+    /// a character of the class, printable ASCII when the class has any
+    fn char(&mut self, class: &ClassUnicode) -> char {
+        let mut printable = ClassUnicode::new([ClassUnicodeRange::new(' ', '~')]);
+        printable.intersect(class);
+        let ranges = match printable.ranges().is_empty() {
+            true => class.ranges(),
+            false => printable.ranges(),
+        };
+        if ranges.is_empty() {
+            return '?';
+        }
+        let range = ranges[self.rng.below(ranges.len())];
+        let size = range.end() as u32 - range.start() as u32 + 1;
+        (0..8)
+            .find_map(|_| char::from_u32(range.start() as u32 + self.rng.below(size as usize) as u32))
+            .unwrap_or(range.start())
+    }
+}
+
+/// the tree `ParserState::pratt` builds from the same operators and operands
+fn fold(items: &mut std::iter::Peekable<std::vec::IntoIter<Item>>, kind: u32, min_power: u32) -> Node {
+    let mut lhs = match items.next() {
+        Some(Item::Prefix(op, value)) => {
+            let mut node = Node::new(kind, "Prefix", Value::String(value));
+            node.add_child(fold(items, kind, op.binding_power().1));
+            node
+        }
+        Some(Item::Operand(node)) => node,
+        _ => unreachable!("Operator chains start with an operand or a prefix operator"),
+    };
+    while let Some(Item::Infix(op, _)) = items.peek() {
+        let (left, right) = op.binding_power();
+        if left < min_power {
+            break;
+        }
+        let Some(Item::Infix(op, value)) = items.next() else { unreachable!() };
+        let mut node = match op.fixity {
+            Fixity::Postfix => Node::new(kind, "Postfix", Value::String(value)),
+            _ => Node::new(kind, "Binary", Value::String(value)),
+        };
+        node.add_child(lhs);
+        if op.fixity != Fixity::Postfix {
+            node.add_child(fold(items, kind, right));
+        }
+        lhs = node;
+    }
+    lhs
+}
+
+/// least nesting of rules needed to finish `expr`, `None` when it never finishes
+fn height(grammar: &Grammar, heights: &HashMap<&str, usize>, expr: &Expr) -> Option<usize> {
+    match expr {
+        Expr::Ref(name, _) if grammar.token(name).is_some() => Some(0),
+        Expr::Ref(name, _) => heights.get(name.as_str()).map(|height| height + 1),
+        Expr::Sequence(items) => items.iter().try_fold(0, |max, item| Some(max.max(height(grammar, heights, item)?))),
+        Expr::Choice(alternatives) => alternatives.iter().filter_map(|alternative| height(grammar, heights, alternative)).min(),
+        Expr::Optional(_) | Expr::ZeroOrMore(_) => Some(0),
+        Expr::OneOrMore(inner) | Expr::Capture(_, inner) | Expr::Drop(inner) => height(grammar, heights, inner),
+    }
+}
+
+fn heights(grammar: &Grammar) -> HashMap<&str, usize> {
+    let mut heights = HashMap::new();
+    loop {
+        let mut changed = false;
+        for rule in grammar.rules.iter() {
+            if let Some(new) = height(grammar, &heights, &rule.expr) {
+                if heights.get(rule.name.as_str()).is_none_or(|&old| new < old) {
+                    heights.insert(rule.name.as_str(), new);
+                    changed = true;
+                }
+            }
+        }
+        if !changed {
+            return heights;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rng() {
+        let mut a = Rng::new(7);
+        let mut b = Rng::new(7);
+        let picks = (0..16).map(|_| a.below(10)).collect::<Vec<_>>();
+        assert_eq!(picks, (0..16).map(|_| b.below(10)).collect::<Vec<_>>());
+        assert!(picks.iter().any(|&pick| pick != picks[0]));
+        assert_eq!(Rng::new(1).weighted(&[0, 5, 0]), 1);
+    }
+
+    #[test]
+    fn test_generate() {
+        let grammar = Grammar::parse(include_str!("../../assets/grammars/small_example_lang.ars")).unwrap();
+        let mut generator = Generator::new(&grammar, 42).unwrap();
+        for _ in 0..20 {
+            let sample = generator.generate().unwrap();
+            assert_eq!(grammar.interpret(&sample.text).unwrap(), sample.node);
+        }
+
+        // same seed, same samples
+        let first = Generator::new(&grammar, 3).unwrap().generate().unwrap();
+        let second = Generator::new(&grammar, 3).unwrap().generate().unwrap();
+        assert_eq!(first.text, second.text);
+    }
+
+    #[test]
+    fn test_generate_depth_and_weights() {
+        let grammar = Grammar::parse("
+            NUM: /[1-9][0-9]{0,2}/;
+            List: ~\"(\" Item* ~\")\";
+            @inline Item: value:NUM | List;
+        ").unwrap();
+        let mut generator = Generator::new(&grammar, 9).unwrap().with_max_depth(3).with_weight("Item", 1, 10);
+        let mut nested = false;
+        for _ in 0..20 {
+            let sample = generator.generate().unwrap();
+            assert!(sample.text.matches('(').count() <= 4, "{}", sample.text);
+            nested |= sample.text.starts_with("((");
+        }
+        assert!(nested);
+    }
+
+    #[test]
+    fn test_generate_pratt() {
+        let grammar = Grammar::parse("
+            NUM: /[0-9]/;
+            %left \"+\" \"-\";
+            %right \"^\";
+            %prefix \"-\";
+            %postfix \"!\";
+            @pratt Expr: NUM | ~\"(\" Expr ~\")\";
+        ").unwrap();
+        let mut generator = Generator::new(&grammar, 5).unwrap();
+        let mut operators = 0;
+        for _ in 0..20 {
+            let sample = generator.generate().unwrap();
+            operators += sample.text.matches(['+', '^', '!']).count();
+        }
+        assert!(operators > 0);
+    }
+}
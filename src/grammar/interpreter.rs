@@ -0,0 +1,369 @@
+use std::{cell::RefCell, collections::HashMap};
+
+use crate::{
+    ast::{Node, Value},
+    lexer::Lexer,
+    parser::{Fixity, Operator, ParseError, ParseResult, ParserState},
+    token::TokenData,
+};
+
+use super::{Expr, Grammar, GrammarError, RuleDef};
+
+enum Symbol<'g> {
+    Token(u32),
+    Rule(&'g RuleDef),
+}
+
+/// parses input by walking the rules of a grammar, no Rust code is needed per rule
+pub struct Interpreter<'g> {
+    grammar: &'g Grammar,
+    lexer: Lexer,
+    symbols: HashMap<&'g str, Symbol<'g>>,
+    /// token kinds each rule leaves out through `@drop`
+    drops: HashMap<&'g str, Vec<u32>>,
+    operators: Vec<Operator>,
+    /// furthest of the errors that optional parts gave up on, it tells what could have
+    /// continued a parse that stopped before the end of the input
+    furthest: RefCell<Option<ParseError>>,
+}
+
+/// builds the node of a rule from the nodes its expression matched
+///
+/// leaves whose kind is in `drop` are left out and the first leaf labeled `value` gives its
+/// value to the node instead of becoming a child, a child with children of its own is kept
+pub fn shape_node(kind: u32, label: &str, value: Option<&str>, drop: &[u32], children: Vec<Node>) -> Node {
+    let mut node = Node::new(kind, label, Value::None);
+    let mut value = value;
+    for child in children {
+        if child.children.is_empty() && drop.contains(&child.kind) {
+            continue;
+        }
+        if child.children.is_empty() && value == Some(child.label.as_str()) {
+            node.value = child.value;
+            value = None;
+            continue;
+        }
+        node.add_child(child);
+    }
+    node
+}
+
+/// node of an operand of a `@pratt` rule, a single matched node stands for itself
+pub fn operand_node(kind: u32, label: &str, value: Option<&str>, drop: &[u32], mut children: Vec<Node>) -> Node {
+    match children.len() {
+        1 => children.pop().unwrap(),
+        _ => shape_node(kind, label, value, drop, children),
+    }
+}
+
+impl<'g> Interpreter<'g> {
+    pub fn new(grammar: &'g Grammar) -> Result<Self, GrammarError> {
+        if grammar.rules.is_empty() {
+            return Err(GrammarError::new("Grammar has no rules".to_string(), Default::default()));
+        }
+        let mut symbols = HashMap::new();
+        for token in grammar.tokens.iter() {
+            symbols.insert(token.name.as_str(), Symbol::Token(token.kind));
+        }
+        for rule in grammar.rules.iter() {
+            symbols.insert(rule.name.as_str(), Symbol::Rule(rule));
+        }
+        let mut drops = HashMap::new();
+        for rule in grammar.rules.iter() {
+            let mut kinds = vec![];
+            for name in rule.attributes.drop.iter() {
+                match symbols.get(name.as_str()) {
+                    Some(Symbol::Token(kind)) => kinds.push(*kind),
+                    _ => return Err(GrammarError::new(format!("`@drop` of `{}` which is not a token", name), rule.span)),
+                }
+            }
+            drops.insert(rule.name.as_str(), kinds);
+
+            let mut error = None;
+            rule.expr.walk(&mut |expr| {
+                if let Expr::Ref(name, span) = expr {
+                    if error.is_none() && !symbols.contains_key(name.as_str()) {
+                        error = Some(GrammarError::new(format!("`{}` is not defined", name), *span));
+                    }
+                }
+            });
+            if let Some(error) = error {
+                return Err(error);
+            }
+        }
+        let lexer = grammar.lexer()?;
+        // an empty match would never move the lexer forward
+        if let Some(token) = grammar.tokens.iter().find(|token| token.matches_empty()) {
+            return Err(GrammarError::new(format!("Token `{}` can match an empty string", token.name), token.span));
+        }
+        Ok(Self { grammar, lexer, symbols, drops, operators: grammar.operators()?, furthest: RefCell::new(None) })
+    }
+
+    pub fn tokenize(&self, input: &str) -> Result<Vec<TokenData>, ParseError> {
+        let mut lexer = self.lexer.clone();
+        lexer.begin(input);
+        lexer.try_all().map_err(|location| ParseError::InvalidCharacter { location })
+    }
+
+    /// parses the whole input with the first rule of the grammar
+    pub fn parse(&self, input: &str) -> ParseResult {
+        let rule = self.grammar.start_rule().expect("`Interpreter::new` checks that there are rules");
+        self.parse_rule(&rule.name, input)
+    }
+
+    /// parses the whole input with the given rule
+    pub fn parse_rule(&self, rule: &str, input: &str) -> ParseResult {
+        self.parse_tokens(rule, self.tokenize(input)?)
+    }
+
+    /// parses all of the already lexed `tokens` with the given rule
+    pub fn parse_tokens(&self, rule: &str, tokens: Vec<TokenData>) -> ParseResult {
+        let mut state = ParserState::new(tokens, None).with_memo();
+        self.furthest.replace(None);
+        let node = self.parse_with(rule, &mut state)?;
+        let Some(token) = state.lookahead(0) else {
+            return Ok(node);
+        };
+        let trailing = ParseError::Unexpected { expected: vec![], found: token.clone() };
+        match self.furthest.take().map(|error| error.merge(trailing.clone())) {
+            Some(error) if !error.expected().is_empty() => Err(error),
+            _ => Err(trailing),
+        }
+    }
+
+    /// runs a rule at the current position of `state`, leaving the rest of the tokens untouched
+    pub fn parse_with(&self, rule: &str, state: &mut ParserState) -> ParseResult {
+        match self.symbols.get(rule) {
+            Some(Symbol::Rule(rule)) => self.rule(rule, state),
+            _ => Err(ParseError::UnknownRule { name: rule.to_string() }),
+        }
+    }
+
+    /// keeps `error` if it got further than the ones seen before
+    fn note(&self, error: &ParseError) {
+        let mut furthest = self.furthest.borrow_mut();
+        *furthest = Some(match furthest.take() {
+            Some(previous) => previous.merge(error.clone()),
+            None => error.clone(),
+        });
+    }
+
+    fn rule(&self, rule: &RuleDef, state: &mut ParserState) -> ParseResult {
+        let drop = &self.drops[rule.name.as_str()];
+        let value = rule.attributes.value.as_deref();
+        state.rule(rule.kind, |state| {
+            if rule.attributes.pratt {
+                let node = state.pratt(&self.operators, rule.node_kind(), &|state| {
+                    let children = self.expr(&rule.expr, state)?;
+                    Ok(operand_node(rule.node_kind(), rule.node_label(), value, drop, children))
+                })?;
+                if let Some(token) = state.lookahead(0) {
+                    let expected = self.operators.iter().filter(|op| op.fixity != Fixity::Prefix).map(|op| op.kind).collect();
+                    self.note(&ParseError::Unexpected { expected, found: token.clone() });
+                }
+                return Ok(node);
+            }
+            let children = self.expr(&rule.expr, state)?;
+            Ok(shape_node(rule.node_kind(), rule.node_label(), value, drop, children))
+        })
+    }
+
+    fn expr(&self, expr: &Expr, state: &mut ParserState) -> ParseResult<Vec<Node>> {
+        match expr {
+            Expr::Ref(name, _) => match &self.symbols[name.as_str()] {
+                Symbol::Token(kind) => {
+                    let token = state.expect_any(&[*kind])?;
+                    Ok(vec![Node::from_token(&token)])
+                }
+                Symbol::Rule(rule) if rule.attributes.inline => Ok(self.rule(rule, state)?.into_children()),
+                Symbol::Rule(rule) => Ok(vec![self.rule(rule, state)?]),
+            },
+            Expr::Capture(name, inner) => {
+                let mut nodes = self.expr(inner, state)?;
+                for node in nodes.iter_mut() {
+                    node.label = name.clone();
+                }
+                Ok(nodes)
+            }
+            Expr::Drop(inner) => self.expr(inner, state).map(|_| vec![]),
+            Expr::Sequence(items) => {
+                let mut nodes = vec![];
+                for item in items {
+                    nodes.extend(self.expr(item, state)?);
+                }
+                Ok(nodes)
+            }
+            Expr::Choice(alternatives) => {
+                let mut error: Option<ParseError> = None;
+                for alternative in alternatives {
+                    match state.attempt(|state| self.expr(alternative, state)) {
+                        Ok(nodes) => return Ok(nodes),
+                        Err(e) => {
+                            self.note(&e);
+                            error = Some(match error {
+                                Some(previous) => previous.merge(e),
+                                None => e,
+                            })
+                        }
+                    }
+                }
+                Err(error.unwrap())
+            }
+            Expr::Optional(inner) => Ok(state.attempt(|state| self.expr(inner, state)).inspect_err(|e| self.note(e)).unwrap_or_default()),
+            Expr::ZeroOrMore(inner) => Ok(state.many(|state| self.expr(inner, state).inspect_err(|e| self.note(e)))),
+            Expr::OneOrMore(inner) => {
+                let mut nodes = self.expr(inner, state)?;
+                nodes.extend(state.many(|state| self.expr(inner, state).inspect_err(|e| self.note(e))));
+                Ok(nodes)
+            }
+        }
+    }
+}
+
+impl Grammar {
+    /// parses `input` with the first rule of the grammar
+    pub fn interpret(&self, input: &str) -> Result<Node, Box<dyn std::error::Error>> {
+        Ok(Interpreter::new(self)?.parse(input)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    #[test]
+    fn test_interpret_example() {
+        let grammar = Grammar::parse(include_str!("../../assets/grammars/small_example_lang.ars")).unwrap();
+        let node = grammar.interpret("let x = 10.5 ").unwrap();
+        assert_eq!(node.label, "VariableDefinition");
+        let labels = node.children.iter().map(|child| child.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["LET", "WS", "IDENTITY", "WS", "EQUAL", "WS", "NUMBER", "WS"]);
+        assert_eq!(node.children[6].value.as_str(), Some("10.5"));
+        assert_eq!(node.children[6].span, Span::new(8, 12, 1, 9));
+        assert_eq!(node.span, Span::new(0, 13, 1, 1));
+
+        let node = grammar.interpret("let y=\"hi\" ").unwrap();
+        assert_eq!(node.children.len(), 6);
+        assert_eq!(node.children[4].label, "STRING");
+
+        assert!(grammar.interpret("let = 1 ").is_err());
+    }
+
+    #[test]
+    fn test_interpret_left_recursion() {
+        let grammar = Grammar::parse("
+            NUM: /\\d+/;
+            Expr: Expr \"+\" Term | Term;
+            Term: Term \"*\" NUM | NUM;
+        ").unwrap();
+        let node = grammar.interpret("1+2*3+4").unwrap();
+        // (1 + (2 * 3)) + 4
+        assert_eq!(node.children.len(), 3);
+        assert_eq!(node.children[2].label, "Term");
+        let left = &node.children[0];
+        assert_eq!(left.children[2].children.len(), 3);
+        assert_eq!(left.children[2].children[0].children[0].value.as_str(), Some("2"));
+    }
+
+    #[test]
+    fn test_node_shaping() {
+        let grammar = Grammar::parse("
+            WS: /\\s+/;
+            NUMBER: /\\d+/;
+            IDENTITY: /[a-z]+/;
+            @label(\"Let\") @value(name) @drop(WS)
+            VariableDefinition: ~\"let\" WS name:IDENTITY WS? \"=\" WS? Value WS?;
+            @inline Value: value:NUMBER | value:IDENTITY;
+        ").unwrap();
+        let node = grammar.interpret("let x = 10").unwrap();
+        assert_eq!(node.label, "Let");
+        assert_eq!(node.kind, grammar.rule("VariableDefinition").unwrap().kind);
+        assert_eq!(node.value.as_str(), Some("x"));
+        let labels = node.children.iter().map(|child| child.label.as_str()).collect::<Vec<_>>();
+        assert_eq!(labels, vec!["\"=\"", "value"]);
+        assert_eq!(node.children[1].value.as_str(), Some("10"));
+    }
+
+    #[test]
+    fn test_value_of_rule_capture() {
+        let grammar = Grammar::parse("
+            WS: /\\s+/;
+            NUMBER: /\\d+/;
+            @value(pair) Entry: pair:Pair;
+            @drop(WS) Pair: NUMBER WS NUMBER;
+        ").unwrap();
+        // taking the value would lose both numbers, so the capture stays a child
+        let node = grammar.interpret("1 2").unwrap();
+        assert_eq!(node.value, Value::None);
+        assert_eq!(node.children[0].label, "pair");
+        assert_eq!(node.children[0].children.len(), 2);
+    }
+
+    #[test]
+    fn test_pratt_rule() {
+        let grammar = Grammar::parse("
+            NUM: /\\d+/;
+            %left \"+\" \"-\";
+            %left \"*\";
+            %right \"^\";
+            %prefix \"-\";
+            @pratt Expr: NUM | ~\"(\" Expr ~\")\";
+        ").unwrap();
+        let node = grammar.interpret("-1+2*(3-4)^2^3").unwrap();
+        assert_eq!(node.label, "Binary");
+        assert_eq!(node.value.as_str(), Some("+"));
+        assert_eq!(node.kind, grammar.rule("Expr").unwrap().kind);
+        assert_eq!(node.children[0].label, "Prefix");
+        let product = &node.children[1];
+        assert_eq!(product.value.as_str(), Some("*"));
+        let power = &product.children[1];
+        assert_eq!(power.value.as_str(), Some("^"));
+        assert_eq!(power.children[0].value.as_str(), Some("-"));
+        assert_eq!(power.children[1].value.as_str(), Some("^"));
+        assert!(grammar.interpret("1+").is_err());
+    }
+
+    #[test]
+    fn test_undefined_reference() {
+        let grammar = Grammar::parse("A: \"a\";\nRule: A B;").unwrap();
+        let error = Interpreter::new(&grammar).err().unwrap();
+        assert_eq!(error.message, "`B` is not defined");
+        assert_eq!((error.span.line, error.span.column), (2, 9));
+    }
+
+    #[test]
+    fn test_no_rules() {
+        let grammar = Grammar::parse("NUM: /\\d+/;").unwrap();
+        assert_eq!(grammar.interpret("1").unwrap_err().to_string(), "Grammar has no rules at 0:0");
+    }
+
+    #[test]
+    fn test_empty_token() {
+        let grammar = Grammar::parse("A: /a*/;\nRule: A;").unwrap();
+        let error = Interpreter::new(&grammar).err().unwrap();
+        assert_eq!(error.message, "Token `A` can match an empty string");
+        assert_eq!(error.span.line, 1);
+    }
+
+    #[test]
+    fn test_rule_errors() {
+        let grammar = Grammar::parse("
+            NUM: /\\d+/;
+            COMMA: \",\";
+            SEMI: \";\";
+            List: NUM (COMMA NUM)*;
+        ").unwrap();
+        let interpreter = Interpreter::new(&grammar).unwrap();
+        let error = interpreter.parse_rule("Missing", "1").unwrap_err();
+        assert_eq!(error, ParseError::UnknownRule { name: "Missing".to_string() });
+
+        let error = interpreter.parse("1,2 $").unwrap_err();
+        assert_eq!(error, ParseError::InvalidCharacter { location: (1, 4) });
+
+        let error = interpreter.parse("1,2;").unwrap_err();
+        let comma = grammar.token("COMMA").unwrap().kind;
+        assert_eq!(error.expected(), &[comma]);
+        assert!(matches!(error, ParseError::Unexpected { found, .. } if found.value == ";"));
+    }
+}
@@ -0,0 +1,343 @@
+use std::{collections::{HashMap, HashSet}, fmt::Display};
+
+use crate::span::Span;
+
+use super::{loader, Expr, Grammar, GrammarError, Pattern};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub span: Span,
+}
+
+impl Diagnostic {
+    pub fn error(message: impl Into<String>, span: Span) -> Self {
+        Self { severity: Severity::Error, message: message.into(), span }
+    }
+
+    pub fn warning(message: impl Into<String>, span: Span) -> Self {
+        Self { severity: Severity::Warning, message: message.into(), span }
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{}: {}: {}", self.span, severity, self.message)
+    }
+}
+
+/// loads the grammar text and checks it, syntax errors are returned as a single diagnostic
+pub fn check_source(source: &str) -> Vec<Diagnostic> {
+    match loader::load_unchecked(source) {
+        Ok(grammar) => check(&grammar),
+        Err(GrammarError { message, span, .. }) => vec![Diagnostic::error(message, span)],
+    }
+}
+
+/// reports mistakes in a grammar, sorted by their position in the file
+pub fn check(grammar: &Grammar) -> Vec<Diagnostic> {
+    let mut diagnostics = vec![];
+    duplicates(grammar, &mut diagnostics);
+    references(grammar, &mut diagnostics);
+    usage(grammar, &mut diagnostics);
+    tokens(grammar, &mut diagnostics);
+    empty_loops(grammar, &mut diagnostics);
+    left_recursion(grammar, &mut diagnostics);
+    diagnostics.sort_by_key(|diagnostic| (diagnostic.span.start, diagnostic.span.end));
+    diagnostics
+}
+
+fn duplicates(grammar: &Grammar, diagnostics: &mut Vec<Diagnostic>) {
+    let mut seen: HashMap<&str, Span> = HashMap::new();
+    let definitions = grammar.tokens.iter().map(|token| (token.name.as_str(), token.span))
+        .chain(grammar.rules.iter().map(|rule| (rule.name.as_str(), rule.span)));
+    for (name, span) in definitions {
+        match seen.get(name) {
+            Some(previous) => diagnostics.push(Diagnostic::error(format!("`{}` is already defined at {}", name, previous), span)),
+            None => {
+                seen.insert(name, span);
+            }
+        }
+    }
+}
+
+fn references(grammar: &Grammar, diagnostics: &mut Vec<Diagnostic>) {
+    for precedence in grammar.precedence.iter() {
+        for name in precedence.operators.iter().filter(|name| grammar.token(name).is_none()) {
+            diagnostics.push(Diagnostic::error(format!("Operator `{}` is not a token", name), precedence.span));
+        }
+    }
+    for rule in grammar.rules.iter() {
+        if rule.attributes.pratt && grammar.precedence.is_empty() {
+            diagnostics.push(Diagnostic::warning(format!("`@pratt` rule `{}` has no operators to use", rule.name), rule.span));
+        }
+        for name in rule.attributes.drop.iter() {
+            if grammar.token(name).is_none() {
+                diagnostics.push(Diagnostic::error(format!("`@drop` of `{}` which is not a token", name), rule.span));
+            }
+        }
+        if let Some(value) = &rule.attributes.value {
+            let mut captured = false;
+            rule.expr.walk(&mut |expr| captured |= matches!(expr, Expr::Capture(name, _) if name == value));
+            if !captured {
+                diagnostics.push(Diagnostic::error(format!("`@value` uses `{}` which is not captured in `{}`", value, rule.name), rule.span));
+            }
+        }
+        rule.expr.walk(&mut |expr| {
+            if let Expr::Ref(name, span) = expr {
+                if grammar.token(name).is_none() && grammar.rule(name).is_none() {
+                    diagnostics.push(Diagnostic::error(format!("`{}` is not defined", name), *span));
+                }
+            }
+        });
+    }
+}
+
+/// rules referenced by `expr`
+fn referenced_rules<'g>(grammar: &'g Grammar, expr: &'g Expr) -> Vec<&'g str> {
+    let mut names = vec![];
+    expr.walk(&mut |expr| {
+        if let Expr::Ref(name, _) = expr {
+            if grammar.rule(name).is_some() {
+                names.push(name.as_str());
+            }
+        }
+    });
+    names
+}
+
+fn usage(grammar: &Grammar, diagnostics: &mut Vec<Diagnostic>) {
+    let Some(start) = grammar.start_rule() else {
+        return;
+    };
+    let referenced = grammar.rules.iter()
+        .flat_map(|rule| referenced_rules(grammar, &rule.expr).into_iter().filter(move |name| *name != rule.name))
+        .collect::<HashSet<_>>();
+
+    let mut reachable = HashSet::from([start.name.as_str()]);
+    let mut pending = vec![start];
+    while let Some(rule) = pending.pop() {
+        for name in referenced_rules(grammar, &rule.expr) {
+            if reachable.insert(name) {
+                pending.push(grammar.rule(name).unwrap());
+            }
+        }
+    }
+
+    for rule in grammar.rules.iter().filter(|rule| !std::ptr::eq(*rule, start)) {
+        if !referenced.contains(rule.name.as_str()) {
+            diagnostics.push(Diagnostic::warning(format!("Rule `{}` is never used", rule.name), rule.span));
+        } else if !reachable.contains(rule.name.as_str()) {
+            diagnostics.push(Diagnostic::warning(format!("Rule `{}` cannot be reached from `{}`", rule.name, start.name), rule.span));
+        }
+    }
+}
+
+fn tokens(grammar: &Grammar, diagnostics: &mut Vec<Diagnostic>) {
+    let mut regexes = vec![];
+    for token in grammar.tokens.iter() {
+        match &token.pattern {
+            Pattern::Regex(pattern) => match regex::Regex::new(pattern) {
+                Ok(regex) => {
+                    if token.matches_empty() {
+                        diagnostics.push(Diagnostic::error(format!("Token `{}` can match an empty string", token.name), token.span));
+                    }
+                    regexes.push((token, Some(regex)));
+                }
+                Err(e) => {
+                    diagnostics.push(Diagnostic::error(format!("Invalid regex for `{}`: {}", token.name, e), token.span));
+                    regexes.push((token, None));
+                }
+            },
+            Pattern::Literal(lit) => {
+                if lit.is_empty() {
+                    diagnostics.push(Diagnostic::error(format!("Token `{}` is an empty literal", token.name), token.span));
+                }
+                // the lexer picks the first token that matches, not the longest
+                let by_regex = regexes.iter().find(|(_, earlier)| match earlier {
+                    Some(regex) => regex.find(lit).is_some_and(|m| m.start() == 0 && m.end() > 0),
+                    None => false,
+                }).map(|(earlier, _)| *earlier);
+                let by_literal = || grammar.tokens.iter().take_while(|earlier| !std::ptr::eq(*earlier, token)).find(|earlier| {
+                    matches!(&earlier.pattern, Pattern::Literal(earlier) if !earlier.is_empty() && lit.starts_with(earlier.as_str()))
+                });
+                let shadow = by_regex.or_else(by_literal);
+                if let Some(earlier) = shadow {
+                    diagnostics.push(Diagnostic::warning(
+                        format!("Token `{}` is shadowed by `{}` defined at {}", token.name, earlier.name, earlier.span),
+                        token.span,
+                    ));
+                }
+            }
+        }
+    }
+}
+
+fn empty_loops(grammar: &Grammar, diagnostics: &mut Vec<Diagnostic>) {
+    let nullable = grammar.nullable_rules();
+    for rule in grammar.rules.iter() {
+        rule.expr.walk(&mut |expr| {
+            if let Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) = expr {
+                if inner.is_nullable(&nullable) {
+                    let span = inner.first_span().unwrap_or(rule.span);
+                    diagnostics.push(Diagnostic::error(format!("Repetition in `{}` can match an empty string", rule.name), span));
+                }
+            }
+        });
+    }
+}
+
+/// rules that can be called at the very start of `expr`, before any token is consumed
+fn left_corners<'g>(grammar: &'g Grammar, expr: &'g Expr, nullable: &HashSet<&str>, corners: &mut Vec<&'g str>) {
+    match expr {
+        Expr::Ref(name, _) => {
+            if grammar.rule(name).is_some() {
+                corners.push(name);
+            }
+        }
+        Expr::Sequence(items) => {
+            for item in items {
+                left_corners(grammar, item, nullable, corners);
+                if !item.is_nullable(nullable) {
+                    break;
+                }
+            }
+        }
+        Expr::Choice(alternatives) => alternatives.iter().for_each(|alternative| left_corners(grammar, alternative, nullable, corners)),
+        Expr::Optional(inner) | Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => left_corners(grammar, inner, nullable, corners),
+        Expr::Capture(_, inner) | Expr::Drop(inner) => left_corners(grammar, inner, nullable, corners),
+    }
+}
+
+/// finds the left recursive cycles of the grammar, each cycle is reported once
+pub fn left_recursive_cycles(grammar: &Grammar) -> Vec<Vec<&str>> {
+    let nullable = grammar.nullable_rules();
+    let mut graph: HashMap<&str, Vec<&str>> = HashMap::new();
+    for rule in grammar.rules.iter() {
+        let mut corners = vec![];
+        left_corners(grammar, &rule.expr, &nullable, &mut corners);
+        graph.insert(rule.name.as_str(), corners);
+    }
+
+    let mut cycles = vec![];
+    let mut reported = HashSet::new();
+    for rule in grammar.rules.iter() {
+        let name = rule.name.as_str();
+        if reported.contains(name) {
+            continue;
+        }
+        // shortest path from the rule back to itself
+        let mut previous: HashMap<&str, &str> = HashMap::new();
+        let mut queue = std::collections::VecDeque::from([name]);
+        let mut found = false;
+        while let Some(current) = queue.pop_front() {
+            for next in graph[current].iter() {
+                if *next == name {
+                    previous.insert(name, current);
+                    found = true;
+                    break;
+                }
+                if !previous.contains_key(next) {
+                    previous.insert(next, current);
+                    queue.push_back(next);
+                }
+            }
+            if found {
+                break;
+            }
+        }
+        if !found {
+            continue;
+        }
+        let mut cycle = vec![name];
+        let mut current = previous[name];
+        while current != name {
+            cycle.push(current);
+            current = previous[current];
+        }
+        cycle.push(name);
+        cycle.reverse();
+        reported.extend(cycle.iter().copied());
+        cycles.push(cycle);
+    }
+    cycles
+}
+
+fn left_recursion(grammar: &Grammar, diagnostics: &mut Vec<Diagnostic>) {
+    for cycle in left_recursive_cycles(grammar) {
+        let rule = grammar.rule(cycle[0]).unwrap();
+        let message = if cycle.len() == 2 {
+            format!("Rule `{}` is left recursive", rule.name)
+        } else {
+            format!("Rule `{}` is indirectly left recursive through {}", rule.name, cycle.join(" -> "))
+        };
+        diagnostics.push(Diagnostic::warning(message, rule.span));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(source: &str) -> Vec<String> {
+        check_source(source).iter().map(|diagnostic| diagnostic.to_string()).collect()
+    }
+
+    #[test]
+    fn test_clean_grammar() {
+        assert!(check_source(include_str!("../../assets/grammars/small_example_lang.ars")).is_empty());
+    }
+
+    #[test]
+    fn test_definitions() {
+        let messages = messages("A: \"a\";\nStart: A B;\nA: \"b\";\nUnused: A;\nIsland: Other;\nOther: A Island;");
+        assert_eq!(messages, vec![
+            "2:10: error: `B` is not defined",
+            "3:1: error: `A` is already defined at 1:1",
+            "4:1: warning: Rule `Unused` is never used",
+            "5:1: warning: Rule `Island` cannot be reached from `Start`",
+            "6:1: warning: Rule `Other` cannot be reached from `Start`",
+        ]);
+    }
+
+    #[test]
+    fn test_attributes() {
+        let messages = messages("A: \"a\";\n@value(x) @drop(B, A)\nStart: y:A;\n%left A Start;");
+        assert_eq!(messages, vec![
+            "3:1: error: `@drop` of `B` which is not a token",
+            "3:1: error: `@value` uses `x` which is not captured in `Start`",
+            "4:1: error: Operator `Start` is not a token",
+        ]);
+    }
+
+    #[test]
+    fn test_tokens() {
+        let messages = messages("IDENT: /[a-z]+/;\nLET: \"let\";\nEQ: \"=\";\nEQEQ: \"==\";\nEMPTY: /a*/;\nStart: IDENT LET EQ EQEQ EMPTY;");
+        assert_eq!(messages, vec![
+            "2:1: warning: Token `LET` is shadowed by `IDENT` defined at 1:1",
+            "4:1: warning: Token `EQEQ` is shadowed by `EQ` defined at 3:1",
+            "5:1: error: Token `EMPTY` can match an empty string",
+        ]);
+    }
+
+    #[test]
+    fn test_rules() {
+        let messages = messages("A: \"a\";\nExpr: Expr A | Term;\nTerm: Loop Expr | A;\nLoop: (A?)*;");
+        assert_eq!(messages, vec![
+            "2:1: warning: Rule `Expr` is left recursive",
+            "3:1: warning: Rule `Term` is indirectly left recursive through Term -> Expr -> Term",
+            "4:8: error: Repetition in `Loop` can match an empty string",
+        ]);
+    }
+}
@@ -0,0 +1,797 @@
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    lexer::Lexer,
+    parser::{Fixity, ParseError, ParserState},
+    span::Span,
+    token::{Token, TokenData},
+};
+
+use super::{Attributes, Expr, Grammar, GrammarError, Pattern, Precedence, RuleDef, TokenDef};
+
+const WHITESPACE: u32 = 0;
+const COMMENT: u32 = 1;
+const REGEX: u32 = 2;
+const STRING: u32 = 3;
+const IDENT: u32 = 4;
+const COLON: u32 = 5;
+const SEMICOLON: u32 = 6;
+const PIPE: u32 = 7;
+const LPAREN: u32 = 8;
+const RPAREN: u32 = 9;
+const QUESTION: u32 = 10;
+const STAR: u32 = 11;
+const PLUS: u32 = 12;
+const UNKNOWN: u32 = 13;
+const AT: u32 = 14;
+const TILDE: u32 = 15;
+const NUMBER: u32 = 16;
+const COMMA: u32 = 17;
+const PERCENT: u32 = 18;
+const DOT: u32 = 19;
+const EXTEND: u32 = 20;
+
+fn lexer() -> Lexer {
+    Lexer::new(vec![
+        Token::new_regex_from_str("whitespace", WHITESPACE, "\\s+"),
+        Token::new_regex_from_str("comment", COMMENT, "//[^\\n]*"),
+        Token::new_regex_from_str("regex", REGEX, "/(\\\\.|[^/\\\\\\n])+/"),
+        Token::new_regex_from_str("string", STRING, "\"(\\\\.|[^\"\\\\])*\""),
+        Token::new_regex_from_str("ident", IDENT, "[a-zA-Z_][a-zA-Z0-9_]*"),
+        Token::new_lit("colon", COLON, ":"),
+        Token::new_lit("semicolon", SEMICOLON, ";"),
+        Token::new_lit("extend", EXTEND, "|="),
+        Token::new_lit("pipe", PIPE, "|"),
+        Token::new_lit("lparen", LPAREN, "("),
+        Token::new_lit("rparen", RPAREN, ")"),
+        Token::new_lit("question", QUESTION, "?"),
+        Token::new_lit("star", STAR, "*"),
+        Token::new_lit("plus", PLUS, "+"),
+        Token::new_lit("at", AT, "@"),
+        Token::new_lit("tilde", TILDE, "~"),
+        Token::new_regex_from_str("number", NUMBER, "\\d+"),
+        Token::new_lit("comma", COMMA, ","),
+        Token::new_lit("percent", PERCENT, "%"),
+        Token::new_lit("dot", DOT, "."),
+        Token::new_regex_from_str("unknown", UNKNOWN, "(?s)."),
+    ])
+}
+
+fn describe(kind: u32) -> &'static str {
+    match kind {
+        REGEX => "regex",
+        STRING => "string",
+        IDENT => "name",
+        COLON => "`:`",
+        SEMICOLON => "`;`",
+        PIPE => "`|`",
+        LPAREN => "`(`",
+        RPAREN => "`)`",
+        QUESTION => "`?`",
+        STAR => "`*`",
+        PLUS => "`+`",
+        AT => "`@`",
+        TILDE => "`~`",
+        NUMBER => "number",
+        COMMA => "`,`",
+        PERCENT => "`%`",
+        DOT => "`.`",
+        EXTEND => "`|=`",
+        _ => "character",
+    }
+}
+
+fn to_error(error: ParseError) -> GrammarError {
+    let expected = error.expected().iter().map(|kind| describe(*kind)).collect::<Vec<_>>().join(" or ");
+    match error {
+        ParseError::Unexpected { found, .. } => GrammarError::new(
+            format!("Expected {} but found `{}`", expected, found.value),
+            Span::from_token(&found),
+        ),
+        ParseError::EndOfInput { .. } => GrammarError::new(format!("Expected {} but reached end of file", expected), Span::default()),
+        error => GrammarError::new(error.to_string(), Span::default()),
+    }
+}
+
+/// whether a definition name denotes a token, only the last part of `namespace.NAME` counts
+pub fn is_token_name(name: &str) -> bool {
+    let name = name.rsplit('.').next().unwrap();
+    name.chars().any(|c| c.is_ascii_uppercase()) && !name.chars().any(|c| c.is_ascii_lowercase())
+}
+
+/// resolves the escapes of a `"..."` literal
+pub fn unescape_literal(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => result.push('\n'),
+            Some('t') => result.push('\t'),
+            Some('r') => result.push('\r'),
+            Some('0') => result.push('\0'),
+            Some(other) => result.push(other),
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// writes text as the body of a `"..."` literal
+pub fn escape_literal(text: &str) -> String {
+    let mut result = String::new();
+    for c in text.chars() {
+        match c {
+            '"' => result.push_str("\\\""),
+            '\\' => result.push_str("\\\\"),
+            '\n' => result.push_str("\\n"),
+            '\t' => result.push_str("\\t"),
+            '\r' => result.push_str("\\r"),
+            '\0' => result.push_str("\\0"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// writes a regex as the body of a `/.../` pattern, the reverse of `unescape_regex`
+pub fn escape_regex(regex: &str) -> String {
+    let mut result = String::new();
+    let mut chars = regex.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\\') => result.push_str("\\\\\\\\"),
+                Some(other) => {
+                    result.push('\\');
+                    result.push(other);
+                }
+                None => result.push('\\'),
+            },
+            '/' => result.push_str("\\/"),
+            '\n' => result.push_str("\\n"),
+            c => result.push(c),
+        }
+    }
+    result
+}
+
+/// turns the body of a `/.../` pattern into a regex, `\\` and `\/` are collapsed
+/// and unknown escapes are passed to the regex as they are
+pub fn unescape_regex(text: &str) -> String {
+    let mut result = String::new();
+    let mut chars = text.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            result.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('/') => result.push('/'),
+            Some('0') => result.push_str("\\x00"),
+            Some('\\') => result.push('\\'),
+            Some(other) => {
+                result.push('\\');
+                result.push(other);
+            }
+            None => result.push('\\'),
+        }
+    }
+    result
+}
+
+/// files being loaded, used to resolve imports and to find cycles
+#[derive(Debug, Default)]
+struct Sources {
+    /// every file seen so far, the index is the `source` of its spans
+    paths: Vec<PathBuf>,
+    /// the chain of imports currently being loaded
+    stack: Vec<PathBuf>,
+}
+
+struct Loader<'s> {
+    state: ParserState,
+    grammar: Grammar,
+    /// literals written inside the rules of this file, see `resolve_literals`
+    anonymous: Vec<TokenDef>,
+    allow_duplicates: bool,
+    /// names defined in this file, imported ones can be overridden once
+    defined: Vec<(String, Span)>,
+    /// directory imports are relative to
+    base: PathBuf,
+    source: usize,
+    sources: &'s mut Sources,
+}
+
+pub fn load(source: &str) -> Result<Grammar, GrammarError> {
+    load_with(source, false)
+}
+
+/// loads a grammar that may define the same name more than once, used by the lint pass
+pub fn load_unchecked(source: &str) -> Result<Grammar, GrammarError> {
+    load_with(source, true)
+}
+
+fn load_with(source: &str, allow_duplicates: bool) -> Result<Grammar, GrammarError> {
+    let mut sources = Sources { paths: vec![PathBuf::new()], stack: vec![] };
+    let mut grammar = load_source(source, Path::new("."), 0, &mut sources, allow_duplicates)?;
+    grammar.sources = sources.paths;
+    resolve_literals(&mut grammar);
+    grammar.assign_kinds();
+    Ok(grammar)
+}
+
+/// loads a grammar file together with everything it imports
+pub fn load_file(path: &Path) -> Result<Grammar, GrammarError> {
+    let mut sources = Sources::default();
+    let mut grammar = load_import(path, &mut sources, false)?;
+    grammar.sources = sources.paths;
+    resolve_literals(&mut grammar);
+    grammar.assign_kinds();
+    Ok(grammar)
+}
+
+fn load_import(path: &Path, sources: &mut Sources, allow_duplicates: bool) -> Result<Grammar, GrammarError> {
+    let canonical = path.canonicalize().unwrap_or_else(|_| path.to_path_buf());
+    if let Some(position) = sources.stack.iter().position(|file| *file == canonical) {
+        let cycle = sources.stack[position..].iter().chain(std::iter::once(&canonical))
+            .map(|file| file.file_name().unwrap_or_default().to_string_lossy())
+            .collect::<Vec<_>>();
+        return Err(GrammarError::new(format!("Import cycle {}", cycle.join(" -> ")), Span::default()));
+    }
+    let text = std::fs::read_to_string(path).map_err(|e| GrammarError::new(format!("Cannot read {}: {}", path.display(), e), Span::default()))?;
+
+    let id = sources.paths.len();
+    sources.paths.push(path.to_path_buf());
+    sources.stack.push(canonical);
+    let base = path.parent().unwrap_or(Path::new(".")).to_path_buf();
+    let grammar = load_source(&text, &base, id, sources, allow_duplicates).map_err(|mut error| {
+        if error.file.is_none() {
+            error.file = Some(path.to_path_buf());
+            error.span.source = id;
+        }
+        error
+    })?;
+    sources.stack.pop();
+    Ok(grammar)
+}
+
+fn load_source(source: &str, base: &Path, id: usize, sources: &mut Sources, allow_duplicates: bool) -> Result<Grammar, GrammarError> {
+    let mut lexer = lexer();
+    lexer.begin(source);
+    let tokens = lexer.all();
+    if let Some(token) = tokens.iter().find(|token| token.kind == UNKNOWN) {
+        return Err(GrammarError::new(format!("Unexpected character `{}`", token.value), Span::from_token(token)));
+    }
+    let mut loader = Loader {
+        state: ParserState::new(tokens, Some(vec![WHITESPACE, COMMENT])),
+        grammar: Grammar::default(),
+        anonymous: vec![],
+        allow_duplicates,
+        defined: vec![],
+        base: base.to_path_buf(),
+        source: id,
+        sources,
+    };
+    loader.definitions()?;
+
+    let mut grammar = loader.grammar;
+    grammar.tokens.append(&mut loader.anonymous);
+    Ok(grammar)
+}
+
+/// once every file is merged, points literals written inside rules at the named token with the
+/// same pattern wherever it is defined, and puts the others first, longest first, so that the
+/// lexer tries `"=="` before `"="`
+fn resolve_literals(grammar: &mut Grammar) {
+    let (anonymous, named): (Vec<_>, Vec<_>) = std::mem::take(&mut grammar.tokens).into_iter().partition(|token| token.name.starts_with('"'));
+    let mut renames = HashMap::new();
+    let mut kept: Vec<TokenDef> = vec![];
+    for token in anonymous {
+        match named.iter().chain(kept.iter()).find(|def| def.pattern == token.pattern) {
+            Some(def) => {
+                renames.insert(token.name, def.name.clone());
+            }
+            None => kept.push(token),
+        }
+    }
+    for rule in grammar.rules.iter_mut() {
+        rename_refs(&mut rule.expr, &renames);
+    }
+    for name in grammar.precedence.iter_mut().flat_map(|precedence| precedence.operators.iter_mut()) {
+        if let Some(renamed) = renames.get(name.as_str()) {
+            *name = renamed.clone();
+        }
+    }
+    kept.sort_by_key(|token| match &token.pattern {
+        Pattern::Literal(lit) => std::cmp::Reverse(lit.len()),
+        Pattern::Regex(_) => std::cmp::Reverse(0),
+    });
+    kept.extend(named);
+    grammar.tokens = kept;
+}
+
+fn rename_refs(expr: &mut Expr, renames: &HashMap<String, String>) {
+    match expr {
+        Expr::Ref(name, _) => {
+            if let Some(renamed) = renames.get(name.as_str()) {
+                *name = renamed.clone();
+            }
+        }
+        Expr::Sequence(items) | Expr::Choice(items) => items.iter_mut().for_each(|item| rename_refs(item, renames)),
+        Expr::Optional(inner) | Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => rename_refs(inner, renames),
+        Expr::Capture(_, inner) | Expr::Drop(inner) => rename_refs(inner, renames),
+    }
+}
+
+/// prefixes the names an imported expression refers to, literals stay shared between files
+fn add_namespace(expr: &mut Expr, namespace: &str) {
+    match expr {
+        Expr::Ref(name, _) if !name.starts_with('"') => *name = format!("{}.{}", namespace, name),
+        Expr::Ref(_, _) => {}
+        Expr::Sequence(items) | Expr::Choice(items) => items.iter_mut().for_each(|item| add_namespace(item, namespace)),
+        Expr::Optional(inner) | Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => add_namespace(inner, namespace),
+        Expr::Capture(_, inner) | Expr::Drop(inner) => add_namespace(inner, namespace),
+    }
+}
+
+impl Loader<'_> {
+    fn definitions(&mut self) -> Result<(), GrammarError> {
+        while self.state.lookahead(0).is_some() {
+            if let Some(percent) = self.state.eat_if(PERCENT) {
+                self.directive(self.span(&percent))?;
+            } else {
+                self.definition()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn span(&self, token: &TokenData) -> Span {
+        Span::from_token(token).with_source(self.source)
+    }
+
+    /// `%name ...;` after the `%`
+    fn directive(&mut self, start: Span) -> Result<(), GrammarError> {
+        let name = self.expect(&[IDENT])?;
+        match name.value.as_str() {
+            "import" => self.import(),
+            "left" => self.precedence(Fixity::Left, name, start),
+            "right" => self.precedence(Fixity::Right, name, start),
+            "prefix" => self.precedence(Fixity::Prefix, name, start),
+            "postfix" => self.precedence(Fixity::Postfix, name, start),
+            _ => Err(GrammarError::new(format!("Unknown directive `%{}`", name.value), self.span(&name))),
+        }
+    }
+
+    /// `%import "file.ars";` or `%import "file.ars" as namespace;`
+    fn import(&mut self) -> Result<(), GrammarError> {
+        let path = self.expect(&[STRING])?;
+        let span = self.span(&path);
+        let path = self.base.join(unescape_literal(&path.value[1..path.value.len() - 1]));
+        let namespace = match self.state.lookahead(0) {
+            Some(token) if token.kind == IDENT && token.value == "as" => {
+                self.state.eat_if(IDENT);
+                Some(self.expect(&[IDENT])?.value)
+            }
+            _ => None,
+        };
+        self.expect(&[SEMICOLON])?;
+
+        let mut imported = load_import(&path, self.sources, self.allow_duplicates).map_err(|mut error| {
+            // point at the import when the file itself cannot be read
+            if error.file.is_none() {
+                error.span = span;
+            }
+            error
+        })?;
+        for token in imported.tokens.iter_mut().filter(|token| !token.name.starts_with('"')) {
+            if let Some(namespace) = &namespace {
+                token.name = format!("{}.{}", namespace, token.name);
+            }
+        }
+        for rule in imported.rules.iter_mut() {
+            if let Some(namespace) = &namespace {
+                rule.name = format!("{}.{}", namespace, rule.name);
+                add_namespace(&mut rule.expr, namespace);
+                for name in rule.attributes.drop.iter_mut() {
+                    *name = format!("{}.{}", namespace, name);
+                }
+            }
+        }
+        for mut precedence in imported.precedence {
+            if let Some(namespace) = &namespace {
+                for name in precedence.operators.iter_mut().filter(|name| !name.starts_with('"')) {
+                    *name = format!("{}.{}", namespace, name);
+                }
+            }
+            self.grammar.precedence.push(precedence);
+        }
+        for token in imported.tokens {
+            if self.grammar.token(&token.name).is_none() {
+                self.grammar.tokens.push(token);
+            }
+        }
+        for rule in imported.rules {
+            if self.grammar.rule(&rule.name).is_none() {
+                self.grammar.rules.push(rule);
+            }
+        }
+        Ok(())
+    }
+
+    /// `%left OP "op" ...;` after the directive name
+    fn precedence(&mut self, fixity: Fixity, directive: TokenData, start: Span) -> Result<(), GrammarError> {
+        let mut operators = vec![];
+        while self.state.lookahead(0).is_some_and(|token| token.kind != SEMICOLON) {
+            let operator = match self.atom()? {
+                Expr::Ref(name, _) => name,
+                _ => return Err(GrammarError::new("Operators must be tokens", self.span(&directive))),
+            };
+            operators.push(operator);
+        }
+        if operators.is_empty() {
+            return Err(GrammarError::new(format!("`%{}` needs at least one operator", directive.value), self.span(&directive)));
+        }
+        let end = self.expect(&[SEMICOLON])?;
+        self.grammar.precedence.push(Precedence { fixity, operators, span: start.merge(self.span(&end)) });
+        Ok(())
+    }
+
+    /// `name` or `namespace.name`
+    fn name(&mut self) -> Result<(String, Span), GrammarError> {
+        let first = self.expect(&[IDENT])?;
+        let mut span = self.span(&first);
+        let mut name = first.value;
+        while self.state.eat_if(DOT).is_some() {
+            let part = self.expect(&[IDENT])?;
+            span = span.merge(self.span(&part));
+            name = format!("{}.{}", name, part.value);
+        }
+        Ok((name, span))
+    }
+
+    fn expect(&mut self, kinds: &[u32]) -> Result<TokenData, GrammarError> {
+        self.state.expect_any(kinds).map_err(to_error)
+    }
+
+    fn definition(&mut self) -> Result<(), GrammarError> {
+        let attributes = self.attributes()?;
+        let (name, span) = self.name()?;
+        let duplicate = self.defined.iter().any(|(defined, _)| *defined == name);
+        if !self.allow_duplicates {
+            self.check_duplicate(&name, span)?;
+        }
+        self.defined.push((name.clone(), span));
+
+        if self.state.eat_if(EXTEND).is_some() {
+            let expr = self.choice()?;
+            self.expect(&[SEMICOLON])?;
+            let Some(rule) = self.grammar.rules.iter_mut().find(|rule| rule.name == name) else {
+                return Err(GrammarError::new(format!("Cannot extend `{}` which is not a rule", name), span));
+            };
+            let mut alternatives = match std::mem::replace(&mut rule.expr, Expr::Choice(vec![])) {
+                Expr::Choice(alternatives) => alternatives,
+                expr => vec![expr],
+            };
+            match expr {
+                Expr::Choice(more) => alternatives.extend(more),
+                expr => alternatives.push(expr),
+            }
+            rule.expr = Expr::Choice(alternatives);
+            return Ok(());
+        }
+        self.expect(&[COLON])?;
+
+        if is_token_name(&name) {
+            if attributes != Attributes::default() {
+                return Err(GrammarError::new(format!("Token `{}` cannot have attributes", name), span));
+            }
+            let body = self.expect(&[STRING, REGEX])?;
+            let pattern = pattern_of(&body);
+            let end = self.expect(&[SEMICOLON])?;
+            let token = TokenDef { name, pattern, kind: 0, span: span.merge(self.span(&end)) };
+            // an imported token is overridden in place so that the lexer tries it at the same point
+            match self.grammar.tokens.iter_mut().find(|existing| existing.name == token.name) {
+                Some(existing) if !duplicate => *existing = token,
+                _ => self.grammar.tokens.push(token),
+            }
+        } else {
+            let expr = self.choice()?;
+            let end = self.expect(&[SEMICOLON])?;
+            let rule = RuleDef { name, expr, kind: 0, span: span.merge(self.span(&end)), attributes };
+            match self.grammar.rules.iter_mut().find(|existing| existing.name == rule.name) {
+                Some(existing) if !duplicate => *existing = rule,
+                _ => self.grammar.rules.push(rule),
+            }
+        }
+        Ok(())
+    }
+
+    /// `@name` or `@name(argument, ...)` annotations before a definition
+    fn attributes(&mut self) -> Result<Attributes, GrammarError> {
+        let mut attributes = Attributes::default();
+        while self.state.eat_if(AT).is_some() {
+            let name = self.expect(&[IDENT])?;
+            let span = self.span(&name);
+            let mut arguments = vec![];
+            if self.state.eat_if(LPAREN).is_some() {
+                loop {
+                    let argument = self.expect(&[IDENT, STRING, NUMBER])?;
+                    arguments.push(match argument.kind {
+                        STRING => unescape_literal(&argument.value[1..argument.value.len() - 1]),
+                        _ => argument.value,
+                    });
+                    if self.state.eat_if(COMMA).is_none() {
+                        break;
+                    }
+                }
+                self.expect(&[RPAREN])?;
+            }
+            let single = |arguments: &mut Vec<String>| match arguments.len() {
+                1 => Ok(arguments.pop().unwrap()),
+                _ => Err(GrammarError::new(format!("`@{}` takes exactly one argument", name.value), span)),
+            };
+            match name.value.as_str() {
+                "inline" if arguments.is_empty() => attributes.inline = true,
+                "label" => attributes.label = Some(single(&mut arguments)?),
+                "kind" => {
+                    let kind = single(&mut arguments)?;
+                    attributes.kind = Some(kind.parse().map_err(|_| GrammarError::new(format!("`{}` is not a valid kind", kind), span))?);
+                }
+                "value" => attributes.value = Some(single(&mut arguments)?),
+                "drop" if !arguments.is_empty() => attributes.drop.extend(arguments),
+                "pratt" if arguments.is_empty() => attributes.pratt = true,
+                _ => return Err(GrammarError::new(format!("Unknown attribute `@{}`", name.value), span)),
+            }
+        }
+        Ok(attributes)
+    }
+
+    fn check_duplicate(&self, name: &str, span: Span) -> Result<(), GrammarError> {
+        let previous = self.defined.iter().find(|(defined, _)| defined == name).map(|(_, span)| *span);
+        match previous {
+            Some(previous) => Err(GrammarError::new(format!("`{}` is already defined at {}", name, previous), span)),
+            None => Ok(()),
+        }
+    }
+
+    fn choice(&mut self) -> Result<Expr, GrammarError> {
+        let mut alternatives = vec![self.sequence()?];
+        while self.state.eat_if(PIPE).is_some() {
+            alternatives.push(self.sequence()?);
+        }
+        Ok(if alternatives.len() == 1 { alternatives.pop().unwrap() } else { Expr::Choice(alternatives) })
+    }
+
+    fn sequence(&mut self) -> Result<Expr, GrammarError> {
+        let mut items = vec![self.element()?];
+        while self.state.lookahead(0).is_some_and(|token| [IDENT, STRING, LPAREN, TILDE].contains(&token.kind)) {
+            // `Name: ...` after a missing `;` starts the next definition
+            if self.state.is_seq(&[IDENT, COLON]) && !self.is_capture() {
+                break;
+            }
+            items.push(self.element()?);
+        }
+        Ok(if items.len() == 1 { items.pop().unwrap() } else { Expr::Sequence(items) })
+    }
+
+    /// an item of a sequence, with its `~` and `name:` markers
+    fn element(&mut self) -> Result<Expr, GrammarError> {
+        if self.state.eat_if(TILDE).is_some() {
+            return Ok(Expr::Drop(Box::new(self.element()?)));
+        }
+        if self.is_capture() {
+            let name = self.expect(&[IDENT])?;
+            self.expect(&[COLON])?;
+            return Ok(Expr::Capture(name.value, Box::new(self.postfix()?)));
+        }
+        self.postfix()
+    }
+
+    /// `name:X` is written without spaces, which tells it apart from a definition
+    fn is_capture(&self) -> bool {
+        if !self.state.is_seq(&[IDENT, COLON]) {
+            return false;
+        }
+        let tokens = (0..3).map(|n| self.state.lookahead(n)).collect::<Option<Vec<_>>>();
+        tokens.is_some_and(|tokens| tokens[0].span.1 == tokens[1].span.0 && tokens[1].span.1 == tokens[2].span.0)
+    }
+
+    fn postfix(&mut self) -> Result<Expr, GrammarError> {
+        let mut expr = self.atom()?;
+        loop {
+            expr = if self.state.eat_if(QUESTION).is_some() {
+                Expr::Optional(Box::new(expr))
+            } else if self.state.eat_if(STAR).is_some() {
+                Expr::ZeroOrMore(Box::new(expr))
+            } else if self.state.eat_if(PLUS).is_some() {
+                Expr::OneOrMore(Box::new(expr))
+            } else {
+                return Ok(expr);
+            };
+        }
+    }
+
+    fn atom(&mut self) -> Result<Expr, GrammarError> {
+        if self.state.lookahead(0).is_some_and(|token| token.kind == IDENT) {
+            let (name, span) = self.name()?;
+            return Ok(Expr::Ref(name, span));
+        }
+        let token = self.expect(&[STRING, LPAREN, REGEX])?;
+        let span = self.span(&token);
+        match token.kind {
+            STRING => Ok(Expr::Ref(self.literal_token(&token), span)),
+            LPAREN => {
+                let expr = self.choice()?;
+                self.expect(&[RPAREN])?;
+                Ok(expr)
+            }
+            _ => Err(GrammarError::new("Regexes can only be used in token definitions", span)),
+        }
+    }
+
+    /// name of the token of a literal used inside a rule, `resolve_literals` later swaps it for a
+    /// named token with the same pattern
+    fn literal_token(&mut self, token: &TokenData) -> String {
+        let pattern = pattern_of(token);
+        let existing = self.grammar.tokens.iter().chain(self.anonymous.iter()).find(|def| def.name.starts_with('"') && def.pattern == pattern);
+        if let Some(existing) = existing {
+            return existing.name.clone();
+        }
+        self.anonymous.push(TokenDef { name: token.value.clone(), pattern, kind: 0, span: self.span(token) });
+        token.value.clone()
+    }
+}
+
+fn pattern_of(token: &TokenData) -> Pattern {
+    let body = &token.value[1..token.value.len() - 1];
+    match token.kind {
+        STRING => Pattern::Literal(unescape_literal(body)),
+        _ => Pattern::Regex(unescape_regex(body)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::TempDir;
+
+    #[test]
+    fn test_load_example() {
+        let grammar = load(include_str!("../../assets/grammars/small_example_lang.ars")).unwrap();
+        let names = grammar.tokens.iter().map(|token| token.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["WS", "NUMBER", "STRING", "LET", "IDENTITY", "EQUAL"]);
+        assert_eq!(grammar.token("WS").unwrap().pattern, Pattern::Regex("\\s+|\\x00".to_string()));
+        assert_eq!(grammar.token("LET").unwrap().pattern, Pattern::Literal("let".to_string()));
+        let rule = grammar.rule("VariableDefinition").unwrap();
+        assert_eq!(rule.kind, 6);
+        match &rule.expr {
+            Expr::Sequence(items) => {
+                assert_eq!(items.len(), 8);
+                assert!(matches!(&items[1], Expr::Optional(_)));
+                assert!(matches!(&items[6], Expr::Choice(alternatives) if alternatives.len() == 2));
+            }
+            other => panic!("unexpected expression {:?}", other),
+        }
+        grammar.lexer().unwrap();
+    }
+
+    #[test]
+    fn test_inline_literals() {
+        let grammar = load("NUM: /\\d+/;\nPLUS: \"+\";\nSum: NUM (\"+\" NUM | \"-\" NUM)*;").unwrap();
+        assert_eq!(grammar.tokens[0].name, "\"-\"");
+        assert_eq!(grammar.tokens.len(), 3);
+    }
+
+    #[test]
+    fn test_literals_before_their_token() {
+        let grammar = load("Rule: \"let\" LET \"==\" \"=\";\nLET: \"let\";\nEQ: \"=\";").unwrap();
+        let names = grammar.tokens.iter().map(|token| token.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["\"==\"", "LET", "EQ"]);
+        assert_eq!(grammar.rule("Rule").unwrap().expr.to_string(), "LET LET \"==\" EQ");
+        assert!(grammar.interpret("letlet===").is_ok());
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = load("A: \"a\";\nRule: A A\nB: \"b\";").unwrap_err();
+        assert_eq!(error.message, "Expected `;` but found `B`");
+        assert_eq!((error.span.line, error.span.column), (3, 1));
+
+        let error = load("A: \"a\";\nA: \"b\";").unwrap_err();
+        assert!(error.message.contains("already defined at 1:1"));
+
+        let error = load("Rule: /a/;").unwrap_err();
+        assert_eq!(error.span.column, 7);
+
+        let error = load("@value(a, b) Rule: A;").unwrap_err();
+        assert_eq!(error.message, "`@value` takes exactly one argument");
+    }
+
+    #[test]
+    fn test_annotations() {
+        let grammar = load("WS: /\\s+/;\n@label(\"Var\") @kind(42) @value(name) @drop(WS)\nVar: ~\"let\" name:ID? (~WS | x:ID)*;\n@inline Other: Var;").unwrap();
+        let rule = grammar.rule("Var").unwrap();
+        assert_eq!(rule.attributes, Attributes {
+            inline: false,
+            label: Some("Var".to_string()),
+            kind: Some(42),
+            value: Some("name".to_string()),
+            drop: vec!["WS".to_string()],
+            pratt: false,
+        });
+        assert_eq!(rule.expr.to_string(), "~\"let\" name:ID? (~WS | x:ID)*");
+        assert!(grammar.rule("Other").unwrap().attributes.inline);
+    }
+
+    /// writes `files` into a fresh directory and returns it
+    fn write_files(name: &str, files: &[(&str, &str)]) -> TempDir {
+        let dir = TempDir::new(name);
+        for (file, text) in files {
+            std::fs::write(dir.join(file), text).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_imports() {
+        let dir = write_files("imports", &[
+            ("common.ars", "NUM: /\\d+/;\nID: /[a-z]+/;\nValue: NUM | ID;"),
+            ("main.ars", "%import \"common.ars\" as common;\nList: \"[\" common.Value* \"]\";\ncommon.ID: /[a-z_]+/;\ncommon.Value |= \"nil\";"),
+        ]);
+        let grammar = load_file(&dir.join("main.ars")).unwrap();
+        let names = grammar.tokens.iter().map(|token| token.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["\"nil\"", "\"[\"", "\"]\"", "common.NUM", "common.ID"]);
+        assert_eq!(grammar.token("common.ID").unwrap().pattern, Pattern::Regex("[a-z_]+".to_string()));
+        assert_eq!(grammar.rule("common.Value").unwrap().expr.to_string(), "common.NUM | common.ID | \"nil\"");
+        assert_eq!(grammar.start_rule().unwrap().name, "List");
+
+        let span = grammar.token("common.NUM").unwrap().span;
+        assert_eq!(grammar.sources[span.source], dir.join("common.ars"));
+        assert!(grammar.location(span).ends_with("common.ars:1:1"));
+
+        let node = grammar.interpret("[a1nil]").unwrap();
+        assert_eq!(node.children.len(), 5);
+    }
+
+    #[test]
+    fn test_imported_literals() {
+        let dir = write_files("imported-literals", &[
+            ("ops.ars", "Op: \"=\" | \"!\";"),
+            ("main.ars", "%import \"ops.ars\";\nCmp: Op | \"==\" | \"!=\";\nBANG: \"!\";"),
+        ]);
+        let grammar = load_file(&dir.join("main.ars")).unwrap();
+        let names = grammar.tokens.iter().map(|token| token.name.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["\"==\"", "\"!=\"", "\"=\"", "BANG"]);
+        assert_eq!(grammar.rule("Op").unwrap().expr.to_string(), "\"=\" | BANG");
+    }
+
+    #[test]
+    fn test_import_errors() {
+        let dir = write_files("import-errors", &[
+            ("a.ars", "%import \"b.ars\";\nA: \"a\";"),
+            ("b.ars", "%import \"a.ars\";\nB: \"b\";"),
+            ("broken.ars", "B: \"b\"\nC: \"c\";"),
+            ("main.ars", "%import \"broken.ars\";"),
+            ("extend.ars", "Rule |= \"x\";"),
+        ]);
+        let error = load_file(&dir.join("a.ars")).unwrap_err();
+        assert_eq!(error.message, "Import cycle a.ars -> b.ars -> a.ars");
+        assert_eq!(error.file, Some(dir.join("b.ars")));
+        assert_eq!((error.span.line, error.span.column), (1, 9));
+
+        let error = load_file(&dir.join("main.ars")).unwrap_err();
+        assert_eq!(error.file, Some(dir.join("broken.ars")));
+        assert_eq!((error.span.line, error.span.column), (2, 1));
+
+        let error = load_file(&dir.join("extend.ars")).unwrap_err();
+        assert_eq!(error.message, "Cannot extend `Rule` which is not a rule");
+    }
+}
@@ -0,0 +1,389 @@
+use std::{collections::HashSet, fmt::Display, path::{Path, PathBuf}};
+
+use crate::{lexer::Lexer, parser::{Fixity, Operator}, span::Span, token::Token};
+
+pub mod loader;
+pub mod interpreter;
+pub mod codegen;
+pub mod lint;
+pub mod analysis;
+pub mod export;
+pub mod convert;
+pub mod generator;
+pub mod railroad;
+
+/// a grammar read from an `.ars` file
+#[derive(Debug, Clone, Default)]
+pub struct Grammar {
+    /// token definitions in the order the lexer tries them
+    pub tokens: Vec<TokenDef>,
+    pub rules: Vec<RuleDef>,
+    /// `%left`, `%right`, `%prefix` and `%postfix` lines, used by `@pratt` rules
+    pub precedence: Vec<Precedence>,
+    /// files the grammar was loaded from, indexed by `Span::source`, the first one is empty for text
+    pub sources: Vec<PathBuf>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct TokenDef {
+    pub name: String,
+    pub pattern: Pattern,
+    pub kind: u32,
+    pub span: Span,
+}
+
+impl TokenDef {
+    /// whether the pattern matches without consuming anything, which would stall the lexer
+    pub fn matches_empty(&self) -> bool {
+        match &self.pattern {
+            Pattern::Literal(lit) => lit.is_empty(),
+            Pattern::Regex(pattern) => regex::Regex::new(pattern).is_ok_and(|regex| regex.is_match("")),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Pattern {
+    Literal(String),
+    Regex(String),
+}
+
+/// one precedence level, levels declared later bind tighter
+#[derive(Debug, Clone, PartialEq)]
+pub struct Precedence {
+    pub fixity: Fixity,
+    /// names of the operator tokens
+    pub operators: Vec<String>,
+    pub span: Span,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RuleDef {
+    pub name: String,
+    pub expr: Expr,
+    /// identifies the rule, also the kind of its nodes unless `@kind` says otherwise
+    pub kind: u32,
+    pub span: Span,
+    pub attributes: Attributes,
+}
+
+/// `@...` annotations written before a rule, they shape the nodes the rule produces
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Attributes {
+    /// `@inline`, the children of the rule are spliced into the parent node
+    pub inline: bool,
+    /// `@label("...")`
+    pub label: Option<String>,
+    /// `@kind(...)`
+    pub kind: Option<u32>,
+    /// `@value(capture)`, the value of the captured child becomes the value of the node when it is a leaf
+    pub value: Option<String>,
+    /// `@drop(TOKEN, ...)`, tokens left out of the node
+    pub drop: Vec<String>,
+    /// `@pratt`, the rule matches operands joined by the declared operators
+    pub pratt: bool,
+}
+
+impl RuleDef {
+    pub fn node_label(&self) -> &str {
+        self.attributes.label.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn node_kind(&self) -> u32 {
+        self.attributes.kind.unwrap_or(self.kind)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// a token or a rule referenced by name
+    Ref(String, Span),
+    Sequence(Vec<Expr>),
+    Choice(Vec<Expr>),
+    Optional(Box<Expr>),
+    ZeroOrMore(Box<Expr>),
+    OneOrMore(Box<Expr>),
+    /// `name:expr`, nodes matched by the expression are labeled `name`
+    Capture(String, Box<Expr>),
+    /// `~expr`, matched but left out of the tree
+    Drop(Box<Expr>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrammarError {
+    pub message: String,
+    pub span: Span,
+    /// the file the error is in when it is not the grammar being loaded
+    pub file: Option<PathBuf>,
+}
+
+impl GrammarError {
+    pub fn new(message: impl Into<String>, span: Span) -> Self {
+        Self { message: message.into(), span, file: None }
+    }
+}
+
+impl Display for GrammarError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.file {
+            Some(file) => write!(f, "{} at {}:{}", self.message, file.display(), self.span),
+            None => write!(f, "{} at {}", self.message, self.span),
+        }
+    }
+}
+
+impl std::error::Error for GrammarError {}
+
+impl Grammar {
+    /// parses the text of an `.ars` grammar
+    pub fn parse(source: &str) -> Result<Grammar, GrammarError> {
+        loader::load(source)
+    }
+
+    /// reads an `.ars` file, imports are resolved relative to it
+    pub fn load(path: impl AsRef<Path>) -> Result<Grammar, GrammarError> {
+        loader::load_file(path.as_ref())
+    }
+
+    pub fn token(&self, name: &str) -> Option<&TokenDef> {
+        self.tokens.iter().find(|token| token.name == name)
+    }
+
+    pub fn rule(&self, name: &str) -> Option<&RuleDef> {
+        self.rules.iter().find(|rule| rule.name == name)
+    }
+
+    /// the first rule of the main file, imported rules only when it has none
+    pub fn start_rule(&self) -> Option<&RuleDef> {
+        self.rules.iter().find(|rule| rule.span.source == 0).or(self.rules.first())
+    }
+
+    /// `file:line:column` of a span, just `line:column` for grammars parsed from text
+    pub fn location(&self, span: Span) -> String {
+        match self.sources.get(span.source) {
+            Some(path) if !path.as_os_str().is_empty() => format!("{}:{}", path.display(), span),
+            _ => span.to_string(),
+        }
+    }
+
+    /// numbers tokens first and rules after them, in definition order
+    pub fn assign_kinds(&mut self) {
+        for (kind, token) in self.tokens.iter_mut().enumerate() {
+            token.kind = kind as u32;
+        }
+        let offset = self.tokens.len();
+        for (kind, rule) in self.rules.iter_mut().enumerate() {
+            rule.kind = (offset + kind) as u32;
+        }
+    }
+
+    /// the precedence levels as operators for `ParserState::pratt`
+    pub fn operators(&self) -> Result<Vec<Operator>, GrammarError> {
+        let mut operators = vec![];
+        for (level, precedence) in self.precedence.iter().enumerate() {
+            for name in precedence.operators.iter() {
+                let token = self.token(name)
+                    .ok_or_else(|| GrammarError::new(format!("Operator `{}` is not a token", name), precedence.span))?;
+                operators.push(Operator::new(token.kind, precedence.fixity, level as u32 + 1));
+            }
+        }
+        Ok(operators)
+    }
+
+    /// token patterns the lexer needs to tokenize input for this grammar
+    pub fn token_patterns(&self) -> Result<Vec<Token>, GrammarError> {
+        self.tokens
+            .iter()
+            .map(|token| match &token.pattern {
+                Pattern::Literal(lit) => Ok(Token::new_lit(&token.name, token.kind, lit)),
+                Pattern::Regex(regex) => regex::Regex::new(regex)
+                    .map(|regex| Token::new_regex(&token.name, token.kind, regex))
+                    .map_err(|e| GrammarError::new(format!("Invalid regex for `{}`: {}", token.name, e), token.span)),
+            })
+            .collect()
+    }
+
+    pub fn lexer(&self) -> Result<Lexer, GrammarError> {
+        Ok(Lexer::new(self.token_patterns()?))
+    }
+
+    /// names of the rules that can match without consuming any token
+    pub fn nullable_rules(&self) -> HashSet<&str> {
+        let mut nullable = HashSet::new();
+        loop {
+            let before = nullable.len();
+            for rule in self.rules.iter() {
+                if !nullable.contains(rule.name.as_str()) && rule.expr.is_nullable(&nullable) {
+                    nullable.insert(rule.name.as_str());
+                }
+            }
+            if nullable.len() == before {
+                return nullable;
+            }
+        }
+    }
+}
+
+impl Expr {
+    /// calls `f` on this expression and every expression nested in it
+    pub fn walk<'a>(&'a self, f: &mut impl FnMut(&'a Expr)) {
+        f(self);
+        match self {
+            Expr::Ref(_, _) => {}
+            Expr::Sequence(items) | Expr::Choice(items) => items.iter().for_each(|item| item.walk(f)),
+            Expr::Optional(inner) | Expr::ZeroOrMore(inner) | Expr::OneOrMore(inner) => inner.walk(f),
+            Expr::Capture(_, inner) | Expr::Drop(inner) => inner.walk(f),
+        }
+    }
+
+    /// span of the first name written in the expression
+    pub fn first_span(&self) -> Option<Span> {
+        let mut span = None;
+        self.walk(&mut |expr| {
+            if let (None, Expr::Ref(_, found)) = (span, expr) {
+                span = Some(*found);
+            }
+        });
+        span
+    }
+
+    /// whether the expression can match nothing, given the rules already known to be nullable
+    pub fn is_nullable(&self, nullable: &HashSet<&str>) -> bool {
+        match self {
+            Expr::Ref(name, _) => nullable.contains(name.as_str()),
+            Expr::Sequence(items) => items.iter().all(|item| item.is_nullable(nullable)),
+            Expr::Choice(alternatives) => alternatives.iter().any(|alternative| alternative.is_nullable(nullable)),
+            Expr::Optional(_) | Expr::ZeroOrMore(_) => true,
+            Expr::OneOrMore(inner) | Expr::Capture(_, inner) | Expr::Drop(inner) => inner.is_nullable(nullable),
+        }
+    }
+
+    /// binding strength used to decide where parentheses are needed when printing
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Choice(_) => 0,
+            Expr::Sequence(_) => 1,
+            Expr::Capture(_, _) | Expr::Drop(_) => 2,
+            Expr::Optional(_) | Expr::ZeroOrMore(_) | Expr::OneOrMore(_) => 3,
+            Expr::Ref(_, _) => 4,
+        }
+    }
+
+    fn fmt_inner(&self, f: &mut std::fmt::Formatter<'_>, precedence: u8) -> std::fmt::Result {
+        if self.precedence() < precedence {
+            write!(f, "(")?;
+            self.fmt_inner(f, 0)?;
+            return write!(f, ")");
+        }
+        match self {
+            Expr::Ref(name, _) => write!(f, "{}", name),
+            Expr::Sequence(items) => {
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " ")?;
+                    }
+                    item.fmt_inner(f, 2)?;
+                }
+                Ok(())
+            }
+            Expr::Choice(alternatives) => {
+                for (i, alternative) in alternatives.iter().enumerate() {
+                    if i > 0 {
+                        write!(f, " | ")?;
+                    }
+                    alternative.fmt_inner(f, 1)?;
+                }
+                Ok(())
+            }
+            Expr::Optional(inner) => {
+                inner.fmt_inner(f, 4)?;
+                write!(f, "?")
+            }
+            Expr::ZeroOrMore(inner) => {
+                inner.fmt_inner(f, 4)?;
+                write!(f, "*")
+            }
+            Expr::OneOrMore(inner) => {
+                inner.fmt_inner(f, 4)?;
+                write!(f, "+")
+            }
+            Expr::Capture(name, inner) => {
+                write!(f, "{}:", name)?;
+                inner.fmt_inner(f, 3)
+            }
+            Expr::Drop(inner) => {
+                write!(f, "~")?;
+                inner.fmt_inner(f, 3)
+            }
+        }
+    }
+}
+
+/// prints the expression in `.ars` syntax
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.fmt_inner(f, 0)
+    }
+}
+
+/// prints the grammar as `.ars` text, imports come out merged into one file
+impl Display for Grammar {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // literals written inside rules come back by themselves
+        for token in self.tokens.iter().filter(|token| !token.name.starts_with('"')) {
+            match &token.pattern {
+                Pattern::Literal(lit) => writeln!(f, "{}: \"{}\";", token.name, loader::escape_literal(lit))?,
+                Pattern::Regex(regex) => writeln!(f, "{}: /{}/;", token.name, loader::escape_regex(regex))?,
+            }
+        }
+        for precedence in self.precedence.iter() {
+            let directive = match precedence.fixity {
+                Fixity::Left => "left",
+                Fixity::Right => "right",
+                Fixity::Prefix => "prefix",
+                Fixity::Postfix => "postfix",
+            };
+            writeln!(f, "%{} {};", directive, precedence.operators.join(" "))?;
+        }
+        for rule in self.rules.iter() {
+            let attributes = &rule.attributes;
+            let mut annotations = vec![];
+            if attributes.inline {
+                annotations.push("@inline".to_string());
+            }
+            if attributes.pratt {
+                annotations.push("@pratt".to_string());
+            }
+            if let Some(label) = &attributes.label {
+                annotations.push(format!("@label(\"{}\")", loader::escape_literal(label)));
+            }
+            if let Some(kind) = attributes.kind {
+                annotations.push(format!("@kind({})", kind));
+            }
+            if let Some(value) = &attributes.value {
+                annotations.push(format!("@value({})", value));
+            }
+            if !attributes.drop.is_empty() {
+                annotations.push(format!("@drop({})", attributes.drop.join(", ")));
+            }
+            if !annotations.is_empty() {
+                writeln!(f, "{}", annotations.join(" "))?;
+            }
+            writeln!(f, "{}: {};", rule.name, rule.expr)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_round_trip() {
+        let source = "SLASH: /\\/|\\\\\\\\/;\nQUOTE: \"\\\"\";\n%left \"+\";\n@pratt @label(\"E\")\nExpr: ~\"(\" Expr \")\" | x:SLASH QUOTE?;\n";
+        let grammar = Grammar::parse(source).unwrap();
+        assert_eq!(grammar.to_string(), source);
+        assert_eq!(grammar.token("SLASH").unwrap().pattern, Pattern::Regex("/|\\\\".to_string()));
+    }
+}
@@ -0,0 +1,300 @@
+use std::{fmt::Write, path::Path};
+
+use crate::parser::Fixity;
+
+use super::{Expr, Grammar, Pattern, RuleDef};
+
+const CHAR_WIDTH: u32 = 8;
+const BOX_HEIGHT: u32 = 22;
+/// horizontal space between items of a sequence
+const GAP: u32 = 10;
+/// radius of the curves joining tracks
+const ARC: u32 = 10;
+/// vertical space between the tracks of a choice
+const SEPARATION: u32 = 8;
+const PADDING: u32 = 10;
+
+const STYLE: &str = "path { fill: none; stroke: #333; stroke-width: 2; } \
+rect { fill: #fff8e0; stroke: #333; stroke-width: 2; } \
+rect.rule { fill: #e8f0ff; } \
+text { font: 13px monospace; text-anchor: middle; dominant-baseline: central; }";
+
+/// one piece of a railroad diagram, entered and left on its baseline
+enum Shape {
+    /// a plain line, the way around an optional item
+    Skip,
+    Terminal(String),
+    /// a rule reference, with the link it points to
+    Rule(String, String),
+    Sequence(Vec<Shape>),
+    /// the first track is on the baseline, the others below it
+    Choice(Vec<Shape>),
+    /// the item with a track looping back under it
+    Repeat(Box<Shape>),
+}
+
+impl Shape {
+    fn width(&self) -> u32 {
+        match self {
+            Shape::Skip => 0,
+            Shape::Terminal(text) | Shape::Rule(text, _) => text.chars().count() as u32 * CHAR_WIDTH + 2 * GAP,
+            Shape::Sequence(items) => items.iter().map(Shape::width).sum::<u32>() + GAP * (items.len() as u32).saturating_sub(1),
+            Shape::Choice(items) => items.iter().map(Shape::width).max().unwrap_or(0) + 4 * ARC,
+            Shape::Repeat(item) => item.width() + 2 * ARC,
+        }
+    }
+
+    /// height above the baseline
+    fn up(&self) -> u32 {
+        match self {
+            Shape::Skip => 0,
+            Shape::Terminal(_) | Shape::Rule(_, _) => BOX_HEIGHT / 2,
+            Shape::Sequence(items) => items.iter().map(Shape::up).max().unwrap_or(0),
+            Shape::Choice(items) => items.first().map_or(0, Shape::up),
+            Shape::Repeat(item) => item.up(),
+        }
+    }
+
+    /// height below the baseline
+    fn down(&self) -> u32 {
+        match self {
+            Shape::Skip => 0,
+            Shape::Terminal(_) | Shape::Rule(_, _) => BOX_HEIGHT / 2,
+            Shape::Sequence(items) => items.iter().map(Shape::down).max().unwrap_or(0),
+            Shape::Choice(items) => {
+                let first = items.first().map_or(0, Shape::down);
+                first + items.iter().skip(1).map(|item| SEPARATION + item.up() + item.down()).sum::<u32>()
+            }
+            Shape::Repeat(item) => self.loop_offset(item),
+        }
+    }
+
+    /// how far below the baseline the track of a repetition runs
+    fn loop_offset(&self, item: &Shape) -> u32 {
+        (item.down() + SEPARATION).max(2 * ARC)
+    }
+
+    /// draws the shape with its entry at (x, y)
+    fn render(&self, out: &mut String, x: u32, y: u32) {
+        let width = self.width();
+        match self {
+            Shape::Skip => {}
+            Shape::Terminal(text) => {
+                writeln!(out, r#"<rect x="{}" y="{}" width="{}" height="{}" rx="10"/>"#, x, y - BOX_HEIGHT / 2, width, BOX_HEIGHT).unwrap();
+                writeln!(out, r#"<text x="{}" y="{}">{}</text>"#, x + width / 2, y, escape(text)).unwrap();
+            }
+            Shape::Rule(text, link) => {
+                writeln!(out, r#"<a href="{}">"#, escape(link)).unwrap();
+                writeln!(out, r#"<rect class="rule" x="{}" y="{}" width="{}" height="{}"/>"#, x, y - BOX_HEIGHT / 2, width, BOX_HEIGHT).unwrap();
+                writeln!(out, r#"<text x="{}" y="{}">{}</text>"#, x + width / 2, y, escape(text)).unwrap();
+                writeln!(out, "</a>").unwrap();
+            }
+            Shape::Sequence(items) => {
+                let mut x = x;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        line(out, x, y, x + GAP, y);
+                        x += GAP;
+                    }
+                    item.render(out, x, y);
+                    x += item.width();
+                }
+            }
+            Shape::Choice(items) => {
+                let end = x + width;
+                let mut track = y;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 {
+                        track += items[i - 1].down() + SEPARATION + item.up();
+                        // down from the entry and back up to the exit
+                        writeln!(
+                            out,
+                            r#"<path d="M{} {} Q{} {} {} {} L{} {} Q{} {} {} {}"/>"#,
+                            x, y, x + ARC, y, x + ARC, y + ARC, x + ARC, track - ARC, x + ARC, track, x + 2 * ARC, track
+                        ).unwrap();
+                        writeln!(
+                            out,
+                            r#"<path d="M{} {} Q{} {} {} {} L{} {} Q{} {} {} {}"/>"#,
+                            end - 2 * ARC, track, end - ARC, track, end - ARC, track - ARC, end - ARC, y + ARC, end - ARC, y, end, y
+                        ).unwrap();
+                    } else {
+                        line(out, x, y, x + 2 * ARC, y);
+                    }
+                    item.render(out, x + 2 * ARC, track);
+                    line(out, x + 2 * ARC + item.width(), track, end - 2 * ARC, track);
+                    if i == 0 {
+                        line(out, end - 2 * ARC, y, end, y);
+                    }
+                }
+            }
+            Shape::Repeat(item) => {
+                let (start, end) = (x + ARC, x + ARC + item.width());
+                let track = y + self.loop_offset(item);
+                line(out, x, y, start, y);
+                item.render(out, start, y);
+                line(out, end, y, x + width, y);
+                writeln!(
+                    out,
+                    r#"<path d="M{} {} Q{} {} {} {} L{} {} Q{} {} {} {} L{} {} Q{} {} {} {} L{} {} Q{} {} {} {}"/>"#,
+                    end, y, end + ARC, y, end + ARC, y + ARC,
+                    end + ARC, track - ARC, end + ARC, track, end, track,
+                    start, track, x, track, x, track - ARC,
+                    x, y + ARC, x, y, start, y
+                ).unwrap();
+            }
+        }
+    }
+}
+
+fn line(out: &mut String, x1: u32, y1: u32, x2: u32, y2: u32) {
+    if x1 != x2 || y1 != y2 {
+        writeln!(out, r#"<path d="M{} {} L{} {}"/>"#, x1, y1, x2, y2).unwrap();
+    }
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+/// builds the shapes of a rule, `link` gives the target of each rule reference
+struct Builder<'g> {
+    grammar: &'g Grammar,
+    link: &'g dyn Fn(&str) -> String,
+}
+
+impl Builder<'_> {
+    fn reference(&self, name: &str) -> Shape {
+        match self.grammar.token(name) {
+            Some(token) => match &token.pattern {
+                Pattern::Literal(lit) if name.starts_with('"') => Shape::Terminal(lit.clone()),
+                _ => Shape::Terminal(name.to_string()),
+            },
+            None => Shape::Rule(name.to_string(), (self.link)(name)),
+        }
+    }
+
+    fn shape(&self, expr: &Expr) -> Shape {
+        match expr {
+            Expr::Ref(name, _) => self.reference(name),
+            Expr::Sequence(items) => Shape::Sequence(items.iter().map(|item| self.shape(item)).collect()),
+            Expr::Choice(alternatives) => Shape::Choice(alternatives.iter().map(|alternative| self.shape(alternative)).collect()),
+            Expr::Optional(inner) => Shape::Choice(vec![Shape::Skip, self.shape(inner)]),
+            Expr::ZeroOrMore(inner) => Shape::Choice(vec![Shape::Skip, Shape::Repeat(Box::new(self.shape(inner)))]),
+            Expr::OneOrMore(inner) => Shape::Repeat(Box::new(self.shape(inner))),
+            // captures and drops shape the tree, not the syntax
+            Expr::Capture(_, inner) | Expr::Drop(inner) => self.shape(inner),
+        }
+    }
+
+    /// a `@pratt` rule is drawn as its operators around references to itself
+    fn rule(&self, rule: &RuleDef) -> Shape {
+        if !rule.attributes.pratt {
+            return self.shape(&rule.expr);
+        }
+        let this = || self.reference(&rule.name);
+        let mut alternatives = vec![self.shape(&rule.expr)];
+        for precedence in self.grammar.precedence.iter() {
+            let operators = Shape::Choice(precedence.operators.iter().map(|op| self.reference(op)).collect());
+            alternatives.push(Shape::Sequence(match precedence.fixity {
+                Fixity::Left | Fixity::Right => vec![this(), operators, this()],
+                Fixity::Prefix => vec![operators, this()],
+                Fixity::Postfix => vec![this(), operators],
+            }));
+        }
+        Shape::Choice(alternatives)
+    }
+}
+
+fn svg(grammar: &Grammar, rule: &RuleDef, link: &dyn Fn(&str) -> String) -> String {
+    let shape = Builder { grammar, link }.rule(rule);
+    // room for the start and end marks
+    let marks = 2 * GAP;
+    let width = shape.width() + 2 * marks + 2 * PADDING;
+    let height = shape.up() + shape.down() + 2 * PADDING;
+    let y = PADDING + shape.up();
+    let mut out = String::new();
+    writeln!(out, r#"<svg xmlns="http://www.w3.org/2000/svg" width="{}" height="{}" viewBox="0 0 {} {}">"#, width, height, width, height).unwrap();
+    writeln!(out, "<style>{}</style>", STYLE).unwrap();
+    writeln!(out, r#"<path d="M{} {} L{} {} M{} {} L{} {}"/>"#, PADDING, y - 7, PADDING, y + 7, PADDING, y, PADDING + marks, y).unwrap();
+    shape.render(&mut out, PADDING + marks, y);
+    let end = PADDING + marks + shape.width();
+    writeln!(out, r#"<path d="M{} {} L{} {} M{} {} L{} {}"/>"#, end, y, end + marks, y, end + marks, y - 7, end + marks, y + 7).unwrap();
+    writeln!(out, "</svg>").unwrap();
+    out
+}
+
+/// the diagram of one rule as a standalone SVG, rule references link to `<rule>.svg`
+pub fn rule_svg(grammar: &Grammar, rule: &RuleDef) -> String {
+    svg(grammar, rule, &|name| format!("{}.svg", name))
+}
+
+/// a page with the diagram and the definition of every rule, references link inside the page
+pub fn html(grammar: &Grammar, title: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "<!DOCTYPE html>").unwrap();
+    writeln!(out, "<html>\n<head>\n<meta charset=\"utf-8\">\n<title>{}</title>", escape(title)).unwrap();
+    writeln!(out, "<style>body {{ font-family: sans-serif; }} pre {{ background: #f4f4f4; padding: 8px; }}</style>").unwrap();
+    writeln!(out, "</head>\n<body>\n<h1>{}</h1>", escape(title)).unwrap();
+    writeln!(out, "<ul>").unwrap();
+    for rule in grammar.rules.iter() {
+        writeln!(out, "<li><a href=\"#{}\">{}</a></li>", escape(&rule.name), escape(&rule.name)).unwrap();
+    }
+    writeln!(out, "</ul>").unwrap();
+    for rule in grammar.rules.iter() {
+        writeln!(out, "<h2 id=\"{}\">{}</h2>", escape(&rule.name), escape(&rule.name)).unwrap();
+        out.push_str(&svg(grammar, rule, &|name| format!("#{}", name)));
+        writeln!(out, "<pre>{}: {};</pre>", escape(&rule.name), escape(&rule.expr.to_string())).unwrap();
+    }
+    writeln!(out, "</body>\n</html>").unwrap();
+    out
+}
+
+/// writes `index.html` and one `<rule>.svg` per rule into `dir`
+pub fn write_diagrams(grammar: &Grammar, title: &str, dir: impl AsRef<Path>) -> std::io::Result<()> {
+    let dir = dir.as_ref();
+    std::fs::create_dir_all(dir)?;
+    for rule in grammar.rules.iter() {
+        std::fs::write(dir.join(format!("{}.svg", rule.name)), rule_svg(grammar, rule))?;
+    }
+    std::fs::write(dir.join("index.html"), html(grammar, title))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grammar() -> Grammar {
+        Grammar::parse("NUM: /\\d+/;\nList: \"[\" (Item (\",\" Item)*)? \"]\";\nItem: NUM | List;").unwrap()
+    }
+
+    #[test]
+    fn test_shapes() {
+        let grammar = grammar();
+        let builder = Builder { grammar: &grammar, link: &|name| name.to_string() };
+        let shape = builder.rule(grammar.rule("Item").unwrap());
+        // two boxes stacked, plus the curves on both sides
+        assert_eq!(shape.width(), "List".len() as u32 * CHAR_WIDTH + 2 * GAP + 4 * ARC);
+        assert_eq!(shape.up(), BOX_HEIGHT / 2);
+        assert_eq!(shape.down(), BOX_HEIGHT / 2 + SEPARATION + BOX_HEIGHT);
+    }
+
+    #[test]
+    fn test_rule_svg() {
+        let grammar = grammar();
+        let svg = rule_svg(&grammar, grammar.rule("List").unwrap());
+        assert!(svg.starts_with("<svg xmlns=\"http://www.w3.org/2000/svg\""));
+        assert!(svg.contains(">[</text>"));
+        assert!(svg.contains("<a href=\"Item.svg\">"));
+        assert_eq!(svg.matches("<rect class=\"rule\"").count(), 2);
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_html() {
+        let html = html(&grammar(), "Lists & items");
+        assert!(html.contains("<title>Lists &amp; items</title>"));
+        assert!(html.contains("<h2 id=\"Item\">Item</h2>"));
+        assert!(html.contains("<a href=\"#List\">"));
+        assert!(html.contains("<pre>List: &quot;[&quot; (Item (&quot;,&quot; Item)*)? &quot;]&quot;;</pre>"));
+    }
+}
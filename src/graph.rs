@@ -0,0 +1,244 @@
+use std::{collections::HashSet, fmt::Write};
+
+use crate::{
+    ast::{Node, Value},
+    printer::Fields,
+    sexp::write_value,
+    visitor::VisitorResult,
+};
+
+/// draws trees as Graphviz DOT or Mermaid flowcharts, one box per node
+#[derive(Debug, Clone)]
+pub struct Graph {
+    name: String,
+    fields: Fields,
+    /// addresses of the nodes to highlight
+    highlighted: HashSet<usize>,
+}
+
+impl Default for Graph {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+struct Vertex {
+    lines: Vec<String>,
+    highlighted: bool,
+}
+
+/// vertices in the order they were found, edges from parents to children
+#[derive(Default)]
+struct Drawing {
+    vertices: Vec<Vertex>,
+    edges: Vec<(usize, usize)>,
+}
+
+impl Drawing {
+    fn add(&mut self, lines: Vec<String>, highlighted: bool, parent: Option<usize>) -> usize {
+        let id = self.vertices.len();
+        self.vertices.push(Vertex { lines, highlighted });
+        if let Some(parent) = parent {
+            self.edges.push((parent, id));
+        }
+        id
+    }
+}
+
+impl Graph {
+    /// shows labels, kinds and values but not spans
+    pub fn new() -> Self {
+        Self {
+            name: "ast".to_string(),
+            fields: Fields { value: true, kind: true, span: false },
+            highlighted: HashSet::new(),
+        }
+    }
+
+    /// name of the DOT graph
+    pub fn with_name(mut self, name: &str) -> Self {
+        self.name = name.to_string();
+        self
+    }
+
+    pub fn with_fields(mut self, fields: Fields) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    /// marks nodes of the drawn tree, found by address so query matches can be passed as they are
+    pub fn highlight<'a>(mut self, nodes: impl IntoIterator<Item = &'a Node>) -> Self {
+        self.highlighted.extend(nodes.into_iter().map(|node| node as *const Node as usize));
+        self
+    }
+
+    pub fn to_dot(&self, node: &Node) -> String {
+        self.dot(&self.draw_node(node))
+    }
+
+    pub fn to_mermaid(&self, node: &Node) -> String {
+        self.mermaid(&self.draw_node(node))
+    }
+
+    pub fn result_to_dot(&self, result: &VisitorResult) -> String {
+        self.dot(&draw_result(result))
+    }
+
+    pub fn result_to_mermaid(&self, result: &VisitorResult) -> String {
+        self.mermaid(&draw_result(result))
+    }
+
+    fn draw_node(&self, root: &Node) -> Drawing {
+        let mut drawing = Drawing::default();
+        let mut stack = vec![(root, None)];
+        while let Some((node, parent)) = stack.pop() {
+            let mut lines = vec![node.label.clone()];
+            if self.fields.kind {
+                lines.push(format!("kind: {}", node.kind));
+            }
+            if self.fields.value && !node.value.is_none() {
+                lines.push(format!("value: {}", value_text(&node.value)));
+            }
+            if self.fields.span && node.span.is_known() {
+                lines.push(format!("span: {} ({}..{})", node.span, node.span.start, node.span.end));
+            }
+            let highlighted = self.highlighted.contains(&(node as *const Node as usize));
+            let id = drawing.add(lines, highlighted, parent);
+            stack.extend(node.children.iter().rev().map(|child| (child.as_ref(), Some(id))));
+        }
+        drawing
+    }
+
+    fn dot(&self, drawing: &Drawing) -> String {
+        let mut out = String::new();
+        writeln!(out, "digraph \"{}\" {{", escape_dot(&self.name)).unwrap();
+        writeln!(out, "    ordering=out;").unwrap();
+        writeln!(out, "    node [shape=box, fontname=\"monospace\"];").unwrap();
+        for (id, vertex) in drawing.vertices.iter().enumerate() {
+            let label = vertex.lines.iter().map(|line| escape_dot(line)).collect::<Vec<_>>().join("\\n");
+            let style = match vertex.highlighted {
+                true => ", style=filled, fillcolor=\"#ffe08a\", penwidth=2",
+                false => "",
+            };
+            writeln!(out, "    n{} [label=\"{}\"{}];", id, label, style).unwrap();
+        }
+        for (parent, child) in drawing.edges.iter() {
+            writeln!(out, "    n{} -> n{};", parent, child).unwrap();
+        }
+        out.push_str("}\n");
+        out
+    }
+
+    fn mermaid(&self, drawing: &Drawing) -> String {
+        let mut out = "flowchart TD\n".to_string();
+        for (id, vertex) in drawing.vertices.iter().enumerate() {
+            let label = vertex.lines.iter().map(|line| escape_mermaid(line)).collect::<Vec<_>>().join("<br/>");
+            writeln!(out, "    n{}[\"{}\"]", id, label).unwrap();
+        }
+        for (parent, child) in drawing.edges.iter() {
+            writeln!(out, "    n{} --> n{}", parent, child).unwrap();
+        }
+        let highlighted = drawing.vertices.iter().enumerate().filter(|(_, vertex)| vertex.highlighted).map(|(id, _)| format!("n{}", id)).collect::<Vec<_>>();
+        if !highlighted.is_empty() {
+            writeln!(out, "    classDef highlight fill:#ffe08a,stroke:#b8860b,stroke-width:2px").unwrap();
+            writeln!(out, "    class {} highlight", highlighted.join(",")).unwrap();
+        }
+        out
+    }
+}
+
+fn draw_result(root: &VisitorResult) -> Drawing {
+    let mut drawing = Drawing::default();
+    let mut stack = vec![(root, None)];
+    while let Some((result, parent)) = stack.pop() {
+        let line = match result {
+            VisitorResult::Compound(_) => "compound".to_string(),
+            VisitorResult::String(s) => format!("string: {:?}", s),
+            VisitorResult::Integer(i) => format!("integer: {}", i),
+            VisitorResult::Number(n) => format!("number: {:?}", n),
+            VisitorResult::Tagged(tag, _) => tag.clone(),
+            VisitorResult::None => "none".to_string(),
+        };
+        let id = drawing.add(vec![line], false, parent);
+        match result {
+            VisitorResult::Compound(items) => stack.extend(items.iter().rev().map(|item| (item, Some(id)))),
+            VisitorResult::Tagged(_, value) => stack.push((value.as_ref(), Some(id))),
+            _ => {}
+        }
+    }
+    drawing
+}
+
+/// values as in S-expressions, so strings are quoted
+fn value_text(value: &Value) -> String {
+    let mut out = String::new();
+    write_value(&mut out, value);
+    out
+}
+
+fn escape_dot(text: &str) -> String {
+    text.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n")
+}
+
+/// mermaid reads entity codes inside quoted labels
+fn escape_mermaid(text: &str) -> String {
+    text.replace('&', "#amp;").replace('"', "#quot;").replace('<', "#lt;").replace('>', "#gt;").replace('\n', "<br/>")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{query::Query, sexp::from_sexp, span::Span};
+
+    fn tree() -> Node {
+        from_sexp("(Sum #3 (NUMBER #1 \"1\") (Paren #2 (NUMBER #1 \"2\")))").unwrap()
+    }
+
+    #[test]
+    fn test_dot() {
+        let tree = tree();
+        let query = Query::new("(NUMBER) @n").unwrap();
+        let graph = Graph::new().highlight(query.captures(&tree, "n"));
+        let expected = r##"digraph "ast" {
+    ordering=out;
+    node [shape=box, fontname="monospace"];
+    n0 [label="Sum\nkind: 3"];
+    n1 [label="NUMBER\nkind: 1\nvalue: \"1\"", style=filled, fillcolor="#ffe08a", penwidth=2];
+    n2 [label="Paren\nkind: 2"];
+    n3 [label="NUMBER\nkind: 1\nvalue: \"2\"", style=filled, fillcolor="#ffe08a", penwidth=2];
+    n0 -> n1;
+    n0 -> n2;
+    n2 -> n3;
+}
+"##;
+        assert_eq!(graph.to_dot(&tree), expected);
+    }
+
+    #[test]
+    fn test_mermaid() {
+        let mut tree = Node::new(4, "Cmp", Value::from("<"));
+        tree.add_child(Node::new(1, "ID", Value::from("a")).with_span(Span::new(0, 1, 1, 1)));
+        let graph = Graph::new().with_fields(Fields { value: true, kind: false, span: true });
+        let expected = "\
+flowchart TD
+    n0[\"Cmp<br/>value: #quot;#lt;#quot;<br/>span: 1:1 (0..1)\"]
+    n1[\"ID<br/>value: #quot;a#quot;<br/>span: 1:1 (0..1)\"]
+    n0 --> n1
+";
+        assert_eq!(graph.to_mermaid(&tree), expected);
+
+        let highlighted = Graph::new().highlight([tree.children[0].as_ref()]).to_mermaid(&tree);
+        assert!(highlighted.ends_with("    class n1 highlight\n"));
+    }
+
+    #[test]
+    fn test_visitor_results() {
+        let result = VisitorResult::Compound(vec![VisitorResult::Integer(1), VisitorResult::Tagged("neg".to_string(), VisitorResult::String("x".to_string()).into())]);
+        let dot = Graph::new().with_name("result").result_to_dot(&result);
+        assert!(dot.starts_with("digraph \"result\" {"));
+        assert!(dot.contains("n3 [label=\"string: \\\"x\\\"\"];"));
+        assert!(dot.contains("n2 -> n3;"));
+        let mermaid = Graph::new().result_to_mermaid(&result);
+        assert!(mermaid.contains("    n0 --> n2\n"));
+    }
+}
@@ -46,6 +46,17 @@ impl Lexer {
         tokens
     }
 
+    /// like `all` but returns the (line, column) of the first character no pattern matches instead of panicking
+    pub fn try_all(&mut self) -> Result<Vec<TokenData>, (usize, usize)> {
+        let mut tokens = Vec::new();
+        while self.current < self.input.len() {
+            self.start = self.current;
+            tokens.push(self.matched().ok_or((self.line, self.column))?);
+        }
+        Ok(tokens)
+    }
+
+    #[allow(clippy::should_implement_trait)]
     pub fn next(&mut self) -> Option<TokenData> {
         self.start = self.current;
         self.tokenize()
@@ -55,41 +66,57 @@ impl Lexer {
         if self.current >= self.input.len() {
             return None;
         }
+        if let Some(token) = self.matched() {
+            return Some(token);
+        }
+
+        panic!("\x1b[31;1mInvalid character\x1b[0m {:?}\n - line {}\n - column: {}", self.input.chars().nth(self.start).unwrap(), self.line, self.column);
+    }
 
+    /// the token of the first pattern matching at the cursor
+    fn matched(&mut self) -> Option<TokenData> {
         for pattern in self.patterns.iter() {
             if let Some(matched) = pattern.check(&self.input[self.current..]) {
+                let location = (self.line, self.column);
                 self.current += matched + 1;
-                self.column += matched + 1;
+                // we add 1 because check return index but slice starts from index 1 (probably)
+                let text = &self.input[self.start..self.start + matched + 1];
+                for c in text.chars() {
+                    if c == '\n' {
+                        self.line += 1;
+                        self.column = 1;
+                    } else {
+                        self.column += 1;
+                    }
+                }
                 let token_data = TokenData::new(
                     pattern.id,
-                    // we add 1 because check return index but slice starts from index 1 (probably)
-                    self.input[self.start..self.start + matched + 1].to_string(),
+                    text.to_string(),
                     pattern.label.clone(),
-                    (self.line, self.column),
+                    location,
                     (self.start, self.current));
                 return Some(token_data);
             }
         }
-
-        panic!("\x1b[31;1mInvalid character\x1b[0m {:?}\n - line {}\n - column: {}", self.input.chars().nth(self.start).unwrap(), self.line, self.column);
+        None
     }
 }
 
 pub trait LexerExt {
-    fn tokenize_all(&self, patterns: &Vec<Token>) -> Vec<TokenData>;
+    fn tokenize_all(&self, patterns: &[Token]) -> Vec<TokenData>;
 }
 
 impl LexerExt for &'static str {
-    fn tokenize_all(&self, patterns: &Vec<Token>) -> Vec<TokenData> {
-        let mut lexer = Lexer::new(patterns.clone());
+    fn tokenize_all(&self, patterns: &[Token]) -> Vec<TokenData> {
+        let mut lexer = Lexer::new(patterns.to_vec());
         lexer.begin(self);
         lexer.all()
     }
 }
 
 impl LexerExt for String {
-    fn tokenize_all(&self, patterns: &Vec<Token>) -> Vec<TokenData> {
-        let mut lexer = Lexer::new(patterns.clone());
+    fn tokenize_all(&self, patterns: &[Token]) -> Vec<TokenData> {
+        let mut lexer = Lexer::new(patterns.to_vec());
         lexer.begin(self);
         lexer.all()
     }
@@ -126,6 +153,18 @@ mod tests {
         assert_eq!(lexer.next(), None);
     }
 
+    #[test]
+    fn test_try_all() {
+        let mut lexer = Lexer::new(vec![
+            Token::new_regex_from_str("whitespace", 0, "\\s+"),
+            Token::new_lit("let", 1, "let"),
+        ]);
+        lexer.begin("let let");
+        assert_eq!(lexer.try_all().map(|tokens| tokens.len()), Ok(3));
+        lexer.begin("let\n x");
+        assert_eq!(lexer.try_all(), Err((2, 2)));
+    }
+
     #[test]
     fn test_variable_example() {
         let mut lexer = Lexer::new(vec![
@@ -145,4 +184,18 @@ mod tests {
         assert_eq!(lexer.next().unwrap().label, "number");
         assert_eq!(lexer.next(), None);
     }
+
+    #[test]
+    fn test_lexer_location() {
+        let mut lexer = Lexer::new(vec![
+            Token::new_regex_from_str("whitespace", 0, "\\s+"),
+            Token::new_lit("let", 1, "let"),
+        ]);
+        lexer.begin("let\n  let");
+        let tokens = lexer.all();
+        assert_eq!(tokens[0].location, (1, 1));
+        assert_eq!(tokens[1].location, (1, 4));
+        assert_eq!(tokens[2].location, (2, 3));
+        assert_eq!(tokens[2].span, (6, 9));
+    }
 }
\ No newline at end of file
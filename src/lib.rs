@@ -1,7 +1,27 @@
 pub mod token;
 pub mod lexer;
+pub mod span;
 pub mod ast;
+pub mod cst;
+pub mod cursor;
+pub mod query;
+pub mod rewrite;
 pub mod parser;
 pub mod visitor;
+pub mod printer;
+pub mod graph;
+pub mod diff;
+pub mod sexp;
+pub mod binary;
+pub mod corpus;
+pub mod grammar;
+pub mod build;
 
-pub mod builder;
\ No newline at end of file
+pub mod builder;
+
+#[cfg(test)]
+mod testing;
+
+// lets tests include generated code, which names the crate as `ars`
+#[cfg(test)]
+extern crate self as ars;
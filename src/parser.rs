@@ -1,9 +1,173 @@
-use core::panic;
+use std::{cell::RefCell, collections::{HashMap, HashSet}, fmt::Display, rc::Rc, sync::Arc};
 
-use crate::{ast::Node, token::TokenData};
+use crate::{ast::{Node, Value}, cst::{self, GreenNode}, span::Span, token::TokenData};
 
 pub trait Parser {
     fn parse(&self, state: &mut ParserState) -> Node;
+
+    /// fallible form of `parse`, `ParserState::try_parse` runs it so that a left recursive
+    /// parser can fail on its first call and be grown from the other alternatives
+    fn try_parse(&self, state: &mut ParserState) -> ParseResult {
+        Ok(self.parse(state))
+    }
+
+    /// rule id used as memoization key, parsers without one are never memoized
+    ///
+    /// two parsers with the same id share their memo entries, so each parser needs its own
+    fn id(&self) -> Option<u32> {
+        None
+    }
+}
+
+impl<P: Parser + ?Sized> Parser for &P {
+    fn parse(&self, state: &mut ParserState) -> Node {
+        (**self).parse(state)
+    }
+
+    fn try_parse(&self, state: &mut ParserState) -> ParseResult {
+        (**self).try_parse(state)
+    }
+
+    fn id(&self) -> Option<u32> {
+        (**self).id()
+    }
+}
+
+pub type ParseResult<T = Node> = Result<T, ParseError>;
+
+pub type Alternative<'a, T = Node> = &'a dyn Fn(&mut ParserState) -> ParseResult<T>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    Unexpected { expected: Vec<u32>, found: TokenData },
+    EndOfInput { expected: Vec<u32> },
+    /// a rule called itself at the same token index before consuming anything, which
+    /// only memoized states can grow
+    LeftRecursion { rule: u32, index: usize },
+    /// a rule was asked for by a name the grammar does not define
+    UnknownRule { name: String },
+    /// no token matches the input at this (line, column)
+    InvalidCharacter { location: (usize, usize) },
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ParseError::Unexpected { expected, found } if expected.is_empty() => write!(
+                f,
+                "Expected end of input but found {:?}({}) at line {}, column {}",
+                found.label, found.kind, found.location.0, found.location.1
+            ),
+            ParseError::Unexpected { expected, found } => write!(
+                f,
+                "Expected {:?} but found {:?}({}) at line {}, column {}",
+                expected, found.label, found.kind, found.location.0, found.location.1
+            ),
+            ParseError::EndOfInput { expected } => write!(f, "Expected {:?} but reached end of input", expected),
+            ParseError::LeftRecursion { rule, index } => write!(
+                f,
+                "Rule {} is left recursive at token {}, enable memoization with `ParserState::with_memo` to parse it",
+                rule, index
+            ),
+            ParseError::UnknownRule { name } => write!(f, "Rule `{}` is not defined", name),
+            ParseError::InvalidCharacter { location } => write!(f, "Invalid character at line {}, column {}", location.0, location.1),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl ParseError {
+    /// byte offset of the token the error points at, end of input counts as the furthest position
+    /// and left recursion as the nearest one
+    fn position(&self) -> Option<usize> {
+        match self {
+            ParseError::Unexpected { found, .. } => Some(found.span.0),
+            ParseError::EndOfInput { .. } => Some(usize::MAX),
+            ParseError::LeftRecursion { .. } | ParseError::UnknownRule { .. } | ParseError::InvalidCharacter { .. } => None,
+        }
+    }
+
+    /// keeps the error that got furthest, joining the expectations of errors at the same place
+    pub fn merge(self, other: ParseError) -> ParseError {
+        match (self.position(), other.position()) {
+            (a, b) if a > b => self,
+            (a, b) if a < b => other,
+            (None, None) => self,
+            _ => {
+                let mut expected = self.expected().to_vec();
+                for kind in other.expected() {
+                    if !expected.contains(kind) {
+                        expected.push(*kind);
+                    }
+                }
+                match self {
+                    ParseError::Unexpected { found, .. } => ParseError::Unexpected { expected, found },
+                    ParseError::EndOfInput { .. } => ParseError::EndOfInput { expected },
+                    error @ (ParseError::LeftRecursion { .. } | ParseError::UnknownRule { .. } | ParseError::InvalidCharacter { .. }) => error,
+                }
+            }
+        }
+    }
+
+    pub fn expected(&self) -> &[u32] {
+        match self {
+            ParseError::Unexpected { expected, .. } => expected,
+            ParseError::EndOfInput { expected } => expected,
+            ParseError::LeftRecursion { .. } | ParseError::UnknownRule { .. } | ParseError::InvalidCharacter { .. } => &[],
+        }
+    }
+}
+
+/// how an operator combines its operands
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Fixity {
+    /// binary, `a - b - c` is `(a - b) - c`
+    Left,
+    /// binary, `a ^ b ^ c` is `a ^ (b ^ c)`
+    Right,
+    Prefix,
+    Postfix,
+}
+
+/// an operator token understood by `ParserState::pratt`, higher precedence binds tighter
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Operator {
+    pub kind: u32,
+    pub fixity: Fixity,
+    pub precedence: u32,
+}
+
+impl Operator {
+    pub const fn new(kind: u32, fixity: Fixity, precedence: u32) -> Self {
+        Self { kind, fixity, precedence }
+    }
+
+    /// (left, right) binding power
+    pub(crate) fn binding_power(&self) -> (u32, u32) {
+        let power = self.precedence * 2;
+        match self.fixity {
+            Fixity::Left => (power, power + 1),
+            Fixity::Right => (power + 1, power),
+            Fixity::Prefix => (0, power + 1),
+            Fixity::Postfix => (power, 0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct MemoStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+#[derive(Debug, Default)]
+struct Memo {
+    /// (rule id, token index) -> (result, index after the rule)
+    table: HashMap<(u32, usize), (ParseResult, usize)>,
+    /// rules found to be left recursive while they were being evaluated
+    heads: HashSet<(u32, usize)>,
+    stats: MemoStats,
 }
 
 #[derive(Debug, Clone)]
@@ -11,26 +175,206 @@ pub struct ParserState {
     pub skip_kinds: Vec<u32>,
     pub tokens: Vec<TokenData>,
     index: usize,
+    /// shared between sandbox copies so that backtracking keeps the results
+    memo: Option<Rc<RefCell<Memo>>>,
+    /// rules currently being evaluated, used to detect left recursion
+    active: Rc<RefCell<Vec<(u32, usize)>>>,
 }
 
 impl ParserState {
     pub fn new(tokens: Vec<TokenData>, skip_kinds: Option<Vec<u32>>) -> Self {
         Self {
-            skip_kinds: skip_kinds.unwrap_or(vec![]),
-            tokens: tokens,
+            skip_kinds: skip_kinds.unwrap_or_default(),
+            tokens,
             index: 0,
+            memo: None,
+            active: Rc::new(RefCell::new(vec![])),
         }
     }
 
+    /// turns on packrat memoization for `rule` and for parsers that have an `id`
+    pub fn with_memo(mut self) -> Self {
+        self.memo = Some(Rc::new(RefCell::new(Memo::default())));
+        self
+    }
+
+    pub fn memo_stats(&self) -> Option<MemoStats> {
+        self.memo.as_ref().map(|memo| memo.borrow().stats)
+    }
+
+    /// runs `parser` and panics if it fails, see `try_parse`
     pub fn parse(&mut self, parser: impl Parser) -> Node {
-        self.skip_until_found(self.skip_kinds.clone());
+        match self.try_parse(parser) {
+            Ok(node) => node,
+            Err(ParseError::LeftRecursion { rule, index }) if self.memo.is_some() => panic!(
+                "Rule {} is left recursive at token {}, call it with `ParserState::try_parse` inside a `choice` so that it can be grown",
+                rule, index
+            ),
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    /// runs `parser`, memoized as a rule when it has an `id`
+    pub fn try_parse(&mut self, parser: impl Parser) -> ParseResult {
+        self.skip_trivia();
+        if let Some(id) = parser.id() {
+            return self.rule(id, |state| parser.try_parse(state));
+        }
         let mut sandbox_state = self.clone();
-        let node = parser.parse(&mut sandbox_state);
+        let node = parser.try_parse(&mut sandbox_state)?;
+        let node = node.with_span(sandbox_state.span_from(self.index));
         self.index = sandbox_state.index;
-        return node;
+        Ok(node)
+    }
+
+    /// runs `f` and rewinds the cursor if it fails
+    pub fn attempt<T>(&mut self, f: impl FnOnce(&mut ParserState) -> ParseResult<T>) -> ParseResult<T> {
+        let start = self.index;
+        let result = f(self);
+        if result.is_err() {
+            self.index = start;
+        }
+        result
+    }
+
+    /// tries every alternative from the same position and returns the first that succeeds
+    pub fn choice<T>(&mut self, alternatives: &[Alternative<T>]) -> ParseResult<T> {
+        let mut error: Option<ParseError> = None;
+        for alternative in alternatives {
+            match self.attempt(alternative) {
+                Ok(value) => return Ok(value),
+                // without memoization the recursion cannot be grown, so trying other alternatives would hide it
+                Err(e @ ParseError::LeftRecursion { .. }) if self.memo.is_none() => return Err(e),
+                Err(e) => error = Some(match error {
+                    Some(previous) => previous.merge(e),
+                    None => e,
+                }),
+            }
+        }
+        Err(error.unwrap_or(ParseError::EndOfInput { expected: vec![] }))
+    }
+
+    /// runs `f` until it fails or stops consuming tokens, collecting everything it produced
+    pub fn many<T>(&mut self, f: impl Fn(&mut ParserState) -> ParseResult<Vec<T>>) -> Vec<T> {
+        let mut items = vec![];
+        loop {
+            let start = self.index;
+            match self.attempt(&f) {
+                Ok(more) if self.index > start => items.extend(more),
+                _ => {
+                    self.index = start;
+                    return items;
+                }
+            }
+        }
+    }
+
+    /// runs a rule identified by `id`, reusing the stored result when memoization is enabled
+    ///
+    /// with memoization a left recursive rule is grown from its non recursive alternatives
+    /// until it stops consuming more tokens, without it left recursion is a `ParseError::LeftRecursion`
+    pub fn rule(&mut self, id: u32, f: impl Fn(&mut ParserState) -> ParseResult) -> ParseResult {
+        let key = (id, self.index);
+        let Some(memo) = self.memo.clone() else {
+            if self.active.borrow().contains(&key) {
+                return Err(ParseError::LeftRecursion { rule: id, index: key.1 });
+            }
+            return self.evaluate(key, &f);
+        };
+        let stored = memo.borrow().table.get(&key).cloned();
+        if let Some((result, end)) = stored {
+            self.index = end;
+            memo.borrow_mut().stats.hits += 1;
+            return result;
+        }
+        if self.active.borrow().contains(&key) {
+            memo.borrow_mut().heads.insert(key);
+            return Err(ParseError::LeftRecursion { rule: id, index: key.1 });
+        }
+        memo.borrow_mut().stats.misses += 1;
+        let mut result = self.evaluate(key, &f);
+        if memo.borrow_mut().heads.remove(&key) {
+            result = self.grow(key, &f, result);
+        }
+        memo.borrow_mut().table.insert(key, (result.clone(), self.index));
+        result
+    }
+
+    fn evaluate(&mut self, key: (u32, usize), f: &impl Fn(&mut ParserState) -> ParseResult) -> ParseResult {
+        self.active.borrow_mut().push(key);
+        let result = self.attempt(f).map(|node| node.with_span(self.span_from(key.1)));
+        self.active.borrow_mut().pop();
+        result
+    }
+
+    /// re-runs a left recursive rule with its previous result as the seed while it keeps getting longer
+    fn grow(&mut self, key: (u32, usize), f: &impl Fn(&mut ParserState) -> ParseResult, seed: ParseResult) -> ParseResult {
+        let memo = self.memo.clone().unwrap();
+        let mut result = seed;
+        while result.is_ok() {
+            let end = self.index;
+            let mut memo_ref = memo.borrow_mut();
+            memo_ref.table.insert(key, (result.clone(), end));
+            // results of other rules at this position may depend on the old seed
+            memo_ref.table.retain(|(rule, index), _| *index != key.1 || *rule == key.0);
+            drop(memo_ref);
+
+            self.index = key.1;
+            let next = self.evaluate(key, f);
+            if next.is_err() || self.index <= end {
+                self.index = end;
+                break;
+            }
+            result = next;
+        }
+        result
     }
 
-    pub fn require(&mut self, kinds: Vec<u32>) -> TokenData {
+    /// parses operands joined by `operators`, respecting their precedence and associativity
+    ///
+    /// binary operators give `Binary` nodes with both operands as children, unary ones `Prefix` and
+    /// `Postfix` nodes, all of them have the given kind and the operator text as value
+    pub fn pratt(&mut self, operators: &[Operator], kind: u32, operand: Alternative) -> ParseResult {
+        self.pratt_from(operators, kind, operand, 0)
+    }
+
+    fn pratt_from(&mut self, operators: &[Operator], kind: u32, operand: Alternative, min_power: u32) -> ParseResult {
+        let find = |token: Option<&TokenData>, prefix: bool| {
+            let token = token?;
+            operators.iter().find(|op| op.kind == token.kind && (op.fixity == Fixity::Prefix) == prefix).copied()
+        };
+
+        let mut lhs = match find(self.lookahead(0), true) {
+            Some(op) => {
+                let token = self.expect_any(&[op.kind])?;
+                let mut node = Node::new(kind, "Prefix", Value::String(token.value.clone())).with_span(&token);
+                node.add_child(self.pratt_from(operators, kind, operand, op.binding_power().1)?);
+                node
+            }
+            None => operand(self)?,
+        };
+        while let Some(op) = find(self.lookahead(0), false) {
+            let (left, right) = op.binding_power();
+            if left < min_power {
+                break;
+            }
+            let token = self.expect_any(&[op.kind])?;
+            let mut node = match op.fixity {
+                Fixity::Postfix => Node::new(kind, "Postfix", Value::String(token.value.clone())),
+                _ => Node::new(kind, "Binary", Value::String(token.value.clone())),
+            };
+            node.add_child(lhs);
+            node.span = node.span.merge(Span::from_token(&token));
+            if op.fixity != Fixity::Postfix {
+                node.add_child(self.pratt_from(operators, kind, operand, right)?);
+            }
+            lhs = node;
+        }
+        Ok(lhs)
+    }
+
+    pub fn require(&mut self, kinds: impl AsRef<[u32]>) -> TokenData {
+        let kinds = kinds.as_ref();
         let token = &self.tokens[self.index];
         if !kinds.contains(&token.kind) {
             panic!("Expected {:?} but found {:?}", kinds, token.kind);
@@ -38,40 +382,366 @@ impl ParserState {
         self.eat()
     }
 
-    pub fn is_kind(&self, kinds: Vec<u32>) -> bool {
+    pub fn is_kind(&self, kinds: impl AsRef<[u32]>) -> bool {
         if self.index >= self.tokens.len() {
             return false;
         }
-        kinds.contains(&self.peek().kind)
+        kinds.as_ref().contains(&self.peek().kind)
     }
 
     pub fn peek(&self) -> TokenData {
         self.tokens[self.index].clone()
     }
 
+    /// returns the token `n` positions after the cursor, trivia included
+    pub fn peek_nth(&self, n: usize) -> Option<&TokenData> {
+        self.tokens.get(self.index + n)
+    }
+
+    /// returns the `n`-th token after the cursor that is not one of `skip_kinds`
+    pub fn lookahead(&self, n: usize) -> Option<&TokenData> {
+        self.significant_index(n).map(|index| &self.tokens[index])
+    }
+
+    /// checks that the next significant tokens have exactly the given kinds, in order
+    pub fn is_seq(&self, kinds: &[u32]) -> bool {
+        kinds.iter().enumerate().all(|(n, kind)| self.lookahead(n).is_some_and(|token| token.kind == *kind))
+    }
+
+    /// skips trivia and eats the next token only if it has the given kind
+    pub fn eat_if(&mut self, kind: u32) -> Option<TokenData> {
+        if self.lookahead(0)?.kind != kind {
+            return None;
+        }
+        self.skip_trivia();
+        Some(self.eat())
+    }
+
+    /// skips trivia and eats the next token if it is one of `kinds`, otherwise reports what was found
+    pub fn expect_any(&mut self, kinds: &[u32]) -> Result<TokenData, ParseError> {
+        match self.lookahead(0) {
+            Some(token) if kinds.contains(&token.kind) => {
+                self.skip_trivia();
+                Ok(self.eat())
+            }
+            Some(token) => Err(ParseError::Unexpected { expected: kinds.to_vec(), found: token.clone() }),
+            None => Err(ParseError::EndOfInput { expected: kinds.to_vec() }),
+        }
+    }
+
     pub fn eat(&mut self) -> TokenData {
         self.index += 1;
         self.tokens[self.index - 1].clone()
     }
 
-    pub fn skip_until_found(&mut self, kinds: Vec<u32>) {
-        while self.index < self.tokens.len() && kinds.contains(&self.peek().kind) {
-            self.eat();
+    pub fn skip_until_found(&mut self, kinds: impl AsRef<[u32]>) {
+        let kinds = kinds.as_ref();
+        while self.index < self.tokens.len() && kinds.contains(&self.tokens[self.index].kind) {
+            self.index += 1;
         }
     }
 
+    /// skips every token listed in `skip_kinds`
+    pub fn skip_trivia(&mut self) {
+        while self.index < self.tokens.len() && self.skip_kinds.contains(&self.tokens[self.index].kind) {
+            self.index += 1;
+        }
+    }
+
+    /// span of the significant tokens from `start` up to the cursor, empty at the next token when there are none
+    pub fn span_from(&self, start: usize) -> Span {
+        let mut significant = (start..self.index.min(self.tokens.len()))
+            .filter(|index| !self.skip_kinds.contains(&self.tokens[*index].kind))
+            .map(|index| Span::from_token(&self.tokens[index]));
+        match significant.next() {
+            Some(first) => significant.fold(first, Span::merge),
+            None => match self.tokens.get(start).or(self.tokens.last()) {
+                Some(token) if start < self.tokens.len() => Span::new(token.span.0, token.span.0, token.location.0, token.location.1),
+                // past the last token, after its text
+                Some(token) => Span::new(token.span.1, token.span.1, token.location.0, token.location.1 + token.value.chars().count()),
+                None => Span::new(0, 0, 1, 1),
+            },
+        }
+    }
+
+    /// lossless concrete tree of `root`, a node this state parsed from gapless tokens, see `cst::from_ast`
+    pub fn cst(&self, root: &Node) -> Result<Arc<GreenNode>, cst::CstError> {
+        cst::from_ast(root, &self.tokens)
+    }
+
     pub fn is_at_end(&self) -> bool {
         self.index >= self.tokens.len()
     }
 
+    fn significant_index(&self, n: usize) -> Option<usize> {
+        (self.index..self.tokens.len())
+            .filter(|index| !self.skip_kinds.contains(&self.tokens[*index].kind))
+            .nth(n)
+    }
 }
 
+/// a unit struct implementing `Parser` with `$logic`, `$kind` is its `id`
+///
+/// the id keys the memo, parsers whose kinds are the same would read each other's results, so
+/// every parser made with it needs a kind of its own
 #[macro_export]
 macro_rules! create_parser {
+    // `$logic` returns a `ParseResult`, needed for left recursion
+    ( $name:ident, $kind:literal, try $logic:expr ) => {
+        pub struct $name;
+        impl ars::parser::Parser for $name {
+            fn parse(&self, state: &mut ParserState) -> Node {
+                self.try_parse(state).unwrap_or_else(|e| panic!("{}", e))
+            }
+            fn try_parse(&self, _state: &mut ParserState) -> ars::parser::ParseResult {($logic)(self, _state)}
+            fn id(&self) -> Option<u32> { Some($kind) }
+        }
+    };
     ( $name:ident, $kind:literal, $logic:expr ) => {
         pub struct $name;
         impl ars::parser::Parser for $name {
             fn parse(&self, _state: &mut ParserState) -> Node {($logic)(self, _state)}
+            fn id(&self) -> Option<u32> { Some($kind) }
         }
     };
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{lexer::LexerExt, token::{Token, TokenExt}};
+
+    fn state() -> ParserState {
+        let tokens = vec![
+            "let".to_token(1),
+            " ".to_token(0),
+            "x".to_token(2),
+            " ".to_token(0),
+            "=".to_token(3),
+            "10".to_token(4),
+        ];
+        ParserState::new(tokens, Some(vec![0]))
+    }
+
+    #[test]
+    fn test_lookahead() {
+        let state = state();
+        assert_eq!(state.peek_nth(1).unwrap().kind, 0);
+        assert_eq!(state.lookahead(1).unwrap().kind, 2);
+        assert_eq!(state.lookahead(3).unwrap().kind, 4);
+        assert_eq!(state.lookahead(4), None);
+        assert!(state.is_seq(&[1, 2, 3]));
+        assert!(!state.is_seq(&[1, 3]));
+        assert!(!state.is_seq(&[1, 2, 3, 4, 5]));
+    }
+
+    #[test]
+    fn test_eat_if_and_expect_any() {
+        let mut state = state();
+        assert!(state.eat_if(2).is_none());
+        assert_eq!(state.eat_if(1).unwrap().value, "let");
+        assert_eq!(state.expect_any(&[2, 4]).unwrap().value, "x");
+        match state.expect_any(&[4]) {
+            Err(ParseError::Unexpected { expected, found }) => {
+                assert_eq!(expected, vec![4]);
+                assert_eq!(found.kind, 3);
+            }
+            other => panic!("unexpected result {:?}", other),
+        }
+        state.expect_any(&[3]).unwrap();
+        state.expect_any(&[4]).unwrap();
+        assert_eq!(state.expect_any(&[4]), Err(ParseError::EndOfInput { expected: vec![4] }));
+        assert!(state.is_at_end());
+    }
+
+    #[test]
+    fn test_memo_reuses_rule_results() {
+        let mut state = state().with_memo();
+        let calls = std::cell::Cell::new(0);
+        let prefix = |state: &mut ParserState| state.rule(0, |state| {
+            calls.set(calls.get() + 1);
+            state.expect_any(&[1])?;
+            let token = state.expect_any(&[2])?;
+            Ok(Node::new(0, "prefix", crate::ast::Value::String(token.value)))
+        });
+        let node = state.choice(&[
+            &|state: &mut ParserState| { let node = prefix(state)?; state.expect_any(&[4])?; Ok(node) },
+            &|state: &mut ParserState| { let node = prefix(state)?; state.expect_any(&[3])?; Ok(node) },
+        ]).unwrap();
+        assert_eq!(node.value.as_str(), Some("x"));
+        assert_eq!(calls.get(), 1);
+        assert_eq!(state.memo_stats(), Some(MemoStats { hits: 1, misses: 1 }));
+        assert_eq!(state.lookahead(0).unwrap().kind, 4);
+    }
+
+    /// the tokens of `state` lexed from text, so that they have real spans
+    fn lexed_state() -> ParserState {
+        let tokens = "let x =10".tokenize_all(&[
+            Token::new_regex_from_str("whitespace", 0, "\\s+"),
+            Token::new_lit("let", 1, "let"),
+            Token::new_regex_from_str("iden", 2, "[a-z]+"),
+            Token::new_lit("equal", 3, "="),
+            Token::new_regex_from_str("number", 4, "\\d+"),
+        ]);
+        ParserState::new(tokens, Some(vec![0]))
+    }
+
+    #[test]
+    fn test_choice_reports_furthest_error() {
+        let mut state = lexed_state();
+        let error = state.choice(&[
+            &|state: &mut ParserState| state.expect_any(&[2]),
+            &|state: &mut ParserState| { state.expect_any(&[1])?; state.expect_any(&[3]) },
+            &|state: &mut ParserState| { state.expect_any(&[1])?; state.expect_any(&[4]) },
+        ]).unwrap_err();
+        assert_eq!(error.expected(), &[3, 4]);
+        assert_eq!(state.lookahead(0).unwrap().kind, 1);
+    }
+
+    /// Expr: Expr "-" NUMBER | NUMBER
+    fn expr(state: &mut ParserState) -> ParseResult {
+        state.rule(10, |state| state.choice(&[
+            &|state: &mut ParserState| {
+                let lhs = expr(state)?;
+                let op = state.expect_any(&[5])?;
+                let rhs = state.expect_any(&[4])?;
+                let mut node = Node::new(10, "Binary", crate::ast::Value::String(op.value));
+                node.add_child(lhs);
+                node.add_child(Node::new(4, "number", crate::ast::Value::String(rhs.value)));
+                Ok(node)
+            },
+            &|state: &mut ParserState| {
+                let number = state.expect_any(&[4])?;
+                Ok(Node::new(4, "number", crate::ast::Value::String(number.value)))
+            },
+        ]))
+    }
+
+    fn subtraction_state() -> ParserState {
+        let tokens = "7 - 2 - 1".tokenize_all(&[
+            Token::new_regex_from_str("whitespace", 0, "\\s+"),
+            Token::new_regex_from_str("number", 4, "\\d+"),
+            Token::new_lit("minus", 5, "-"),
+        ]);
+        ParserState::new(tokens, Some(vec![0]))
+    }
+
+    #[test]
+    fn test_left_recursion_grows_seed() {
+        let mut state = subtraction_state().with_memo();
+        let node = expr(&mut state).unwrap();
+        assert!(state.is_at_end());
+        // (7 - 2) - 1
+        assert_eq!(node.label, "Binary");
+        assert_eq!(node.children[1].value.as_str(), Some("1"));
+        assert_eq!(node.children[0].label, "Binary");
+        assert_eq!(node.children[0].children[0].value.as_str(), Some("7"));
+    }
+
+    #[test]
+    fn test_left_recursion_without_memo_fails() {
+        let mut state = subtraction_state();
+        let error = expr(&mut state).unwrap_err();
+        assert_eq!(error, ParseError::LeftRecursion { rule: 10, index: 0 });
+        assert!(error.to_string().contains("ParserState::with_memo"));
+    }
+
+    create_parser!(Number, 4, try |_, state: &mut ParserState| {
+        let number = state.expect_any(&[4])?;
+        Ok(Node::new(4, "number", crate::ast::Value::String(number.value)))
+    });
+
+    // Subtraction: Subtraction "-" Number | Number
+    create_parser!(Subtraction, 10, try |_, state: &mut ParserState| state.choice(&[
+        &|state: &mut ParserState| {
+            let lhs = state.try_parse(Subtraction)?;
+            let op = state.expect_any(&[5])?;
+            let mut node = Node::new(10, "Binary", crate::ast::Value::String(op.value));
+            node.add_child(lhs);
+            node.add_child(state.try_parse(Number)?);
+            Ok(node)
+        },
+        &|state: &mut ParserState| state.try_parse(Number),
+    ]));
+
+    create_parser!(Naive, 11, |_, state: &mut ParserState| state.parse(Naive));
+
+    #[test]
+    fn test_left_recursive_parser() {
+        let mut state = subtraction_state().with_memo();
+        let node = state.parse(Subtraction);
+        assert!(state.is_at_end());
+        assert_eq!(show(&node), "(- (- 7 2) 1)");
+
+        let mut state = subtraction_state();
+        assert_eq!(state.try_parse(Subtraction), Err(ParseError::LeftRecursion { rule: 10, index: 0 }));
+    }
+
+    #[test]
+    #[should_panic(expected = "call it with `ParserState::try_parse`")]
+    fn test_naive_left_recursive_parser() {
+        subtraction_state().with_memo().parse(Naive);
+    }
+
+    /// writes operator nodes as `(op lhs rhs)` and operands as their value
+    fn show(node: &Node) -> String {
+        match node.label.as_str() {
+            "Binary" | "Prefix" | "Postfix" => {
+                let children = node.children.iter().map(|child| show(child)).collect::<Vec<_>>();
+                format!("({} {})", node.value.as_string().unwrap_or_default(), children.join(" "))
+            }
+            _ => node.value.as_string().unwrap_or_default(),
+        }
+    }
+
+    #[test]
+    fn test_spans() {
+        let tokens = "  1 +\n -2 ".tokenize_all(&[
+            Token::new_regex_from_str("whitespace", 0, "\\s+"),
+            Token::new_regex_from_str("number", 4, "\\d+"),
+            Token::new_lit("plus", 5, "+"),
+            Token::new_lit("minus", 6, "-"),
+        ]);
+        let operators = [Operator::new(5, Fixity::Left, 1), Operator::new(6, Fixity::Prefix, 2)];
+        let mut state = ParserState::new(tokens, Some(vec![0])).with_memo();
+        let node = state.rule(1, |state| {
+            state.pratt(&operators, 10, &|state: &mut ParserState| Ok(Node::from_token(&state.expect_any(&[4])?)))
+        }).unwrap();
+        // trivia around the rule is left out
+        assert_eq!(node.span, Span::new(2, 9, 1, 3));
+        assert_eq!(node.children[1].span, Span::new(7, 9, 2, 2));
+        assert_eq!(node.children[1].children[0].span, Span::new(8, 9, 2, 3));
+
+        // a rule that matches nothing gets an empty span where it stopped
+        let node = state.rule(2, |_| Ok(Node::new(2, "empty", Value::None))).unwrap();
+        assert_eq!(node.span, Span::new(9, 9, 2, 4));
+        assert!(node.span.is_empty() && node.span.is_known());
+    }
+
+    #[test]
+    fn test_pratt() {
+        let tokens = "-1 - 2 * 3 ^ 2 ^ 2 ! - 4".tokenize_all(&[
+            Token::new_regex_from_str("whitespace", 0, "\\s+"),
+            Token::new_regex_from_str("number", 4, "\\d+"),
+            Token::new_lit("minus", 5, "-"),
+            Token::new_lit("star", 6, "*"),
+            Token::new_lit("caret", 7, "^"),
+            Token::new_lit("bang", 8, "!"),
+        ]);
+        let operators = [
+            Operator::new(5, Fixity::Left, 1),
+            Operator::new(6, Fixity::Left, 2),
+            Operator::new(7, Fixity::Right, 3),
+            Operator::new(5, Fixity::Prefix, 4),
+            Operator::new(8, Fixity::Postfix, 5),
+        ];
+        let mut state = ParserState::new(tokens, Some(vec![0]));
+        let node = state.pratt(&operators, 10, &|state: &mut ParserState| {
+            let number = state.expect_any(&[4])?;
+            Ok(Node::new(4, "number", Value::String(number.value)))
+        }).unwrap();
+        assert!(state.is_at_end());
+        assert_eq!(show(&node), "(- (- (- 1) (* 2 (^ 3 (^ 2 (! 2))))) 4)");
+        assert_eq!(node.kind, 10);
+    }
+}
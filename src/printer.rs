@@ -0,0 +1,511 @@
+use std::{
+    fmt::{self, Display, Write},
+    io::IsTerminal,
+};
+
+use crate::{
+    ast::{Node, Value},
+    sexp::write_value,
+    token::{Token, TokenData, TokenValue},
+    visitor::VisitorResult,
+};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    Always,
+    Never,
+    /// colors when stdout is a terminal and `NO_COLOR` is not set
+    #[default]
+    Auto,
+}
+
+impl ColorChoice {
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => std::io::stdout().is_terminal() && std::env::var_os("NO_COLOR").is_none(),
+        }
+    }
+}
+
+/// escape codes written before each part of the output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Theme {
+    pub label: String,
+    pub field: String,
+    pub string: String,
+    pub number: String,
+    pub none: String,
+    /// the lines of the tree
+    pub line: String,
+}
+
+impl Theme {
+    /// 24-bit colors
+    pub fn truecolor() -> Self {
+        let rgb = |r: u8, g: u8, b: u8| format!("\x1b[38;2;{};{};{}m", r, g, b);
+        Self {
+            label: rgb(200, 30, 200),
+            field: rgb(150, 150, 170),
+            string: rgb(60, 180, 100),
+            number: rgb(240, 140, 30),
+            none: rgb(255, 100, 100),
+            line: rgb(120, 120, 120),
+        }
+    }
+
+    /// the 8 basic colors, for terminals without 24-bit support
+    pub fn basic() -> Self {
+        Self {
+            label: "\x1b[32m".to_string(),
+            field: "\x1b[31;3m".to_string(),
+            string: "\x1b[34m".to_string(),
+            number: "\x1b[33m".to_string(),
+            none: "\x1b[31m".to_string(),
+            line: "\x1b[2m".to_string(),
+        }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::truecolor()
+    }
+}
+
+/// which fields are printed, `kind` is the id of tokens and `span` covers locations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Fields {
+    pub value: bool,
+    pub kind: bool,
+    pub span: bool,
+}
+
+impl Default for Fields {
+    fn default() -> Self {
+        Self { value: true, kind: true, span: true }
+    }
+}
+
+/// how nodes, visitor results and tokens are printed, their `Display` uses the default one
+#[derive(Debug, Clone)]
+pub struct Printer {
+    pub color: ColorChoice,
+    pub theme: Theme,
+    /// spaces per level of the tree
+    pub indent: usize,
+    /// levels printed under the root, deeper children are counted instead
+    pub max_depth: Option<usize>,
+    pub fields: Fields,
+    /// everything on one line, as S-expressions
+    pub compact: bool,
+}
+
+impl Default for Printer {
+    fn default() -> Self {
+        Self {
+            color: ColorChoice::default(),
+            theme: Theme::default(),
+            indent: 4,
+            max_depth: None,
+            fields: Fields::default(),
+            compact: false,
+        }
+    }
+}
+
+/// something a `Printer` can write, `depth` is its level in the tree
+pub trait Printable {
+    fn print(&self, printer: &Printer, out: &mut dyn Write, depth: usize) -> fmt::Result;
+}
+
+/// a printable with the printer to use, for `format!` and friends
+pub struct Printed<'a, T: ?Sized> {
+    printer: &'a Printer,
+    item: &'a T,
+}
+
+impl<T: Printable + ?Sized> Display for Printed<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.item.print(self.printer, f, 0)
+    }
+}
+
+/// the theme when colors are on, empty codes otherwise
+struct Colors<'a> {
+    label: &'a str,
+    field: &'a str,
+    string: &'a str,
+    number: &'a str,
+    none: &'a str,
+    line: &'a str,
+    stop: &'a str,
+}
+
+/// a line under a header, or a section of lines when it has `sub` fields
+struct Field {
+    name: &'static str,
+    text: String,
+    sub: Vec<(&'static str, String)>,
+}
+
+impl Field {
+    fn new(name: &'static str, text: String) -> Self {
+        Self { name, text, sub: vec![] }
+    }
+}
+
+impl Printer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_color(mut self, color: ColorChoice) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn with_indent(mut self, indent: usize) -> Self {
+        self.indent = indent;
+        self
+    }
+
+    pub fn with_max_depth(mut self, depth: usize) -> Self {
+        self.max_depth = Some(depth);
+        self
+    }
+
+    pub fn with_fields(mut self, fields: Fields) -> Self {
+        self.fields = fields;
+        self
+    }
+
+    pub fn with_compact(mut self, compact: bool) -> Self {
+        self.compact = compact;
+        self
+    }
+
+    pub fn render<T: Printable + ?Sized>(&self, item: &T) -> String {
+        let mut out = String::new();
+        item.print(self, &mut out, 0).unwrap();
+        out
+    }
+
+    pub fn display<'a, T: Printable + ?Sized>(&'a self, item: &'a T) -> Printed<'a, T> {
+        Printed { printer: self, item }
+    }
+
+    fn colors(&self) -> Colors<'_> {
+        let theme = &self.theme;
+        match self.color.enabled() {
+            true => Colors {
+                label: &theme.label,
+                field: &theme.field,
+                string: &theme.string,
+                number: &theme.number,
+                none: &theme.none,
+                line: &theme.line,
+                stop: "\x1b[0m",
+            },
+            false => Colors { label: "", field: "", string: "", number: "", none: "", line: "", stop: "" },
+        }
+    }
+
+    fn too_deep(&self, depth: usize) -> bool {
+        self.max_depth.is_some_and(|max| depth >= max)
+    }
+
+    /// the fields under a header, `└┬─` for the first, `└─` for the last
+    fn write_fields(&self, out: &mut dyn Write, space: &str, fields: &[Field]) -> fmt::Result {
+        let c = self.colors();
+        for (i, field) in fields.iter().enumerate() {
+            let last = i + 1 == fields.len();
+            let branch = match (i, last) {
+                (0, true) => "└─",
+                (0, false) => "└┬─",
+                (_, true) => " └─",
+                (_, false) => " ├─",
+            };
+            match field.sub.is_empty() {
+                true => writeln!(out, "{space}{}{branch}{} {}{}:{} {}", c.line, c.stop, c.field, field.name, c.stop, field.text)?,
+                false => writeln!(out, "{space}{}{branch}{} {}{}{}", c.line, c.stop, c.label, field.name, c.stop)?,
+            }
+            let rail = if last { "   " } else { " │ " };
+            for (j, (name, text)) in field.sub.iter().enumerate() {
+                let branch = if j + 1 == field.sub.len() { "└─" } else { "├─" };
+                writeln!(out, "{space}{}{rail} {branch}{} {}{}:{} {}", c.line, c.stop, c.field, name, c.stop, text)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn value_text(&self, value: &Value) -> String {
+        let c = self.colors();
+        match value {
+            Value::String(s) => format!("{}{}{}", c.string, s, c.stop),
+            Value::Char(ch) => format!("{}{:?}{}", c.string, ch, c.stop),
+            Value::None => format!("{}none{}", c.none, c.stop),
+            value => format!("{}{}{}", c.number, value, c.stop),
+        }
+    }
+}
+
+impl Printable for Node {
+    fn print(&self, printer: &Printer, out: &mut dyn Write, depth: usize) -> fmt::Result {
+        let c = printer.colors();
+        if printer.compact {
+            write!(out, "({}{}{}", c.label, self.label, c.stop)?;
+            if printer.fields.kind {
+                write!(out, " {}#{}{}", c.number, self.kind, c.stop)?;
+            }
+            if printer.fields.value && !self.value.is_none() {
+                let mut value = String::new();
+                write_value(&mut value, &self.value);
+                let color = if matches!(self.value, Value::String(_) | Value::Char(_)) { c.string } else { c.number };
+                write!(out, " {}{}{}", color, value, c.stop)?;
+            }
+            if printer.fields.span && self.span.is_known() {
+                write!(out, " {}@{}{}", c.number, self.span, c.stop)?;
+            }
+            match printer.too_deep(depth) && !self.children.is_empty() {
+                true => write!(out, " {}…{}", c.line, c.stop)?,
+                false => {
+                    for child in self.children.iter() {
+                        write!(out, " ")?;
+                        child.print(printer, out, depth + 1)?;
+                    }
+                }
+            }
+            return write!(out, ")");
+        }
+
+        let space = " ".repeat(depth * printer.indent);
+        let pad = " ".repeat(printer.indent);
+        writeln!(out, "{}{}{}", c.label, self.label, c.stop)?;
+        let mut fields = vec![];
+        if printer.fields.value {
+            fields.push(Field::new("value", printer.value_text(&self.value)));
+        }
+        if printer.fields.kind {
+            fields.push(Field::new("kind", format!("{}{}{}", c.number, self.kind, c.stop)));
+        }
+        if printer.fields.span && self.span.is_known() {
+            let span = self.span;
+            fields.push(Field::new("span", format!("{}{} ({}..{}){}", c.number, span, span.start, span.end, c.stop)));
+        }
+        printer.write_fields(out, &space, &fields)?;
+        if printer.too_deep(depth) && !self.children.is_empty() {
+            return writeln!(out, "{space}{pad}{}┌─ … {} children{}", c.line, self.children.len(), c.stop);
+        }
+        for child in &self.children {
+            write!(out, "{space}{pad}{}┌─{} ", c.line, c.stop)?;
+            child.print(printer, out, depth + 1)?;
+        }
+        Ok(())
+    }
+}
+
+impl Printable for VisitorResult {
+    fn print(&self, printer: &Printer, out: &mut dyn Write, depth: usize) -> fmt::Result {
+        let c = printer.colors();
+        if printer.compact {
+            return match self {
+                VisitorResult::Compound(items) if printer.too_deep(depth) && !items.is_empty() => write!(out, "[{}…{}]", c.line, c.stop),
+                VisitorResult::Compound(items) => {
+                    write!(out, "[")?;
+                    for (i, item) in items.iter().enumerate() {
+                        if i > 0 {
+                            write!(out, ", ")?;
+                        }
+                        item.print(printer, out, depth + 1)?;
+                    }
+                    write!(out, "]")
+                }
+                VisitorResult::String(s) => write!(out, "{}{:?}{}", c.string, s, c.stop),
+                VisitorResult::Integer(i) => write!(out, "{}{}{}", c.number, i, c.stop),
+                VisitorResult::Number(n) => write!(out, "{}{:?}{}", c.number, n, c.stop),
+                VisitorResult::Tagged(tag, value) => {
+                    write!(out, "{}{}{}(", c.label, tag, c.stop)?;
+                    value.print(printer, out, depth + 1)?;
+                    write!(out, ")")
+                }
+                VisitorResult::None => write!(out, "{}none{}", c.none, c.stop),
+            };
+        }
+
+        let space = " ".repeat(depth * printer.indent);
+        match self {
+            VisitorResult::Compound(items) => {
+                writeln!(out, "{}compound{}", c.label, c.stop)?;
+                if printer.too_deep(depth) && !items.is_empty() {
+                    return writeln!(out, "{space}{}└─ … {} items{}", c.line, items.len(), c.stop);
+                }
+                for (i, child) in items.iter().enumerate() {
+                    let branch = match child {
+                        VisitorResult::Tagged(_, _) | VisitorResult::Compound(_) => "─",
+                        _ if i < items.len() - 1 => "├─",
+                        _ => "└─",
+                    };
+                    write!(out, "{space}{}{branch}{} ", c.line, c.stop)?;
+                    child.print(printer, out, depth + 1)?;
+                }
+            }
+            VisitorResult::String(s) => writeln!(out, "{}string:{} {}{}{}", c.field, c.stop, c.string, s, c.stop)?,
+            VisitorResult::Integer(i) => writeln!(out, "{}integer:{} {}{}{}", c.field, c.stop, c.number, i, c.stop)?,
+            VisitorResult::Number(n) => writeln!(out, "{}number:{} {}{}{}", c.field, c.stop, c.number, n, c.stop)?,
+            VisitorResult::Tagged(tag, value) => {
+                writeln!(out, "{}{}{}", c.label, tag, c.stop)?;
+                write!(out, "{space}{}└─{} ", c.line, c.stop)?;
+                value.print(printer, out, depth + 1)?;
+            }
+            VisitorResult::None => writeln!(out, "{}none{}", c.none, c.stop)?,
+        }
+        Ok(())
+    }
+}
+
+impl Printable for Token {
+    fn print(&self, printer: &Printer, out: &mut dyn Write, depth: usize) -> fmt::Result {
+        let c = printer.colors();
+        let pattern = match &self.token {
+            TokenValue::Lit(lit) => vec![("lit", format!("{:?}", lit))],
+            TokenValue::Regex(regex) => vec![("regex", regex.as_str().to_string())],
+            TokenValue::URegex(regex) => vec![("uregex", regex.as_str().to_string())],
+            TokenValue::Range(start, end) => vec![("start", format!("{:?}", start)), ("end", format!("{:?}", end))],
+        };
+        if printer.compact {
+            write!(out, "({}{}{}", c.label, self.label, c.stop)?;
+            if printer.fields.kind {
+                write!(out, " {}#{}{}", c.number, self.id, c.stop)?;
+            }
+            if printer.fields.value {
+                for (name, text) in pattern {
+                    write!(out, " {}{}:{} {}{}{}", c.field, name, c.stop, c.string, text, c.stop)?;
+                }
+            }
+            return write!(out, ")");
+        }
+
+        writeln!(out, "{}{}{}", c.label, self.label, c.stop)?;
+        let mut fields = vec![];
+        if printer.fields.kind {
+            fields.push(Field::new("id", format!("{}{}{}", c.number, self.id, c.stop)));
+        }
+        if printer.fields.value {
+            let sub = pattern.into_iter().map(|(name, text)| (name, format!("{}{}{}", c.string, text, c.stop))).collect();
+            fields.push(Field { name: "token", text: String::new(), sub });
+        }
+        printer.write_fields(out, &" ".repeat(depth * printer.indent), &fields)
+    }
+}
+
+impl Printable for TokenData {
+    fn print(&self, printer: &Printer, out: &mut dyn Write, depth: usize) -> fmt::Result {
+        let c = printer.colors();
+        let number = |n: usize| format!("{}{}{}", c.number, n, c.stop);
+        if printer.compact {
+            write!(out, "({}{}{}", c.label, self.label, c.stop)?;
+            if printer.fields.kind {
+                write!(out, " {}#{}{}", c.number, self.kind, c.stop)?;
+            }
+            if printer.fields.value {
+                write!(out, " {}{:?}{}", c.string, self.value, c.stop)?;
+            }
+            if printer.fields.span {
+                write!(out, " {}@{}:{}{}", c.number, self.location.0, self.location.1, c.stop)?;
+            }
+            return write!(out, ")");
+        }
+
+        writeln!(out, "{}{}{}", c.label, self.label, c.stop)?;
+        let mut fields = vec![];
+        if printer.fields.value {
+            fields.push(Field::new("value", format!("{}{}{}", c.string, self.value, c.stop)));
+        }
+        if printer.fields.kind {
+            fields.push(Field::new("kind", number(self.kind as usize)));
+        }
+        if printer.fields.span {
+            let sub = vec![("line", number(self.location.0)), ("column", number(self.location.1))];
+            fields.push(Field { name: "location", text: String::new(), sub });
+            let sub = vec![("start", number(self.span.0)), ("end", number(self.span.1))];
+            fields.push(Field { name: "span", text: String::new(), sub });
+        }
+        printer.write_fields(out, &" ".repeat(depth * printer.indent), &fields)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::span::Span;
+
+    fn tree() -> Node {
+        let mut root = Node::new(3, "Sum", Value::None);
+        let mut inner = Node::new(2, "Paren", Value::None);
+        inner.add_child(Node::new(1, "NUMBER", Value::from("2")).with_span(Span::new(4, 5, 1, 5)));
+        root.add_child(Node::new(1, "NUMBER", Value::from(1)));
+        root.add_child(inner);
+        root
+    }
+
+    fn plain() -> Printer {
+        Printer::new().with_color(ColorChoice::Never)
+    }
+
+    #[test]
+    fn test_tree() {
+        let expected = "\
+Sum
+└┬─ value: none
+ ├─ kind: 3
+ └─ span: 1:5 (4..5)
+    ┌─ NUMBER
+    └┬─ value: 1
+     └─ kind: 1
+    ┌─ Paren
+    └┬─ value: none
+     ├─ kind: 2
+     └─ span: 1:5 (4..5)
+        ┌─ NUMBER
+        └┬─ value: 2
+         ├─ kind: 1
+         └─ span: 1:5 (4..5)
+";
+        assert_eq!(plain().render(&tree()), expected);
+
+        let fields = Fields { value: false, kind: true, span: false };
+        let expected = "Sum\n└─ kind: 3\n  ┌─ NUMBER\n  └─ kind: 1\n  ┌─ Paren\n  └─ kind: 2\n    ┌─ … 1 children\n";
+        assert_eq!(plain().with_fields(fields).with_indent(2).with_max_depth(1).render(&tree()), expected);
+
+        let colored = Printer::new().with_color(ColorChoice::Always).with_theme(Theme::basic()).render(&tree());
+        assert!(colored.starts_with("\x1b[32mSum\x1b[0m\n"));
+        let escapes = regex::Regex::new("\x1b\\[[0-9;]*m").unwrap();
+        assert_eq!(escapes.replace_all(&colored, ""), plain().render(&tree()));
+    }
+
+    #[test]
+    fn test_compact() {
+        let printer = plain().with_compact(true);
+        assert_eq!(printer.render(&tree()), "(Sum #3 @1:5 (NUMBER #1 1) (Paren #2 @1:5 (NUMBER #1 \"2\" @1:5)))");
+        assert_eq!(printer.clone().with_max_depth(1).render(&tree()), "(Sum #3 @1:5 (NUMBER #1 1) (Paren #2 @1:5 …))");
+
+        let result = VisitorResult::Compound(vec![VisitorResult::Integer(1), VisitorResult::Tagged("neg".to_string(), VisitorResult::Number(2.0).into())]);
+        assert_eq!(printer.render(&result), "[1, neg(2.0)]");
+        assert_eq!(format!("{}", printer.display(&Token::new_lit("PLUS", 4, "+"))), "(PLUS #4 lit: \"+\")");
+    }
+
+    #[test]
+    fn test_tokens() {
+        let token = TokenData::new(2, "x".to_string(), "ID".to_string(), (1, 3), (2, 3));
+        let expected = "ID\n└┬─ value: x\n ├─ kind: 2\n ├─ location\n │  ├─ line: 1\n │  └─ column: 3\n └─ span\n    ├─ start: 2\n    └─ end: 3\n";
+        assert_eq!(plain().render(&token), expected);
+        assert_eq!(plain().render(&Token::new_lit("PLUS", 4, "+")), "PLUS\n└┬─ id: 4\n └─ token\n    └─ lit: \"+\"\n");
+    }
+}
@@ -0,0 +1,432 @@
+use std::{collections::VecDeque, fmt::Display};
+
+use regex::Regex;
+
+use crate::{
+    ast::{Node, Value},
+    cursor::DepthFirst,
+    sexp::{Reader, SexpError},
+};
+
+/// a compiled set of patterns selecting nodes of a tree
+///
+/// ```text
+/// (Label "main"                 ; label, optionally `#kind` and a value, `_` matches any label
+///   ... (Instruction) @inst     ; `...` looks at every descendant instead of the children
+///   (#match? @inst "^(add|sub)$"))
+/// [(Register) (Constant)] @arg  ; either pattern
+/// ```
+///
+/// nested patterns match children in order, not necessarily next to each other
+#[derive(Debug, Clone)]
+pub struct Query {
+    patterns: Vec<(Pattern, Vec<Predicate>)>,
+}
+
+#[derive(Debug, Clone)]
+struct Pattern {
+    shape: Shape,
+    captures: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+enum Shape {
+    Node {
+        label: Option<String>,
+        kind: Option<u32>,
+        value: Option<String>,
+        /// (pattern, whether it matches any descendant)
+        children: Vec<(Pattern, bool)>,
+    },
+    Choice(Vec<Pattern>),
+}
+
+#[derive(Debug, Clone)]
+struct Predicate {
+    capture: String,
+    argument: Argument,
+    negated: bool,
+}
+
+#[derive(Debug, Clone)]
+enum Argument {
+    Capture(String),
+    Text(String),
+    Regex(Regex),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub message: String,
+    /// (line, column) of the error
+    pub location: (usize, usize),
+}
+
+impl Display for QueryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}:{}", self.message, self.location.0, self.location.1)
+    }
+}
+
+impl std::error::Error for QueryError {}
+
+impl From<SexpError> for QueryError {
+    fn from(error: SexpError) -> Self {
+        QueryError { message: error.message, location: error.location }
+    }
+}
+
+/// one way a pattern matched, `node` is where the pattern starts
+#[derive(Debug, Clone)]
+pub struct Match<'a> {
+    pub pattern: usize,
+    pub node: &'a Node,
+    pub captures: Vec<(String, &'a Node)>,
+}
+
+impl<'a> Match<'a> {
+    /// the first node captured with `name`
+    pub fn get(&self, name: &str) -> Option<&'a Node> {
+        self.captures.iter().find(|(capture, _)| capture == name).map(|(_, node)| *node)
+    }
+}
+
+type Captures<'a> = Vec<(String, &'a Node)>;
+
+/// text of a value compared by predicates and value patterns
+fn text(value: &Value) -> String {
+    match value {
+        Value::None => String::new(),
+        value => value.to_string(),
+    }
+}
+
+impl Query {
+    pub fn new(source: &str) -> Result<Self, QueryError> {
+        let mut reader = Reader { text: source, position: 0 };
+        let mut patterns = vec![];
+        reader.skip();
+        while reader.peek().is_some() {
+            let mut predicates = vec![];
+            let pattern = reader.pattern(&mut predicates)?;
+            let mut names = vec![];
+            pattern.capture_names(&mut names);
+            for predicate in predicates.iter() {
+                let mut used = vec![&predicate.capture];
+                if let Argument::Capture(other) = &predicate.argument {
+                    used.push(other);
+                }
+                if let Some(missing) = used.into_iter().find(|name| !names.contains(name)) {
+                    return Err(reader.error(format!("Predicate uses `@{}` which the pattern does not capture", missing)).into());
+                }
+            }
+            patterns.push((pattern, predicates));
+            reader.skip();
+        }
+        match patterns.is_empty() {
+            true => Err(reader.error("Query has no patterns".to_string()).into()),
+            false => Ok(Self { patterns }),
+        }
+    }
+
+    /// names of every capture of every pattern
+    pub fn capture_names(&self) -> Vec<String> {
+        let mut names = vec![];
+        for (pattern, _) in self.patterns.iter() {
+            pattern.capture_names(&mut names);
+        }
+        names
+    }
+
+    /// every match of every pattern at `root` and the nodes under it, in depth first order
+    pub fn matches<'q, 'a>(&'q self, root: &'a Node) -> Matches<'q, 'a> {
+        Matches { query: self, nodes: root.cursor().depth_first(), pending: VecDeque::new() }
+    }
+
+    /// the nodes captured with `name` in every match, in order and without repeats
+    pub fn captures<'a>(&self, root: &'a Node, name: &str) -> Vec<&'a Node> {
+        let mut nodes: Vec<&Node> = vec![];
+        for found in self.matches(root) {
+            for (capture, node) in found.captures {
+                if capture == name && !nodes.iter().any(|seen| std::ptr::eq(*seen, node)) {
+                    nodes.push(node);
+                }
+            }
+        }
+        nodes
+    }
+
+    /// the matches of patterns starting exactly at `node`
+    pub fn matches_at<'a>(&self, node: &'a Node) -> Vec<Match<'a>> {
+        let mut found = vec![];
+        for (index, (pattern, predicates)) in self.patterns.iter().enumerate() {
+            for captures in pattern.matches(node, vec![]) {
+                if predicates.iter().all(|predicate| predicate.holds(&captures)) {
+                    found.push(Match { pattern: index, node, captures });
+                }
+            }
+        }
+        found
+    }
+}
+
+pub struct Matches<'q, 'a> {
+    query: &'q Query,
+    nodes: DepthFirst<'a>,
+    pending: VecDeque<Match<'a>>,
+}
+
+impl<'a> Iterator for Matches<'_, 'a> {
+    type Item = Match<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while self.pending.is_empty() {
+            let cursor = self.nodes.next()?;
+            self.pending.extend(self.query.matches_at(cursor.node()));
+        }
+        self.pending.pop_front()
+    }
+}
+
+impl Pattern {
+    fn capture_names(&self, names: &mut Vec<String>) {
+        names.extend(self.captures.iter().cloned());
+        match &self.shape {
+            Shape::Node { children, .. } => children.iter().for_each(|(child, _)| child.capture_names(names)),
+            Shape::Choice(alternatives) => alternatives.iter().for_each(|alternative| alternative.capture_names(names)),
+        }
+    }
+
+    /// every way the pattern matches `node`, each extending `captures`
+    fn matches<'a>(&self, node: &'a Node, captures: Captures<'a>) -> Vec<Captures<'a>> {
+        let mut results: Vec<Captures<'a>> = match &self.shape {
+            Shape::Choice(alternatives) => alternatives.iter().flat_map(|alternative| alternative.matches(node, captures.clone())).collect(),
+            Shape::Node { label, kind, value, children } => {
+                let fits = label.as_ref().is_none_or(|label| *label == node.label)
+                    && kind.is_none_or(|kind| kind == node.kind)
+                    && value.as_ref().is_none_or(|value| !node.value.is_none() && *value == text(&node.value));
+                if !fits {
+                    return vec![];
+                }
+                // (captures so far, index of the first child the next pattern may use)
+                let mut states = vec![(captures, 0)];
+                for (child, descendant) in children {
+                    let mut next = vec![];
+                    for (captures, first) in states {
+                        match descendant {
+                            true => {
+                                for below in node.descendants().skip(1) {
+                                    next.extend(child.matches(below, captures.clone()).into_iter().map(|captures| (captures, first)));
+                                }
+                            }
+                            false => {
+                                for (index, below) in node.children.iter().enumerate().skip(first) {
+                                    next.extend(child.matches(below, captures.clone()).into_iter().map(|captures| (captures, index + 1)));
+                                }
+                            }
+                        }
+                    }
+                    states = next;
+                }
+                states.into_iter().map(|(captures, _)| captures).collect()
+            }
+        };
+        for result in results.iter_mut() {
+            result.extend(self.captures.iter().map(|name| (name.clone(), node)));
+        }
+        results
+    }
+}
+
+impl Predicate {
+    fn holds(&self, captures: &Captures) -> bool {
+        let lookup = |name: &str| captures.iter().find(|(capture, _)| capture == name).map(|(_, node)| text(&node.value));
+        let Some(value) = lookup(&self.capture) else { return false };
+        let result = match &self.argument {
+            Argument::Text(text) => value == *text,
+            Argument::Capture(other) => lookup(other).is_some_and(|other| value == other),
+            Argument::Regex(regex) => regex.is_match(&value),
+        };
+        result != self.negated
+    }
+}
+
+/// queries are read with the S-expression reader, so strings and comments work the same way
+impl Reader<'_> {
+    fn word(&mut self) -> Result<String, SexpError> {
+        self.symbol_until("()[]\"@;").map_err(|_| self.error("Expected a name".to_string()))
+    }
+
+    fn capture(&mut self) -> Result<String, SexpError> {
+        self.expect('@')?;
+        self.word()
+    }
+
+    /// a pattern with its captures, predicates inside it are added to `predicates`
+    fn pattern(&mut self, predicates: &mut Vec<Predicate>) -> Result<Pattern, SexpError> {
+        self.skip();
+        let shape = match self.peek() {
+            Some('[') => {
+                self.bump();
+                let mut alternatives = vec![];
+                while { self.skip(); self.peek().is_some_and(|c| c != ']') } {
+                    alternatives.push(self.pattern(predicates)?);
+                }
+                self.expect(']')?;
+                if alternatives.is_empty() {
+                    return Err(self.error("Empty alternation".to_string()));
+                }
+                Shape::Choice(alternatives)
+            }
+            Some('(') => self.node_shape(predicates)?,
+            Some(c) => return Err(self.error(format!("Expected a pattern but found `{}`", c))),
+            None => return Err(self.error("Expected a pattern but reached the end".to_string())),
+        };
+        let mut captures = vec![];
+        while { self.skip(); self.peek() == Some('@') } {
+            captures.push(self.capture()?);
+        }
+        Ok(Pattern { shape, captures })
+    }
+
+    fn node_shape(&mut self, predicates: &mut Vec<Predicate>) -> Result<Shape, SexpError> {
+        self.expect('(')?;
+        self.skip();
+        let label = match self.word()? {
+            label if label == "_" => None,
+            label if label.starts_with('#') => return Err(self.error(format!("Predicate `{}` must be inside a pattern", label))),
+            label => Some(label),
+        };
+        self.skip();
+        let mut kind = None;
+        if self.peek() == Some('#') {
+            let word = self.word()?;
+            kind = Some(word[1..].parse().map_err(|_| self.error(format!("`{}` is not a valid kind", word)))?);
+            self.skip();
+        }
+        let value = match self.peek() {
+            Some('"') => Some(self.string()?),
+            _ => None,
+        };
+        let mut children = vec![];
+        loop {
+            self.skip();
+            match self.peek() {
+                Some(')') => break,
+                Some('(') if self.text[self.position + 1..].trim_start().starts_with('#') => predicates.push(self.predicate()?),
+                Some('.') => {
+                    let start = self.position;
+                    let word = self.word()?;
+                    if word != "..." {
+                        self.position = start;
+                        return Err(self.error(format!("Unknown `{}`, descendants are written `...`", word)));
+                    }
+                    children.push((self.pattern(predicates)?, true));
+                }
+                _ => children.push((self.pattern(predicates)?, false)),
+            }
+        }
+        self.expect(')')?;
+        Ok(Shape::Node { label, kind, value, children })
+    }
+
+    fn predicate(&mut self) -> Result<Predicate, SexpError> {
+        self.expect('(')?;
+        self.skip();
+        let start = self.position;
+        let name = self.word()?;
+        let (negated, regex) = match name.as_str() {
+            "#eq?" => (false, false),
+            "#not-eq?" => (true, false),
+            "#match?" => (false, true),
+            "#not-match?" => (true, true),
+            _ => {
+                self.position = start;
+                return Err(self.error(format!("Unknown predicate `{}`", name)));
+            }
+        };
+        self.skip();
+        let capture = self.capture()?;
+        self.skip();
+        let argument = match (self.peek(), regex) {
+            (Some('@'), false) => Argument::Capture(self.capture()?),
+            (Some('"'), false) => Argument::Text(self.string()?),
+            (Some('"'), true) => {
+                let pattern = self.string()?;
+                Argument::Regex(Regex::new(&pattern).map_err(|e| self.error(format!("Invalid regex: {}", e)))?)
+            }
+            _ => return Err(self.error(format!("`{}` takes a capture and a {}", name, if regex { "regex" } else { "string or capture" }))),
+        };
+        self.expect(')')?;
+        Ok(Predicate { capture, argument, negated })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sexp::from_sexp;
+
+    fn program() -> Node {
+        from_sexp("
+            (Program
+              (Label \"main\"
+                (Instruction \"add\" (Register \"1\") (Constant #7 10))
+                (Label \"loop\"
+                  (Instruction \"sub\" (Register \"2\") (Register \"1\"))))
+              (Label \"other\"
+                (Instruction \"add\" (Register \"3\") (Register \"3\"))))
+        ").unwrap()
+    }
+
+    fn values(nodes: Vec<&Node>) -> Vec<String> {
+        nodes.into_iter().map(|node| node.value.to_string()).collect()
+    }
+
+    #[test]
+    fn test_descendants_and_children() {
+        let program = program();
+        let query = Query::new("(Label \"main\" ... (Instruction) @inst)").unwrap();
+        assert_eq!(values(query.captures(&program, "inst")), vec!["add", "sub"]);
+
+        // children in order, a register followed later by a constant
+        let query = Query::new("(Instruction (Register) @reg (_ #7) @constant) @inst").unwrap();
+        let found = query.matches(&program).collect::<Vec<_>>();
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].get("constant").map(|node| node.value.to_string()), Some("10".to_string()));
+        assert!(std::ptr::eq(found[0].node, found[0].get("inst").unwrap()));
+
+        // one match for every way to bind the captures
+        let query = Query::new("(Instruction (Register) @reg)").unwrap();
+        assert_eq!(query.matches(&program).count(), 5);
+    }
+
+    #[test]
+    fn test_predicates_and_alternatives() {
+        let program = program();
+        let query = Query::new("(Instruction (Register) @a (Register) @b (#eq? @a @b))").unwrap();
+        assert_eq!(values(query.captures(&program, "a")), vec!["3"]);
+
+        let query = Query::new("
+            ; instructions with a constant or named `sub`
+            (Instruction [(Constant) (Register \"2\")] @arg) @inst
+            (Label (#not-match? @label \"^m\")) @label
+        ").unwrap();
+        let found = query.matches(&program).collect::<Vec<_>>();
+        let summary = found.iter().map(|found| (found.pattern, found.node.value.to_string())).collect::<Vec<_>>();
+        assert_eq!(summary, vec![(0, "add".to_string()), (1, "loop".to_string()), (0, "sub".to_string()), (1, "other".to_string())]);
+    }
+
+    #[test]
+    fn test_query_errors() {
+        let error = |source: &str| Query::new(source).unwrap_err().to_string();
+        assert_eq!(error("(Label"), "Expected a pattern but reached the end at 1:7");
+        assert_eq!(error("(Label @x)"), "Expected a pattern but found `@` at 1:8");
+        assert_eq!(error("(A (#eq? @x \"a\"))"), "Predicate uses `@x` which the pattern does not capture at 1:18");
+        assert_eq!(error("(A (#same? @x \"a\"))"), "Unknown predicate `#same?` at 1:5");
+        assert_eq!(error("(A .. (B))"), "Unknown `..`, descendants are written `...` at 1:4");
+        assert_eq!(error("  "), "Query has no patterns at 1:3");
+        assert_eq!(error("(A \"\\q\")"), "Unknown escape `\\q` at 1:7");
+        assert_eq!(error("(A) @"), "Expected a name at 1:6");
+    }
+}
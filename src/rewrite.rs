@@ -0,0 +1,365 @@
+use std::{fmt::Display, sync::Arc};
+
+use crate::{
+    ast::{Node, Value},
+    query::{Match, Query, QueryError},
+    sexp::{Reader, SexpError},
+    span::Span,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RewriteError {
+    Pattern(QueryError),
+    Template(SexpError),
+    /// the template uses a capture the pattern does not have
+    UnknownCapture(String),
+    /// rules kept firing for the given number of passes
+    NoFixpoint { passes: usize },
+}
+
+impl Display for RewriteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RewriteError::Pattern(e) => write!(f, "Invalid pattern: {}", e),
+            RewriteError::Template(e) => write!(f, "Invalid template: {}", e),
+            RewriteError::UnknownCapture(name) => write!(f, "Template uses `@{}` which the pattern does not capture", name),
+            RewriteError::NoFixpoint { passes } => write!(f, "Rewriting did not settle after {} passes", passes),
+        }
+    }
+}
+
+impl std::error::Error for RewriteError {}
+
+/// the right side of a rule, an S-expression tree like the ones of `sexp` with holes for captures
+///
+/// `@x` is the captured node, `@x.children` its children inside a node and `@x.value`, in place of a
+/// value, its value; nodes without `#kind` take the kind of the node they replace
+#[derive(Debug, Clone)]
+enum Template {
+    Node { label: String, kind: Option<u32>, value: TemplateValue, children: Vec<Template> },
+    Capture(String),
+    Children(String),
+}
+
+#[derive(Debug, Clone)]
+enum TemplateValue {
+    Literal(Value),
+    Capture(String),
+}
+
+type Build = Box<dyn Fn(&Match) -> Node>;
+type Guard = Box<dyn Fn(&Match) -> bool>;
+
+enum Output {
+    Template(Template),
+    Build(Build),
+}
+
+/// a named rewrite from a pattern to a template or a closure building the new node
+pub struct Rule {
+    pub name: String,
+    pattern: Query,
+    output: Output,
+    guards: Vec<Guard>,
+}
+
+impl Rule {
+    pub fn new(name: &str, pattern: &str, template: &str) -> Result<Self, RewriteError> {
+        let pattern = Query::new(pattern).map_err(RewriteError::Pattern)?;
+        let template = parse_template(template).map_err(RewriteError::Template)?;
+        let names = pattern.capture_names();
+        let mut used = vec![];
+        template.captures(&mut used);
+        if let Some(missing) = used.into_iter().find(|name| !names.contains(name)) {
+            return Err(RewriteError::UnknownCapture(missing));
+        }
+        Ok(Self { name: name.to_string(), pattern, output: Output::Template(template), guards: vec![] })
+    }
+
+    /// a rule whose replacement is built by `build` from the match
+    pub fn from_fn(name: &str, pattern: &str, build: impl Fn(&Match) -> Node + 'static) -> Result<Self, RewriteError> {
+        let pattern = Query::new(pattern).map_err(RewriteError::Pattern)?;
+        Ok(Self { name: name.to_string(), pattern, output: Output::Build(Box::new(build)), guards: vec![] })
+    }
+
+    /// the rule only fires for matches the guard accepts, guards add up
+    pub fn when(mut self, guard: impl Fn(&Match) -> bool + 'static) -> Self {
+        self.guards.push(Box::new(guard));
+        self
+    }
+
+    /// the replacement of `node` by the first accepted match, if any
+    fn apply(&self, node: &Node) -> Option<Node> {
+        let found = self.pattern.matches_at(node).into_iter().find(|found| self.guards.iter().all(|guard| guard(found)))?;
+        let mut replaced = match &self.output {
+            // a capture left out by an alternation gives nothing, then the rule does not fire
+            Output::Template(template) => template.build(&found, node.kind).pop()?,
+            Output::Build(build) => build(&found),
+        };
+        if !replaced.span.is_known() {
+            replaced.span = node.span;
+        }
+        Some(replaced)
+    }
+}
+
+impl Template {
+    fn captures(&self, names: &mut Vec<String>) {
+        match self {
+            Template::Node { value, children, .. } => {
+                if let TemplateValue::Capture(name) = value {
+                    names.push(name.clone());
+                }
+                children.iter().for_each(|child| child.captures(names));
+            }
+            Template::Capture(name) | Template::Children(name) => names.push(name.clone()),
+        }
+    }
+
+    /// the nodes the template stands for, captures that did not match give nothing
+    fn build(&self, found: &Match, kind: u32) -> Vec<Node> {
+        match self {
+            Template::Node { label, kind: own, value, children } => {
+                let value = match value {
+                    TemplateValue::Literal(value) => value.clone(),
+                    TemplateValue::Capture(name) => found.get(name).map_or(Value::None, |node| node.value.clone()),
+                };
+                let mut node = Node::new(own.unwrap_or(kind), label, value);
+                for child in children {
+                    for built in child.build(found, kind) {
+                        node.add_child(built);
+                    }
+                }
+                vec![node]
+            }
+            Template::Capture(name) => found.get(name).cloned().into_iter().collect(),
+            Template::Children(name) => found.get(name).map_or(vec![], |node| node.clone().into_children()),
+        }
+    }
+}
+
+fn parse_template(text: &str) -> Result<Template, SexpError> {
+    let mut reader = Reader { text, position: 0 };
+    reader.skip();
+    let start = reader.position;
+    let template = template(&mut reader)?;
+    if let Template::Children(name) = &template {
+        reader.position = start;
+        return Err(reader.error(format!("`@{}.children` can be any number of nodes, put it inside a node to make one", name)));
+    }
+    reader.skip();
+    match reader.peek() {
+        Some(c) => Err(reader.error(format!("Unexpected `{}` after the template", c))),
+        None => Ok(template),
+    }
+}
+
+/// `@name` with an optional `.children` or `.value`
+fn capture(reader: &mut Reader) -> Result<(String, Option<String>), SexpError> {
+    reader.expect('@')?;
+    let word = reader.symbol()?;
+    Ok(match word.split_once('.') {
+        Some((name, field)) => (name.to_string(), Some(field.to_string())),
+        None => (word, None),
+    })
+}
+
+fn template(reader: &mut Reader) -> Result<Template, SexpError> {
+    reader.skip();
+    if reader.peek() == Some('@') {
+        return match capture(reader)? {
+            (name, None) => Ok(Template::Capture(name)),
+            (name, Some(field)) if field == "children" => Ok(Template::Children(name)),
+            (_, Some(field)) => Err(reader.error(format!("Unknown `.{}`, a child can be `@x` or `@x.children`", field))),
+        };
+    }
+    reader.expect('(')?;
+    reader.skip();
+    let label = match reader.peek() {
+        Some('"') => reader.string()?,
+        _ => reader.symbol()?,
+    };
+    reader.skip();
+    let mut kind = None;
+    if reader.peek() == Some('#') {
+        reader.bump();
+        let word = reader.symbol()?;
+        kind = Some(word.parse().map_err(|_| reader.error(format!("`{}` is not a valid kind", word)))?);
+        reader.skip();
+    }
+    let value = match reader.peek() {
+        Some('(') | Some(')') => TemplateValue::Literal(Value::None),
+        Some('@') => {
+            let start = reader.position;
+            match capture(reader)? {
+                (name, Some(field)) if field == "value" => TemplateValue::Capture(name),
+                // a capture that is not a value is the first child
+                _ => {
+                    reader.position = start;
+                    TemplateValue::Literal(Value::None)
+                }
+            }
+        }
+        _ => TemplateValue::Literal(reader.value()?),
+    };
+    let mut children = vec![];
+    while { reader.skip(); reader.peek().is_some_and(|c| c != ')') } {
+        children.push(template(reader)?);
+    }
+    reader.expect(')')?;
+    Ok(Template::Node { label, kind, value, children })
+}
+
+/// where and when a rule fired
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Step {
+    pub rule: String,
+    /// child indices from the root to the replaced node, in the tree of that pass
+    pub path: Vec<usize>,
+    pub span: Span,
+    /// starting from 1
+    pub pass: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Rewritten {
+    pub node: Node,
+    pub trace: Vec<Step>,
+}
+
+/// applies rules bottom up, the first rule that fires on a node wins
+pub struct Rewriter {
+    rules: Vec<Rule>,
+    max_passes: usize,
+}
+
+impl Default for Rewriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Rewriter {
+    pub fn new() -> Self {
+        Self { rules: vec![], max_passes: 100 }
+    }
+
+    /// adds a rule after the existing ones
+    pub fn rule(mut self, rule: Rule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// passes `rewrite` makes before giving up
+    pub fn with_max_passes(mut self, passes: usize) -> Self {
+        self.max_passes = passes;
+        self
+    }
+
+    /// one pass, every node is rewritten at most once after its children
+    pub fn rewrite_once(&self, node: &Node) -> Rewritten {
+        let mut trace = vec![];
+        let node = self.pass(node, &mut vec![], 1, &mut trace).unwrap_or_else(|| node.clone());
+        Rewritten { node, trace }
+    }
+
+    /// passes until no rule fires anymore
+    pub fn rewrite(&self, node: &Node) -> Result<Rewritten, RewriteError> {
+        let mut trace = vec![];
+        let mut current = node.clone();
+        for pass in 1..=self.max_passes {
+            match self.pass(&current, &mut vec![], pass, &mut trace) {
+                Some(next) => current = next,
+                None => return Ok(Rewritten { node: current, trace }),
+            }
+        }
+        Err(RewriteError::NoFixpoint { passes: self.max_passes })
+    }
+
+    /// the rewritten node, `None` when nothing under it changed
+    fn pass(&self, node: &Node, path: &mut Vec<usize>, pass: usize, trace: &mut Vec<Step>) -> Option<Node> {
+        let mut changed: Option<Node> = None;
+        for (index, child) in node.children.iter().enumerate() {
+            path.push(index);
+            if let Some(rewritten) = self.pass(child, path, pass, trace) {
+                changed.get_or_insert_with(|| node.clone()).children[index] = Arc::new(rewritten);
+            }
+            path.pop();
+        }
+        let current = changed.as_ref().unwrap_or(node);
+        for rule in self.rules.iter() {
+            if let Some(replaced) = rule.apply(current) {
+                trace.push(Step { rule: rule.name.clone(), path: path.clone(), span: current.span, pass });
+                return Some(replaced);
+            }
+        }
+        changed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sexp::{from_sexp, to_sexp};
+
+    fn show(node: &Node) -> String {
+        to_sexp(node, false).split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn test_templates() {
+        // `a - b` into `a + -b`, and `--x` into `x`
+        let rewriter = Rewriter::new()
+            .rule(Rule::new("double negation", "(Prefix \"-\" (Prefix \"-\" (_) @x))", "@x").unwrap())
+            .rule(Rule::new("subtraction", "(Binary \"-\" (_) @a (_) @b)", "(Binary \"+\" @a (Prefix \"-\" @b))").unwrap());
+        let tree = from_sexp("(Binary \"-\" (Num 1) (Prefix \"-\" (Num 2)))").unwrap();
+
+        let once = rewriter.rewrite_once(&tree);
+        assert_eq!(show(&once.node), "(Binary \"+\" (Num 1) (Prefix \"-\" (Prefix \"-\" (Num 2))))");
+
+        let done = rewriter.rewrite(&tree).unwrap();
+        assert_eq!(show(&done.node), "(Binary \"+\" (Num 1) (Num 2))");
+        let steps = done.trace.iter().map(|step| (step.rule.as_str(), step.path.clone(), step.pass)).collect::<Vec<_>>();
+        assert_eq!(steps, vec![("subtraction", vec![], 1), ("double negation", vec![1], 2)]);
+    }
+
+    #[test]
+    fn test_splices_values_and_guards() {
+        let tree = from_sexp("(Seq (Block (Block (Let \"x\" (Num 1)) (Num 30))) (Let \"y\" (Num 2)))").unwrap();
+        let rewriter = Rewriter::new()
+            .rule(Rule::new("flatten", "(Block (Block) @inner)", "(Block @inner.children)").unwrap())
+            .rule(Rule::new("assign", "(Let (Num) @n) @let", "(Assign #9 @let.value @n)").unwrap())
+            .rule(
+                Rule::from_fn("big numbers", "(Num) @n", |found| Node::new(0, "Big", found.get("n").unwrap().value.clone()))
+                    .unwrap()
+                    .when(|found| found.get("n").and_then(|n| n.value.as_integer()).is_some_and(|n| n > 10)),
+            );
+        let done = rewriter.rewrite(&tree).unwrap();
+        // children are rewritten before the block that takes them over
+        assert_eq!(show(&done.node), "(Seq (Block (Assign \"x\" (Num 1)) (Big 30)) (Assign \"y\" (Num 2)))");
+        assert_eq!(done.node.children[1].kind, 9);
+        assert_eq!(done.trace.len(), 4);
+    }
+
+    #[test]
+    fn test_errors() {
+        let error = |result: Result<Rule, RewriteError>| result.err().unwrap().to_string();
+        assert_eq!(error(Rule::new("r", "(A) @a", "(B @b)")), "Template uses `@b` which the pattern does not capture");
+        assert_eq!(error(Rule::new("r", "(A) @a", "(B @a.kind)")), "Invalid template: Unknown `.kind`, a child can be `@x` or `@x.children` at 1:11");
+        assert!(error(Rule::new("r", "(A", "(B)")).starts_with("Invalid pattern"));
+        assert_eq!(
+            error(Rule::new("r", "(Block) @b", " @b.children")),
+            "Invalid template: `@b.children` can be any number of nodes, put it inside a node to make one at 1:2"
+        );
+
+        // `@a` is missing when the second alternative matched, so the rule leaves `(B)` alone
+        let rewriter = Rewriter::new().rule(Rule::new("r", "[(A) @a (B)]", "@a").unwrap());
+        let done = rewriter.rewrite(&from_sexp("(Root (B))").unwrap()).unwrap();
+        assert_eq!(show(&done.node), "(Root (B))");
+        assert!(done.trace.is_empty());
+
+        let rewriter = Rewriter::new().with_max_passes(3).rule(Rule::new("grow", "(A) @a", "(A @a)").unwrap());
+        let result = rewriter.rewrite(&from_sexp("(A)").unwrap());
+        assert_eq!(result.err(), Some(RewriteError::NoFixpoint { passes: 3 }));
+    }
+}
@@ -0,0 +1,355 @@
+use std::fmt::{Display, Write};
+
+use crate::ast::{Node, Value};
+
+/// writes `node` as an S-expression, one node per line
+///
+/// `(Label #kind value children...)`, the kind only when `kinds` is set and
+/// the value only when there is one, written as `"text"`, `10`, `1.5`, `true`,
+/// `'c'`, `[values...]` or `{"key" value...}`
+pub fn to_sexp(node: &Node, kinds: bool) -> String {
+    let mut out = String::new();
+    write_node(&mut out, node, kinds, 0);
+    out
+}
+
+fn write_node(out: &mut String, node: &Node, kinds: bool, depth: usize) {
+    out.push('(');
+    write_symbol(out, &node.label);
+    if kinds {
+        write!(out, " #{}", node.kind).unwrap();
+    }
+    if !node.value.is_none() {
+        out.push(' ');
+        write_value(out, &node.value);
+    }
+    for child in node.children.iter() {
+        out.push('\n');
+        out.push_str(&"  ".repeat(depth + 1));
+        write_node(out, child, kinds, depth + 1);
+    }
+    out.push(')');
+}
+
+pub(crate) fn write_value(out: &mut String, value: &Value) {
+    match value {
+        Value::String(s) => write_string(out, s),
+        Value::Integer(i) => write!(out, "{}", i).unwrap(),
+        Value::Float(f) if f.is_nan() => out.push_str("nan"),
+        Value::Float(f) if f.is_infinite() => out.push_str(if *f > 0.0 { "inf" } else { "-inf" }),
+        // debug formatting always keeps a `.` or an exponent
+        Value::Float(f) => write!(out, "{:?}", f).unwrap(),
+        Value::Bool(b) => write!(out, "{}", b).unwrap(),
+        Value::Char(c) => {
+            out.push('\'');
+            match c {
+                '\'' => out.push_str("\\'"),
+                '"' => out.push('"'),
+                c => write_escaped(out, *c),
+            }
+            out.push('\'');
+        }
+        Value::List(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_value(out, item);
+            }
+            out.push(']');
+        }
+        Value::Map(entries) => {
+            out.push('{');
+            for (i, (key, value)) in entries.iter().enumerate() {
+                if i > 0 {
+                    out.push(' ');
+                }
+                write_string(out, key);
+                out.push(' ');
+                write_value(out, value);
+            }
+            out.push('}');
+        }
+        Value::None => out.push_str("none"),
+    }
+}
+
+/// labels are written bare unless they need quoting
+fn write_symbol(out: &mut String, symbol: &str) {
+    let bare = !symbol.is_empty() && !symbol.starts_with('#') && symbol.chars().all(|c| !c.is_whitespace() && !"()\";".contains(c));
+    match bare {
+        true => out.push_str(symbol),
+        false => write_string(out, symbol),
+    }
+}
+
+fn write_string(out: &mut String, text: &str) {
+    out.push('"');
+    for c in text.chars() {
+        write_escaped(out, c);
+    }
+    out.push('"');
+}
+
+fn write_escaped(out: &mut String, c: char) {
+    match c {
+        '"' => out.push_str("\\\""),
+        '\\' => out.push_str("\\\\"),
+        '\n' => out.push_str("\\n"),
+        '\r' => out.push_str("\\r"),
+        '\t' => out.push_str("\\t"),
+        c => out.push(c),
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SexpError {
+    pub message: String,
+    /// (line, column) of the error
+    pub location: (usize, usize),
+}
+
+impl Display for SexpError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}:{}", self.message, self.location.0, self.location.1)
+    }
+}
+
+impl std::error::Error for SexpError {}
+
+/// reads a node written by `to_sexp`, `;` starts a comment and missing kinds are 0
+pub fn from_sexp(text: &str) -> Result<Node, SexpError> {
+    let mut reader = Reader { text, position: 0 };
+    let node = reader.node()?;
+    reader.skip();
+    match reader.peek() {
+        Some(c) => Err(reader.error(format!("Unexpected `{}` after the tree", c))),
+        None => Ok(node),
+    }
+}
+
+/// reads the pieces of S-expressions, shared with the templates of `rewrite`
+pub(crate) struct Reader<'t> {
+    pub(crate) text: &'t str,
+    pub(crate) position: usize,
+}
+
+impl Reader<'_> {
+    pub(crate) fn peek(&self) -> Option<char> {
+        self.text[self.position..].chars().next()
+    }
+
+    pub(crate) fn bump(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.position += c.len_utf8();
+        Some(c)
+    }
+
+    pub(crate) fn error(&self, message: String) -> SexpError {
+        let before = &self.text[..self.position];
+        let line = before.matches('\n').count() + 1;
+        let column = before.chars().rev().take_while(|&c| c != '\n').count() + 1;
+        SexpError { message, location: (line, column) }
+    }
+
+    /// skips whitespace and comments
+    pub(crate) fn skip(&mut self) {
+        while let Some(c) = self.peek() {
+            match c {
+                ';' => {
+                    while self.peek().is_some_and(|c| c != '\n') {
+                        self.bump();
+                    }
+                }
+                c if c.is_whitespace() => {
+                    self.bump();
+                }
+                _ => break,
+            }
+        }
+    }
+
+    pub(crate) fn expect(&mut self, expected: char) -> Result<(), SexpError> {
+        self.skip();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.bump();
+                Ok(())
+            }
+            Some(c) => Err(self.error(format!("Expected `{}` but found `{}`", expected, c))),
+            None => Err(self.error(format!("Expected `{}` but reached the end", expected))),
+        }
+    }
+
+    fn node(&mut self) -> Result<Node, SexpError> {
+        self.expect('(')?;
+        self.skip();
+        let label = match self.peek() {
+            Some('"') => self.string()?,
+            _ => self.symbol()?,
+        };
+        let mut node = Node::new(0, &label, Value::None);
+        self.skip();
+        if self.peek() == Some('#') {
+            self.bump();
+            let kind = self.symbol()?;
+            node.kind = kind.parse().map_err(|_| self.error(format!("`{}` is not a valid kind", kind)))?;
+            self.skip();
+        }
+        if self.peek().is_some_and(|c| c != '(' && c != ')') {
+            node.value = self.value()?;
+            self.skip();
+        }
+        while self.peek() == Some('(') {
+            node.add_child(self.node()?);
+            self.skip();
+        }
+        self.expect(')')?;
+        Ok(node)
+    }
+
+    pub(crate) fn value(&mut self) -> Result<Value, SexpError> {
+        self.skip();
+        match self.peek() {
+            Some('"') => Ok(Value::String(self.string()?)),
+            Some('\'') => {
+                self.bump();
+                let c = match self.bump() {
+                    Some('\\') => self.escape()?,
+                    Some(c) => c,
+                    None => return Err(self.error("Unterminated char".to_string())),
+                };
+                self.expect('\'')?;
+                Ok(Value::Char(c))
+            }
+            Some('[') => {
+                self.bump();
+                let mut items = vec![];
+                while { self.skip(); self.peek() != Some(']') } {
+                    items.push(self.value()?);
+                }
+                self.expect(']')?;
+                Ok(Value::List(items))
+            }
+            Some('{') => {
+                self.bump();
+                let mut entries = std::collections::BTreeMap::new();
+                while { self.skip(); self.peek() != Some('}') } {
+                    let key = self.string()?;
+                    entries.insert(key, self.value()?);
+                }
+                self.expect('}')?;
+                Ok(Value::Map(entries))
+            }
+            _ => {
+                let atom = self.symbol_until("()[]{}\";")?;
+                match atom.as_str() {
+                    "true" => Ok(Value::Bool(true)),
+                    "false" => Ok(Value::Bool(false)),
+                    "none" => Ok(Value::None),
+                    "nan" => Ok(Value::Float(f64::NAN)),
+                    "inf" => Ok(Value::Float(f64::INFINITY)),
+                    "-inf" => Ok(Value::Float(f64::NEG_INFINITY)),
+                    _ if atom.contains(['.', 'e', 'E']) => atom.parse().map(Value::Float).map_err(|_| self.error(format!("`{}` is not a valid value", atom))),
+                    _ => atom.parse().map(Value::Integer).map_err(|_| self.error(format!("`{}` is not a valid value", atom))),
+                }
+            }
+        }
+    }
+
+    pub(crate) fn symbol(&mut self) -> Result<String, SexpError> {
+        self.symbol_until("()\";")
+    }
+
+    pub(crate) fn symbol_until(&mut self, stops: &str) -> Result<String, SexpError> {
+        let start = self.position;
+        while self.peek().is_some_and(|c| !c.is_whitespace() && !stops.contains(c)) {
+            self.bump();
+        }
+        match start == self.position {
+            true => Err(self.error("Expected a label".to_string())),
+            false => Ok(self.text[start..self.position].to_string()),
+        }
+    }
+
+    /// the character after a `\\`
+    fn escape(&mut self) -> Result<char, SexpError> {
+        match self.bump() {
+            Some('n') => Ok('\n'),
+            Some('r') => Ok('\r'),
+            Some('t') => Ok('\t'),
+            Some(c @ ('"' | '\\' | '\'')) => Ok(c),
+            Some(c) => Err(self.error(format!("Unknown escape `\\{}`", c))),
+            None => Err(self.error("Unterminated string".to_string())),
+        }
+    }
+
+    pub(crate) fn string(&mut self) -> Result<String, SexpError> {
+        self.expect('"')?;
+        let mut value = String::new();
+        loop {
+            match self.bump() {
+                Some('"') => return Ok(value),
+                Some('\\') => value.push(self.escape()?),
+                Some(c) => value.push(c),
+                None => return Err(self.error("Unterminated string".to_string())),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tree() -> Node {
+        let mut node = Node::new(3, "Let", Value::None);
+        node.add_child(Node::new(1, "\"=\"", Value::String("=".to_string())));
+        node.add_child(Node::new(2, "WS", Value::String(" \n\t\"\\".to_string())));
+        node
+    }
+
+    #[test]
+    fn test_values() {
+        let map = [("k".to_string(), Value::from(-3)), ("k 2".to_string(), Value::from(1e20))].into();
+        let values = [
+            Value::from(10),
+            Value::from(-0.5),
+            Value::from(f64::NEG_INFINITY),
+            Value::from(false),
+            Value::from('\''),
+            Value::from('"'),
+            Value::from('\n'),
+            Value::List(vec![Value::from("a]"), Value::List(vec![]), Value::from('x')]),
+            Value::Map(map),
+        ];
+        for value in values {
+            let node = Node::new(1, "N", value);
+            let text = to_sexp(&node, true);
+            assert_eq!(from_sexp(&text).unwrap(), node, "{}", text);
+        }
+        assert_eq!(to_sexp(&Node::new(1, "N", Value::from(2.0)), false), "(N 2.0)");
+        assert_eq!(to_sexp(&Node::new(1, "N", Value::from('\'')), false), "(N '\\'')");
+        assert!(from_sexp("(N 1x)").is_err());
+    }
+
+    #[test]
+    fn test_to_sexp() {
+        assert_eq!(to_sexp(&tree(), false), "(Let\n  (\"\\\"=\\\"\" \"=\")\n  (WS \" \\n\\t\\\"\\\\\"))");
+        assert_eq!(to_sexp(&tree(), true).lines().next(), Some("(Let #3"));
+    }
+
+    #[test]
+    fn test_from_sexp() {
+        let node = tree();
+        assert_eq!(from_sexp(&to_sexp(&node, true)).unwrap(), node);
+        let node = from_sexp("; comment\n(Let (WS \" \") ; trailing\n)").unwrap();
+        assert_eq!(node.children[0].value.as_str(), Some(" "));
+        assert_eq!(node.kind, 0);
+
+        let error = from_sexp("(Let\n  (WS \"x\"").unwrap_err();
+        assert_eq!(error.to_string(), "Expected `)` but reached the end at 2:10");
+        assert!(from_sexp("(A #x)").is_err());
+    }
+}
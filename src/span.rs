@@ -0,0 +1,65 @@
+use std::fmt::Display;
+
+use crate::token::TokenData;
+
+/// a region of the source text
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    /// byte offset of the first character
+    pub start: usize,
+    /// byte offset after the last character
+    pub end: usize,
+    /// line of the first character, starting from 1
+    pub line: usize,
+    /// column of the first character, starting from 1
+    pub column: usize,
+    /// which file the span belongs to when several are involved, 0 for the main one
+    pub source: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize, line: usize, column: usize) -> Self {
+        Self { start, end, line, column, source: 0 }
+    }
+
+    pub fn with_source(mut self, source: usize) -> Self {
+        self.source = source;
+        self
+    }
+
+    pub fn from_token(token: &TokenData) -> Self {
+        Self::new(token.span.0, token.span.1, token.location.0, token.location.1)
+    }
+
+    /// smallest span covering both `self` and `other`
+    pub fn merge(self, other: Span) -> Span {
+        let first = if other.start < self.start { other } else { self };
+        Span::new(first.start, self.end.max(other.end), first.line, first.column).with_source(first.source)
+    }
+
+    /// default spans point nowhere, real ones start at line 1
+    pub fn is_known(&self) -> bool {
+        self.line > 0
+    }
+
+    pub fn len(&self) -> usize {
+        self.end - self.start
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl From<&TokenData> for Span {
+    fn from(token: &TokenData) -> Self {
+        Self::from_token(token)
+    }
+}
+
+impl Display for Span {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.line, self.column)
+    }
+}
@@ -2,12 +2,7 @@ use std::fmt::Display;
 
 use regex;
 
-const RESET: &str = "\x1b[0m";
-const YELLOW: &str = "\x1b[33m";
-const CYAN: &str = "\x1b[36m";
-const BLUE: &str = "\x1b[34m";
-const VALUE_DECOR: &str = "\x1b[31;3m";
-const GREEN: &str = "\x1b[32m";
+use crate::printer::{Printable, Printer};
 
 #[derive(Debug, Clone)]
 pub struct Token {
@@ -28,8 +23,8 @@ impl Token {
     pub fn new(label: &str, id: u32, token: TokenValue) -> Self {
         Self {
             label: label.to_string(),
-            id: id,
-            token: token,
+            id,
+            token,
         }
     }
 
@@ -64,7 +59,7 @@ impl Token {
     /// takes a string and return an index of last matched character  
     /// if not exist returns None
     pub fn check(&self, text: &str) -> Option<usize> {
-        if text.len() == 0 {
+        if text.is_empty() {
             return None;
         }
 
@@ -96,7 +91,7 @@ impl Token {
                     if caps.start() != 0 {
                         return None;
                     }
-                    Some(caps.end() - 1)
+                    caps.end().checked_sub(1)
                 } else {
                     None
                 }
@@ -106,7 +101,7 @@ impl Token {
                     if caps.start() != 0 {
                         return None;
                     }
-                    Some(caps.end() - 1)
+                    caps.end().checked_sub(1)
                 } else {
                     None
                 }
@@ -117,19 +112,7 @@ impl Token {
 
 impl Display for Token {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{GREEN}{}{RESET}\n", self.label)?;
-        write!(f, "└┬─ {VALUE_DECOR}id:{RESET} {YELLOW}{}{RESET}\n", self.id)?;
-        write!(f, " └─ {CYAN}token{RESET}\n")?;
-        match &self.token {
-            TokenValue::Lit(lit) => write!(f, "    └─ {VALUE_DECOR}lit:{RESET} {BLUE}{}{RESET}", lit)?,
-            TokenValue::Regex(regex) => write!(f, "    └─ {VALUE_DECOR}regex:{RESET} {BLUE}{}{RESET}", regex.as_str())?,
-            TokenValue::URegex(regex) => write!(f, "    └─ {VALUE_DECOR}uregex:{RESET} {BLUE}{}{RESET}", regex.as_str())?,
-            TokenValue::Range(start, end) => {
-                write!(f, "    ├─ {VALUE_DECOR}start:{RESET} {BLUE}{}{RESET}\n", start)?;
-                write!(f, "    └─ {VALUE_DECOR}end:{RESET} {BLUE}{}{RESET}", end)?;
-            }
-        }
-        Ok(())
+        self.print(&Printer::default(), f, 0)
     }
 }
 
@@ -139,21 +122,21 @@ pub struct TokenData {
     pub value: String,
     pub label: String,
 
-    /// (start, end)
+    /// (line, column)
     pub location: (usize, usize),
 
-    /// (line, column)
+    /// (start, end)
     pub span: (usize, usize),
 }
 
 impl TokenData {
     pub fn new(kind: u32, value: String, label: String, location: (usize, usize), span: (usize, usize)) -> Self {
         Self {
-            kind: kind,
-            label: label,
-            value: value,
-            location: location,
-            span: span,
+            kind,
+            label,
+            value,
+            location,
+            span,
         }
     }
 
@@ -180,15 +163,7 @@ impl TokenExt for String {
 
 impl Display for TokenData {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        write!(f, "{GREEN}{}{RESET}\n", self.label)?;
-        write!(f, "└┬─ {VALUE_DECOR}value:{RESET} {BLUE}{}{RESET}\n", self.value)?;
-        write!(f, " ├─ {CYAN}location{RESET}\n")?;
-        write!(f, " │  ├─ {VALUE_DECOR}line:{RESET} {YELLOW}{}{RESET}\n", self.location.0)?;
-        write!(f, " │  └─ {VALUE_DECOR}column:{RESET} {YELLOW}{}{RESET}\n", self.location.1)?;
-        write!(f, " └─ {CYAN}span{RESET}\n")?;
-        write!(f, "    ├─ {VALUE_DECOR}start:{RESET} {YELLOW}{}{RESET}\n", self.span.0)?;
-        write!(f, "    └─ {VALUE_DECOR}end:{RESET} {YELLOW}{}{RESET}\n", self.span.1)?;
-        Ok(())
+        self.print(&Printer::default(), f, 0)
     }
 }
 
@@ -1,6 +1,9 @@
-use std::{borrow::BorrowMut, cell::{Ref, RefCell}, collections::HashMap, fmt::{format, Display}, ops::ShrAssign, rc::Rc, sync::{Arc, Mutex, RwLock}};
+use std::{cell::RefCell, collections::HashMap, fmt::Display, rc::Rc, sync::{Arc, Mutex, RwLock}};
 
-use crate::ast::Node;
+use crate::{
+    ast::Node,
+    printer::{Printable, Printer},
+};
 
 #[derive(Debug, Clone)]
 pub enum VisitorResult {
@@ -31,46 +34,9 @@ impl VisitorResult {
         }
     }
 
+    /// writes the result as a tree with the default printer, `depth` levels deep
     pub fn display(&self, f: &mut std::fmt::Formatter<'_>, depth: usize) -> std::fmt::Result {
-        let line_color = format!("\x1b[38;2;{};{};{}m", 120, 120, 120);
-        let number_color = format!("\x1b[38;2;{};{};{}m", 240, 140, 30);
-        let label_color = format!("\x1b[38;2;{};{};{}m", 200, 30, 200);
-        let field_color = format!("\x1b[38;2;{};{};{}m", 150, 150, 170);
-        let string_color = format!("\x1b[38;2;{};{};{}m", 60, 180, 100);
-        let none_color = format!("\x1b[38;2;{};{};{}m", 255, 100, 100);
-        let stop = "\x1b[0m";
-
-        let space = " ".repeat(depth * 2);
-        match self {
-            VisitorResult::Compound(s) => {
-                write!(f, "{label_color}{}{stop}\n", "compound")?;
-                for (i, child) in s.iter().enumerate() {
-                    match child {
-                        VisitorResult::Tagged(_, _) => write!(f, "{space}{line_color}─{stop} ")?,
-                        VisitorResult::Compound(_) => write!(f, "{space}{line_color}─{stop} ")?,
-                        _ => {
-                            if i < s.len() - 1 {
-                                write!(f, "{space}{line_color}├─{stop} ")?;
-                            } else {
-                                write!(f, "{space}{line_color}└─{stop} ")?;
-                            }
-                        }
-                    }
-                    child.display(f, depth + 2)?;
-                }
-            }
-            VisitorResult::String(s) => write!(f, "{field_color}string:{stop} {string_color}{}{stop}\n", s)?,
-            VisitorResult::Integer(s) => write!(f, "{field_color}integer:{stop} {number_color}{}{stop}\n", s)?,
-            VisitorResult::Number(s) => write!(f, "{field_color}number:{stop} {number_color}{}{stop}\n", s)?,
-            VisitorResult::Tagged(s, value) => {
-                write!(f, "{label_color}{}{stop}\n", s)?;
-                write!(f, "{space}{line_color}└─{stop} ")?;
-                value.display(f, depth + 2)?;
-            }
-            VisitorResult::None => write!(f, "{none_color}none{stop}\n")?,
-        }
-
-        Ok(())
+        self.print(&Printer::default(), f, depth)
     }
 }
 